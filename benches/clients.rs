@@ -0,0 +1,76 @@
+//! Compares checks on one cached HTTP client, which keeps its connections alive,
+//! with a new client per check (`connection = "fresh"`), against a local server.
+//!
+//! Run with `cargo bench --bench clients`.
+
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Checks per connection mode
+const CHECKS: u32 = 500;
+
+/// HTTP server on a random local port that keeps connections alive
+async fn spawn_keep_alive_server() -> std::io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = vec![0; 4096];
+                while let Ok(len) = stream.read(&mut request).await {
+                    if len == 0 {
+                        return;
+                    }
+                    let reply = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                    if stream.write_all(reply).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    Ok(format!("http://{addr}/health"))
+}
+
+fn client() -> reqwest::Result<Client> {
+    Client::builder().timeout(Duration::from_secs(10)).build()
+}
+
+/// Send `CHECKS` requests, each on the client `client_for` returns, and return the
+/// total time taken
+async fn run(
+    url: &str,
+    mut client_for: impl FnMut() -> reqwest::Result<Client>,
+) -> reqwest::Result<Duration> {
+    let start = Instant::now();
+    for _ in 0..CHECKS {
+        client_for()?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+    }
+    Ok(start.elapsed())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = spawn_keep_alive_server().await?;
+
+    let fresh = run(&url, client).await?;
+    let cached_client = client()?;
+    let cached = run(&url, || Ok(cached_client.clone())).await?;
+
+    for (mode, elapsed) in [("fresh", fresh), ("cached", cached)] {
+        println!(
+            "{mode:>6}: {CHECKS} checks in {elapsed:?}, {:?} per check",
+            elapsed / CHECKS
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1 @@
+ALTER TABLE uptime_events DROP COLUMN IF EXISTS status;
@@ -0,0 +1,3 @@
+-- Tri-state check status: 'up', 'degraded' or 'down'.
+-- Older rows have no status and fall back to the success flag.
+ALTER TABLE uptime_events ADD COLUMN status text;
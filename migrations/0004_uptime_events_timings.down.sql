@@ -0,0 +1,6 @@
+ALTER TABLE uptime_events
+    DROP COLUMN IF EXISTS dns_ms,
+    DROP COLUMN IF EXISTS connect_ms,
+    DROP COLUMN IF EXISTS tls_ms,
+    DROP COLUMN IF EXISTS ttfb_ms,
+    DROP COLUMN IF EXISTS download_ms;
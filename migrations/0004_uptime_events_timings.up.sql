@@ -0,0 +1,8 @@
+-- Per-phase timing breakdown of HTTP checks, in milliseconds.
+-- NULL for other check types and for phases that did not happen (e.g. TLS over plain HTTP).
+ALTER TABLE uptime_events
+    ADD COLUMN dns_ms int,
+    ADD COLUMN connect_ms int,
+    ADD COLUMN tls_ms int,
+    ADD COLUMN ttfb_ms int,
+    ADD COLUMN download_ms int;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS content_changes;
+DROP TABLE IF EXISTS content_baselines;
@@ -0,0 +1,17 @@
+-- Last seen response body of endpoints with content_hash, after removing ignored regions
+CREATE TABLE content_baselines (
+    endpoint_id text PRIMARY KEY,
+    hash text NOT NULL,
+    body text NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+-- Detected content changes with a short diff of the changed lines
+CREATE TABLE content_changes (
+    endpoint_id text NOT NULL,
+    ts timestamptz NOT NULL,
+    old_hash text NOT NULL,
+    new_hash text NOT NULL,
+    diff text NOT NULL,
+    PRIMARY KEY (endpoint_id, ts)
+);
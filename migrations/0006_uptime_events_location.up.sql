@@ -0,0 +1,3 @@
+-- Location of the probe agent that ran the check.
+-- NULL for checks run by the central instance itself.
+ALTER TABLE uptime_events ADD COLUMN location text;
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS uptime_events_endpoint_ts_location_key;
+
+-- Agent rows would collide with the central instance's rows in the old key
+DELETE FROM uptime_events
+WHERE location IS NOT NULL;
+
+ALTER TABLE uptime_events ADD PRIMARY KEY (endpoint_id, ts);
@@ -0,0 +1,6 @@
+-- Agents report checks at the same timestamps as the central instance, so the
+-- location has to be part of the key. NULL (the central instance) counts as one
+-- location, so its rows stay unique per timestamp.
+ALTER TABLE uptime_events DROP CONSTRAINT uptime_events_pkey;
+
+CREATE UNIQUE INDEX uptime_events_endpoint_ts_location_key ON uptime_events (endpoint_id, ts, location) NULLS NOT DISTINCT;
@@ -0,0 +1 @@
+COMMENT ON COLUMN uptime_events.status IS NULL;
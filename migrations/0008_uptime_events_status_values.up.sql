@@ -0,0 +1,4 @@
+-- Besides 'up', 'degraded' and 'down' (see 0003), status holds 'unreachable' for
+-- checks skipped while a dependency is down and 'partial_outage' for endpoints
+-- down from fewer locations than their quorum.
+COMMENT ON COLUMN uptime_events.status IS 'Check status: up, degraded, down, unreachable or partial_outage. NULL for rows recorded before the column existed, which fall back to success.';
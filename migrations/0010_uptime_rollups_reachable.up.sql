@@ -0,0 +1,51 @@
+-- Checks skipped while a dependency is down say nothing about the endpoint, so
+-- the rollups leave them out of the uptime
+SELECT remove_continuous_aggregate_policy('uptime_events_daily', if_exists => true);
+SELECT remove_continuous_aggregate_policy('uptime_events_hourly', if_exists => true);
+
+DROP MATERIALIZED VIEW IF EXISTS uptime_events_daily;
+DROP MATERIALIZED VIEW IF EXISTS uptime_events_hourly;
+
+CREATE MATERIALIZED VIEW uptime_events_hourly WITH (timescaledb.continuous) AS
+SELECT
+    endpoint_id,
+    time_bucket (INTERVAL '1 hour', ts) AS hour,
+    count(*) AS checks,
+    sum(success::int) AS successes,
+    avg(latency_ms) AS avg_latency_ms,
+    max(latency_ms) AS max_latency_ms,
+    percentile_cont(0.95) WITHIN GROUP (ORDER BY latency_ms) AS p95_latency_ms,
+    percentile_cont(0.99) WITHIN GROUP (ORDER BY latency_ms) AS p99_latency_ms
+FROM
+    uptime_events
+WHERE
+    location IS NULL
+    AND status IS DISTINCT FROM 'unreachable'
+GROUP BY
+    endpoint_id,
+    hour WITH NO DATA;
+
+SELECT
+    add_continuous_aggregate_policy ('uptime_events_hourly', start_offset => INTERVAL '30 days', end_offset => INTERVAL '1 hour', schedule_interval => INTERVAL '5 minutes');
+
+CREATE MATERIALIZED VIEW uptime_events_daily WITH (timescaledb.continuous) AS
+SELECT
+    endpoint_id,
+    time_bucket (INTERVAL '1 day', ts) AS day,
+    count(*) AS checks,
+    sum(success::int) AS successes,
+    avg(latency_ms) AS avg_latency_ms,
+    max(latency_ms) AS max_latency_ms,
+    percentile_cont(0.95) WITHIN GROUP (ORDER BY latency_ms) AS p95_latency_ms,
+    percentile_cont(0.99) WITHIN GROUP (ORDER BY latency_ms) AS p99_latency_ms
+FROM
+    uptime_events
+WHERE
+    location IS NULL
+    AND status IS DISTINCT FROM 'unreachable'
+GROUP BY
+    endpoint_id,
+    day WITH NO DATA;
+
+SELECT
+    add_continuous_aggregate_policy ('uptime_events_daily', start_offset => INTERVAL '365 days', end_offset => INTERVAL '1 day', schedule_interval => INTERVAL '1 hour');
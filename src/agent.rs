@@ -0,0 +1,425 @@
+//! Probe agents: instances that check endpoints from another location and report
+//! their results to a central instance, which stores them tagged with the location.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::checker::{
+    CheckResult, CheckResultsState, CheckStatus, ErrorType, LocationResults, LocationStatus,
+    base_result,
+};
+use crate::config::{AgentAuth, AgentConfig, Endpoint};
+
+/// Path of the central instance's API for agent reports, below its base path
+pub const RESULTS_PATH: &str = "/api/agent/results";
+
+/// How often an agent sends the results of its latest checks
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Timeout of a single report request
+const REPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Location shown for this instance's own checks when `[server] location` is unset
+pub const DEFAULT_LOCATION: &str = "local";
+
+/// Results an agent sends to the central instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub location: String,
+    pub results: Vec<ReportedResult>,
+}
+
+/// Outcome of one check run by an agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportedResult {
+    pub name: String,
+    pub checked_at: DateTime<Utc>,
+    pub status: CheckStatus,
+    pub status_code: Option<u16>,
+    pub response_time_ms: Option<u64>,
+    pub error: Option<String>,
+    pub error_type: Option<ErrorType>,
+}
+
+impl From<&CheckResult> for ReportedResult {
+    fn from(result: &CheckResult) -> Self {
+        ReportedResult {
+            name: result.name.clone(),
+            checked_at: result.checked_at,
+            status: result.status,
+            status_code: result.status_code,
+            response_time_ms: result.response_time_ms,
+            error: result.error.clone(),
+            error_type: result.error_type.clone(),
+        }
+    }
+}
+
+/// Why a report was rejected
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReportError {
+    /// Missing or wrong token for the report's location
+    Unauthorized,
+}
+
+/// Spawn the task that sends new results from `state` to the central instance.
+/// Results that fail to send are sent again with the next report.
+pub fn spawn_reporter(agent: AgentConfig, state: CheckResultsState) {
+    let url = format!("{}{RESULTS_PATH}", agent.server.trim_end_matches('/'));
+    let token = agent.resolved_token();
+    let client = match Client::builder().timeout(REPORT_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to build client for agent reports");
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        // Time of the latest check sent for each endpoint
+        let mut sent: HashMap<String, DateTime<Utc>> = HashMap::new();
+        let mut ticker = tokio::time::interval(REPORT_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+
+            let report = Report {
+                location: agent.location.clone(),
+                results: unsent_results(&*state.read().await, &sent),
+            };
+            if report.results.is_empty() {
+                continue;
+            }
+
+            let response = client
+                .post(&url)
+                .bearer_auth(&token)
+                .json(&report)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            match response {
+                Ok(_) => {
+                    tracing::debug!(results = report.results.len(), "sent agent report");
+                    for result in report.results {
+                        sent.insert(result.name, result.checked_at);
+                    }
+                }
+                Err(e) => tracing::warn!(url = %url, error = %e, "failed to send agent report"),
+            }
+        }
+    });
+}
+
+/// Results checked since the last one sent for their endpoint, in name order
+fn unsent_results(
+    results: &HashMap<String, CheckResult>,
+    sent: &HashMap<String, DateTime<Utc>>,
+) -> Vec<ReportedResult> {
+    let mut unsent: Vec<_> = results
+        .values()
+        .filter(|result| {
+            sent.get(&result.name)
+                .is_none_or(|&last| result.checked_at > last)
+        })
+        .map(ReportedResult::from)
+        .collect();
+    unsent.sort_by(|a, b| a.name.cmp(&b.name));
+    unsent
+}
+
+/// Check the bearer token of a report against the token configured for its location
+pub fn authorize(
+    agents: &HashMap<String, AgentAuth>,
+    headers: &HeaderMap,
+    location: &str,
+) -> Result<(), ReportError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(ReportError::Unauthorized)?;
+    let expected = agents
+        .get(location)
+        .map(AgentAuth::resolved_token)
+        .ok_or(ReportError::Unauthorized)?;
+
+    if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(ReportError::Unauthorized)
+    }
+}
+
+/// Compare two byte strings in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Turn a report into results of the configured endpoints, tagged with the agent's
+/// location. Results of endpoints missing from the config are dropped.
+pub fn apply_report(report: Report, endpoints: &HashMap<String, Endpoint>) -> Vec<CheckResult> {
+    report
+        .results
+        .into_iter()
+        .filter_map(|reported| {
+            let Some(endpoint) = endpoints.get(&reported.name) else {
+                tracing::debug!(
+                    endpoint = %reported.name,
+                    location = %report.location,
+                    "ignoring agent result for unknown endpoint"
+                );
+                return None;
+            };
+            Some(CheckResult {
+                status: reported.status,
+                status_code: reported.status_code,
+                response_time_ms: reported.response_time_ms,
+                error: reported.error,
+                error_type: reported.error_type,
+                checked_at: reported.checked_at,
+                location: Some(report.location.clone()),
+                ..base_result(&reported.name, endpoint)
+            })
+        })
+        .collect()
+}
+
+/// Latest status of a result at its location, as checked there
+pub fn location_status(result: &CheckResult, location: &str) -> LocationStatus {
+    LocationStatus {
+        location: location.to_string(),
+        status: result.local_status.unwrap_or(result.status),
+        response_time_ms: result.response_time_ms,
+        error: result.error.clone(),
+        checked_at: result.checked_at,
+    }
+}
+
+/// Record the results of agents as the latest status of their locations, keeping a
+/// newer status if reports arrive out of order
+pub async fn store_locations(locations: &LocationResults, results: &[CheckResult]) {
+    let mut locations = locations.write().await;
+    for result in results {
+        let Some(ref location) = result.location else {
+            continue;
+        };
+        let statuses = locations.entry(result.name.clone()).or_default();
+        if statuses
+            .get(location)
+            .is_none_or(|latest| latest.checked_at <= result.checked_at)
+        {
+            statuses.insert(location.clone(), location_status(result, location));
+        }
+    }
+}
+
+/// Fill in the status of every location for results that agents report on, this
+/// instance's own check first
+pub fn attach_locations(
+    results: &mut [CheckResult],
+    locations: &HashMap<String, BTreeMap<String, LocationStatus>>,
+    local_location: &str,
+) {
+    for result in results {
+        let Some(remote) = locations
+            .get(&result.name)
+            .filter(|remote| !remote.is_empty())
+        else {
+            continue;
+        };
+        result.locations = std::iter::once(location_status(result, local_location))
+            .chain(remote.values().cloned())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn make_endpoint() -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://api.example.com\"").unwrap();
+        endpoint.group = Some("backend".to_string());
+        endpoint
+    }
+
+    fn make_result(name: &str, status: CheckStatus, checked_at: &str) -> CheckResult {
+        let mut result = base_result(name, &make_endpoint());
+        result.status = status;
+        result.checked_at = checked_at.parse().unwrap();
+        result
+    }
+
+    fn reported(name: &str, status: CheckStatus, checked_at: &str) -> ReportedResult {
+        ReportedResult {
+            name: name.to_string(),
+            checked_at: checked_at.parse().unwrap(),
+            status,
+            status_code: None,
+            response_time_ms: Some(42),
+            error: None,
+            error_type: None,
+        }
+    }
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+        headers
+    }
+
+    fn agents() -> HashMap<String, AgentAuth> {
+        HashMap::from([(
+            "eu-west".to_string(),
+            AgentAuth {
+                token: "secret".to_string(),
+            },
+        )])
+    }
+
+    #[test]
+    fn authorize_checks_the_token_of_the_location() {
+        let agents = agents();
+        assert_eq!(
+            authorize(&agents, &headers("Bearer secret"), "eu-west"),
+            Ok(())
+        );
+        assert_eq!(
+            authorize(&agents, &headers("Bearer wrong"), "eu-west"),
+            Err(ReportError::Unauthorized)
+        );
+        assert_eq!(
+            authorize(&agents, &headers("secret"), "eu-west"),
+            Err(ReportError::Unauthorized)
+        );
+        // A valid token doesn't allow reporting for another location
+        assert_eq!(
+            authorize(&agents, &headers("Bearer secret"), "us-east"),
+            Err(ReportError::Unauthorized)
+        );
+        assert_eq!(
+            authorize(&agents, &HeaderMap::new(), "eu-west"),
+            Err(ReportError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn unsent_results_skips_results_already_sent() {
+        let results = HashMap::from([
+            (
+                "api".to_string(),
+                make_result("api", CheckStatus::Up, "2026-03-02T10:00:00Z"),
+            ),
+            (
+                "db".to_string(),
+                make_result("db", CheckStatus::Down, "2026-03-02T10:00:05Z"),
+            ),
+        ]);
+        let sent = HashMap::from([("api".to_string(), "2026-03-02T10:00:00Z".parse().unwrap())]);
+
+        let unsent = unsent_results(&results, &sent);
+
+        assert_eq!(unsent.len(), 1);
+        assert_eq!(unsent[0].name, "db");
+        assert_eq!(unsent[0].status, CheckStatus::Down);
+    }
+
+    #[test]
+    fn apply_report_tags_configured_endpoints_with_the_location() {
+        // Endpoints count before this instance has checked them itself
+        let endpoints = HashMap::from([("api".to_string(), make_endpoint())]);
+        let report = Report {
+            location: "eu-west".to_string(),
+            results: vec![
+                reported("api", CheckStatus::Down, "2026-03-02T10:00:03Z"),
+                reported("unknown", CheckStatus::Up, "2026-03-02T10:00:03Z"),
+            ],
+        };
+
+        let results = apply_report(report, &endpoints);
+
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.name, "api");
+        assert_eq!(result.group.as_deref(), Some("backend"));
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(result.response_time_ms, Some(42));
+        assert_eq!(result.location.as_deref(), Some("eu-west"));
+        assert_eq!(
+            result.checked_at,
+            "2026-03-02T10:00:03Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn store_locations_keeps_the_newest_status() {
+        let locations = LocationResults::default();
+        let mut newer = make_result("api", CheckStatus::Down, "2026-03-02T10:00:10Z");
+        newer.location = Some("eu-west".to_string());
+        let mut older = make_result("api", CheckStatus::Up, "2026-03-02T10:00:00Z");
+        older.location = Some("eu-west".to_string());
+        let local = make_result("api", CheckStatus::Up, "2026-03-02T10:00:20Z");
+
+        store_locations(&locations, &[newer, older, local]).await;
+
+        let locations = locations.read().await;
+        assert_eq!(locations["api"].len(), 1);
+        assert_eq!(locations["api"]["eu-west"].status, CheckStatus::Down);
+    }
+
+    #[test]
+    fn attach_locations_lists_this_instance_first() {
+        let mut results = vec![
+            make_result("api", CheckStatus::Up, "2026-03-02T10:00:00Z"),
+            make_result("db", CheckStatus::Up, "2026-03-02T10:00:00Z"),
+        ];
+        let remote = make_result("api", CheckStatus::Down, "2026-03-02T10:00:03Z");
+        let locations = HashMap::from([(
+            "api".to_string(),
+            BTreeMap::from([
+                ("us-east".to_string(), location_status(&remote, "us-east")),
+                ("eu-west".to_string(), location_status(&remote, "eu-west")),
+            ]),
+        )]);
+
+        attach_locations(&mut results, &locations, "hq");
+
+        let names: Vec<_> = results[0]
+            .locations
+            .iter()
+            .map(|l| l.location.as_str())
+            .collect();
+        assert_eq!(names, ["hq", "eu-west", "us-east"]);
+        assert_eq!(results[0].locations[0].status, CheckStatus::Up);
+        assert_eq!(results[0].locations[1].status, CheckStatus::Down);
+        assert!(results[1].locations.is_empty());
+    }
+
+    #[test]
+    fn report_round_trips_through_json() {
+        let report = Report {
+            location: "eu-west".to_string(),
+            results: vec![ReportedResult {
+                error: Some("connection refused".to_string()),
+                error_type: Some(ErrorType::TcpRefused),
+                ..reported("api", CheckStatus::Down, "2026-03-02T10:00:03Z")
+            }],
+        };
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""status":"down""#), "{json}");
+        assert!(json.contains(r#""error_type":"tcp_refused""#), "{json}");
+        let parsed: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.results, report.results);
+    }
+}
@@ -1,21 +1,58 @@
-use std::{collections::HashMap, net::ToSocketAddrs, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
 
-use hickory_resolver::{Resolver, config::ResolverConfig, name_server::TokioConnectionProvider};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use reqwest::{Certificate, Client, ClientBuilder, Identity, Response, Url, redirect};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tokio::{
     io::AsyncWriteExt,
-    net::TcpStream,
+    net::{TcpSocket, TcpStream},
     sync::{RwLock, mpsc},
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::config::{CheckType, Config, Endpoint};
+use crate::config::{
+    AuthConfig, CheckType, Config, Endpoint, ProxyConfig, client_identity_pem, substitute_env_vars,
+};
 use crate::db;
 
+mod addresses;
+mod alert;
+mod auth;
+mod broker;
+mod clients;
+mod content;
+mod dependencies;
+mod domain;
+mod flow;
+mod headers;
+mod limits;
+mod mail;
+mod proxy;
+mod quorum;
+pub mod registry;
+mod schedule;
+mod script;
+mod ssh;
+mod timing;
+mod tls;
+mod udp;
+
 /// Shared state containing cached check results
 pub type CheckResultsState = Arc<RwLock<HashMap<String, CheckResult>>>;
 
+/// Latest result of every endpoint per agent location (endpoint name -> location -> status)
+pub type LocationResults = Arc<RwLock<HashMap<String, BTreeMap<String, LocationStatus>>>>;
+
+/// Endpoints of the current config, updated when it is reloaded
+pub type EndpointsState = Arc<RwLock<HashMap<String, Endpoint>>>;
+
 /// Shared state for active endpoint tasks (name -> cancellation token)
 type ActiveTasks = Arc<RwLock<HashMap<String, CancellationToken>>>;
 
@@ -23,7 +60,8 @@ type ActiveTasks = Arc<RwLock<HashMap<String, CancellationToken>>>;
 pub type ReloadTrigger = mpsc::Sender<()>;
 
 /// Error type classification for failed checks
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ErrorType {
     Timeout,
     Dns,
@@ -33,7 +71,37 @@ pub enum ErrorType {
     TcpRefused,
     DnsNxdomain,
     DnsMismatch,
+    BodyMismatch,
+    ExtractFailed,
+    AuthToken,
     ClientBuild,
+    Redirect,
+    Proxy,
+    HeaderMismatch,
+    SlowResponse,
+    DomainLookup,
+    DomainExpiring,
+    ReplyMismatch,
+    PortUnreachable,
+    NtpInvalid,
+    MailGreeting,
+    MailCapabilities,
+    #[serde(rename = "starttls")]
+    StartTls,
+    MailLogin,
+    BrokerAuth,
+    BrokerError,
+    ProbeLost,
+    ProbeSlow,
+    /// The SSH server presented a host key other than the pinned `host_key`,
+    /// which may mean the server was replaced or the connection intercepted
+    HostKeyMismatch,
+    SshAuth,
+    SshCommand,
+    /// The script failed to run or returned an invalid result
+    ScriptError,
+    /// The script reported the endpoint as down
+    ScriptDown,
     Unknown,
 }
 
@@ -48,7 +116,32 @@ impl ErrorType {
             ErrorType::TcpRefused => "tcp_refused",
             ErrorType::DnsNxdomain => "dns_nxdomain",
             ErrorType::DnsMismatch => "dns_mismatch",
+            ErrorType::BodyMismatch => "body_mismatch",
+            ErrorType::ExtractFailed => "extract_failed",
+            ErrorType::AuthToken => "auth_token",
             ErrorType::ClientBuild => "client_build",
+            ErrorType::Redirect => "redirect",
+            ErrorType::Proxy => "proxy",
+            ErrorType::HeaderMismatch => "header_mismatch",
+            ErrorType::SlowResponse => "slow_response",
+            ErrorType::DomainLookup => "domain_lookup",
+            ErrorType::DomainExpiring => "domain_expiring",
+            ErrorType::ReplyMismatch => "reply_mismatch",
+            ErrorType::PortUnreachable => "port_unreachable",
+            ErrorType::NtpInvalid => "ntp_invalid",
+            ErrorType::MailGreeting => "mail_greeting",
+            ErrorType::MailCapabilities => "mail_capabilities",
+            ErrorType::StartTls => "starttls",
+            ErrorType::MailLogin => "mail_login",
+            ErrorType::BrokerAuth => "broker_auth",
+            ErrorType::BrokerError => "broker_error",
+            ErrorType::ProbeLost => "probe_lost",
+            ErrorType::ProbeSlow => "probe_slow",
+            ErrorType::HostKeyMismatch => "host_key_mismatch",
+            ErrorType::SshAuth => "ssh_auth",
+            ErrorType::SshCommand => "ssh_command",
+            ErrorType::ScriptError => "script_error",
+            ErrorType::ScriptDown => "script_down",
             ErrorType::Unknown => "unknown",
         }
     }
@@ -63,17 +156,221 @@ pub struct CheckResult {
     pub tags: Vec<String>,
     pub addr: String,
     pub check_type: CheckType,
-    pub is_up: bool,
+    pub status: CheckStatus,
     pub status_code: Option<u16>,
     pub response_time_ms: Option<u64>,
     pub error: Option<String>,
     pub error_type: Option<ErrorType>,
+    /// Per-step results (for flow checks)
+    pub steps: Vec<StepResult>,
+    /// URLs redirected to, in order (for http checks)
+    pub redirects: Vec<String>,
+    /// Findings that degrade an up check, such as missing security headers
+    pub warnings: Vec<String>,
+    /// Per-phase timing breakdown (for http checks)
+    pub timings: Option<HttpTimings>,
+    /// Per-address results when checking specific or all resolved addresses
+    pub addresses: Vec<AddressResult>,
+    /// Normalized response body and its hash (for http checks with `content_hash`)
+    pub content: Option<ContentSample>,
+    /// Registration data (for domain checks)
+    pub domain: Option<DomainInfo>,
+    /// Offset of the local clock to the server's (for NTP checks)
+    pub clock_offset_ms: Option<i64>,
+    /// Certificate presented by the server (for mail and broker checks using TLS)
+    pub certificate: Option<CertificateInfo>,
+    /// Time from publishing a probe message to consuming it (for broker checks)
+    pub round_trip_ms: Option<u64>,
+    /// SHA-256 fingerprint of the host key the server presented (for ssh checks)
+    pub host_key: Option<String>,
+    /// Message returned by the script of an up result (for script checks)
+    pub message: Option<String>,
+    /// Custom metrics returned by the script, in name order (for script checks)
+    pub metrics: Vec<(String, f64)>,
+    /// When the endpoint's checker runs the next check
+    pub next_check_at: Option<DateTime<Utc>>,
+    /// When the check started
+    pub checked_at: DateTime<Utc>,
+    /// Location of the agent that ran the check, `None` for checks of this instance
+    pub location: Option<String>,
+    /// Latest status from every location, filled in for the dashboard once agents
+    /// report results for the endpoint
+    pub locations: Vec<LocationStatus>,
+    /// Status of this instance's own check when the quorum across locations
+    /// decided `status` differently
+    pub local_status: Option<CheckStatus>,
+}
+
+/// Latest result of an endpoint from one location
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationStatus {
+    pub location: String,
+    pub status: CheckStatus,
+    pub response_time_ms: Option<u64>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl CheckResult {
+    /// Whether the endpoint responded successfully, possibly degraded
+    pub fn is_up(&self) -> bool {
+        !matches!(self.status, CheckStatus::Down | CheckStatus::Unreachable)
+    }
+
+    /// Whether the endpoint is up but slow or has warnings
+    pub fn is_degraded(&self) -> bool {
+        self.status == CheckStatus::Degraded
+    }
+
+    /// Record a warning, degrading an up result
+    fn degrade(&mut self, warning: String) {
+        self.warnings.push(warning);
+        if self.status == CheckStatus::Up {
+            self.status = CheckStatus::Degraded;
+        }
+    }
+}
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Up,
+    /// Up, but slower than `degraded_after_ms` or with warnings
+    Degraded,
+    Down,
+    /// Outside the endpoint's active hours, so not checked
+    NotMonitored,
+    /// Down while a parent in `depends_on` is down
+    Unreachable,
+    /// Down from fewer locations than the endpoint's `quorum`
+    PartialOutage,
+}
+
+impl CheckStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CheckStatus::Up => "up",
+            CheckStatus::Degraded => "degraded",
+            CheckStatus::Down => "down",
+            CheckStatus::NotMonitored => "not_monitored",
+            CheckStatus::Unreachable => "unreachable",
+            CheckStatus::PartialOutage => "partial_outage",
+        }
+    }
+
+    /// Parse a status stored in the database
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "up" => Some(CheckStatus::Up),
+            "degraded" => Some(CheckStatus::Degraded),
+            "down" => Some(CheckStatus::Down),
+            "not_monitored" => Some(CheckStatus::NotMonitored),
+            "unreachable" => Some(CheckStatus::Unreachable),
+            "partial_outage" => Some(CheckStatus::PartialOutage),
+            _ => None,
+        }
+    }
+}
+
+/// Result of a single step of a flow check
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub name: String,
+    pub status_code: Option<u16>,
+    pub response_time_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Result of checking one resolved address of an endpoint
+#[derive(Debug, Clone)]
+pub struct AddressResult {
+    pub addr: IpAddr,
+    pub status: CheckStatus,
+    pub response_time_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Registration data of a domain
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainInfo {
+    pub expires: DateTime<Utc>,
+    pub registrar: Option<String>,
+    /// Protocol the data was retrieved with: "rdap" or "whois"
+    pub source: &'static str,
+}
+
+/// Details of a server certificate
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// DNS names from the subject alternative name extension
+    pub dns_names: Vec<String>,
+}
+
+/// Response body with the `content_hash` ignore regions removed, and its SHA-256 hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentSample {
+    pub hash: String,
+    pub text: String,
+}
+
+/// Change of an endpoint's content between two checks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentChange {
+    pub old_hash: String,
+    pub new_hash: String,
+    /// Changed lines with some context, see `content::diff_snippet`
+    pub diff: String,
+}
+
+/// Time spent in each phase of an HTTP check, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HttpTimings {
+    /// DNS resolution, `None` for IP addresses and proxies that resolve remotely
+    pub dns: Option<u64>,
+    /// TCP connect, including proxy tunnel setup
+    pub connect: Option<u64>,
+    /// TLS handshake, `None` for plain HTTP
+    pub tls: Option<u64>,
+    /// Time from sending the request until the response headers arrived
+    pub ttfb: Option<u64>,
+    /// Reading the response body
+    pub download: Option<u64>,
+}
+
+impl HttpTimings {
+    /// Phase labels and durations in the order they happen
+    pub fn phases(&self) -> [(&'static str, Option<u64>); 5] {
+        [
+            ("DNS", self.dns),
+            ("Connect", self.connect),
+            ("TLS", self.tls),
+            ("TTFB", self.ttfb),
+            ("Download", self.download),
+        ]
+    }
+
+    /// Sum of all recorded phases
+    pub fn total_ms(&self) -> u64 {
+        self.phases().iter().filter_map(|(_, ms)| *ms).sum()
+    }
 }
 
 /// Classify a reqwest error into an `ErrorType`
-fn classify_reqwest_error(e: &reqwest::Error) -> ErrorType {
+fn classify_reqwest_error(e: &reqwest::Error, proxy: Option<&ProxyConfig>) -> ErrorType {
+    let via_proxy = e
+        .url()
+        .and_then(Url::host_str)
+        .is_some_and(|host| proxy::intercepts(proxy, host));
+
     if e.is_timeout() {
         ErrorType::Timeout
+    } else if e.is_connect() && via_proxy {
+        proxy::classify_connect_error(e)
     } else if e.is_connect() {
         // Check for DNS errors in the error chain
         let error_str = e.to_string().to_lowercase();
@@ -93,7 +390,7 @@ fn classify_reqwest_error(e: &reqwest::Error) -> ErrorType {
 }
 
 /// Create a base `CheckResult` with common fields
-fn base_result(name: &str, endpoint: &Endpoint) -> CheckResult {
+pub(crate) fn base_result(name: &str, endpoint: &Endpoint) -> CheckResult {
     CheckResult {
         name: name.to_string(),
         description: endpoint.description.clone(),
@@ -101,18 +398,309 @@ fn base_result(name: &str, endpoint: &Endpoint) -> CheckResult {
         tags: endpoint.tags.clone(),
         addr: endpoint.resolved_addr(),
         check_type: endpoint.check_type.clone(),
-        is_up: false,
+        status: CheckStatus::Down,
         status_code: None,
         response_time_ms: None,
         error: None,
         error_type: None,
+        steps: Vec::new(),
+        redirects: Vec::new(),
+        warnings: Vec::new(),
+        timings: None,
+        addresses: Vec::new(),
+        content: None,
+        domain: None,
+        clock_offset_ms: None,
+        certificate: None,
+        round_trip_ms: None,
+        host_key: None,
+        message: None,
+        metrics: Vec::new(),
+        next_check_at: None,
+        checked_at: Utc::now(),
+        location: None,
+        locations: Vec::new(),
+        local_status: None,
+    }
+}
+
+/// Create an HTTP client builder with the endpoint's timeout, TLS and proxy settings
+fn http_client_builder(endpoint: &Endpoint) -> reqwest::Result<ClientBuilder> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(endpoint.timeout))
+        .danger_accept_invalid_certs(endpoint.skip_tls_verification);
+
+    let tls = &endpoint.tls_files;
+    if let Some(ref bundle) = tls.ca_bundle {
+        for cert in Certificate::from_pem_bundle(bundle)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+        builder = builder.identity(Identity::from_pem(&client_identity_pem(cert, key))?);
+    }
+    if let Some(ref proxy) = endpoint.proxy {
+        builder = builder.proxy(proxy::reqwest_proxy(proxy)?);
+    }
+    if let Some(local_address) = endpoint.local_address {
+        builder = builder.local_address(local_address);
+    }
+
+    Ok(builder)
+}
+
+/// Build the HTTP client for an endpoint and the URL to request with it.
+/// With `pinned`, the endpoint's host connects to that address only.
+async fn http_client(
+    endpoint: &Endpoint,
+    redirect_policy: redirect::Policy,
+    pinned: Option<IpAddr>,
+) -> Result<(Client, String), (ErrorType, String)> {
+    let client_build_error = |e: reqwest::Error| {
+        (
+            ErrorType::ClientBuild,
+            format!("failed to build HTTP client: {e}"),
+        )
+    };
+
+    let builder = http_client_builder(endpoint)
+        .map_err(client_build_error)?
+        .redirect(redirect_policy);
+    let builder = timing::instrument(builder, endpoint).map_err(|e| {
+        (
+            ErrorType::ClientBuild,
+            format!("failed to build HTTP client: {e}"),
+        )
+    })?;
+    let url = endpoint.resolved_addr();
+
+    let (builder, url) = match endpoint.tls_server_name {
+        Some(ref server_name) => {
+            let (builder, url) = override_server_name(builder, &url, server_name, pinned)
+                .await
+                .map_err(|e| (ErrorType::Dns, e))?;
+            (builder, url.to_string())
+        }
+        None => match (pinned, Url::parse(&url).ok()) {
+            (Some(ip), Some(ref parsed)) if let Some(host) = parsed.host_str() => {
+                // The port is taken from the URL
+                (builder.resolve(host, SocketAddr::new(ip, 0)), url)
+            }
+            _ => (builder, url),
+        },
+    };
+
+    let client = builder.build().map_err(client_build_error)?;
+    Ok((client, url))
+}
+
+/// Point the request URL at `server_name` while still connecting to the addresses of
+/// the original host (or `pinned`), so SNI, certificate verification and `Host` use
+/// `server_name`.
+async fn override_server_name(
+    builder: ClientBuilder,
+    url: &str,
+    server_name: &str,
+    pinned: Option<IpAddr>,
+) -> Result<(ClientBuilder, reqwest::Url), String> {
+    let mut url = reqwest::Url::parse(url).map_err(|e| format!("invalid URL '{url}': {e}"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("URL '{url}' has no host"))?
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| format!("URL '{url}' has no port"))?;
+
+    let addrs: Vec<_> = match pinned {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|e| format!("failed to resolve address: {e}"))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("no addresses found for '{host}'"));
+    }
+
+    url.set_host(Some(server_name))
+        .map_err(|e| format!("invalid tls_server_name '{server_name}': {e}"))?;
+
+    Ok((builder.resolve_to_addrs(server_name, &addrs), url))
+}
+
+/// Redirects seen during an HTTP check, recorded by the client's redirect policy
+#[derive(Debug, Default)]
+struct RedirectLog {
+    /// URLs redirected to, in order
+    chain: Vec<Url>,
+    /// Why the last redirect was not followed
+    refused: Option<String>,
+}
+
+type SharedRedirectLog = Arc<Mutex<RedirectLog>>;
+
+tokio::task_local! {
+    /// Redirect log of the check sending a request in the current task. Clients are
+    /// shared between checks, so their redirect policy records into this one.
+    static REDIRECT_LOG: SharedRedirectLog;
+}
+
+/// Path segments (ignoring extensions) that identify a login page
+const LOGIN_PATH_SEGMENTS: &[&str] = &[
+    "login",
+    "signin",
+    "sign-in",
+    "sign_in",
+    "logon",
+    "sso",
+    "authorize",
+];
+
+/// Whether a URL looks like a login page
+fn is_login_url(url: &Url) -> bool {
+    url.path_segments().is_some_and(|mut segments| {
+        segments.any(|segment| {
+            let stem = segment.split('.').next().unwrap_or_default();
+            LOGIN_PATH_SEGMENTS
+                .iter()
+                .any(|login| stem.eq_ignore_ascii_case(login))
+        })
+    })
+}
+
+/// Redirect policy for an HTTP check. Follows up to `follow_redirects` hops and
+/// refuses redirects to login pages (unless expected) or from HTTPS to plain HTTP.
+/// Redirects are recorded in the `REDIRECT_LOG` of the request.
+fn redirect_policy(endpoint: &Endpoint) -> redirect::Policy {
+    let max_hops = endpoint.follow_redirects.max_hops();
+    let expected_final_url = endpoint
+        .expected_final_url
+        .as_deref()
+        .and_then(|url| endpoint.resolve_url(url).ok());
+
+    redirect::Policy::custom(move |attempt| {
+        if max_hops == 0 {
+            return attempt.stop();
+        }
+
+        let url = attempt.url();
+        let refused = if attempt.previous().len() > max_hops {
+            Some(format!("exceeded maximum of {max_hops} redirects"))
+        } else if url.scheme() == "http"
+            && attempt
+                .previous()
+                .first()
+                .is_some_and(|first| first.scheme() == "https")
+        {
+            Some(format!("redirected from HTTPS to non-TLS URL {url}"))
+        } else if is_login_url(url) && expected_final_url.as_ref() != Some(url) {
+            Some(format!("redirected to login page {url}"))
+        } else {
+            None
+        };
+
+        let follow = refused.is_none();
+        // Requests outside a check, such as OAuth2 token requests, are not recorded
+        let _ = REDIRECT_LOG.try_with(|log| {
+            let mut log = log.lock().unwrap_or_else(PoisonError::into_inner);
+            match refused {
+                Some(reason) => log.refused = Some(reason),
+                None => log.chain.push(url.clone()),
+            }
+        });
+        if follow {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    })
+}
+
+/// Check the response against the endpoint's status, redirect and header expectations
+fn evaluate_http_response(
+    endpoint: &Endpoint,
+    response: &Response,
+    refused_redirect: Option<String>,
+) -> Option<(ErrorType, String)> {
+    if let Some(reason) = refused_redirect {
+        return Some((ErrorType::Redirect, reason));
+    }
+
+    let status = response.status().as_u16();
+    if status != endpoint.expected_status {
+        return Some((
+            ErrorType::StatusMismatch,
+            format!(
+                "expected status {}, got {}",
+                endpoint.expected_status, status
+            ),
+        ));
+    }
+
+    if let Some(ref expected) = endpoint.expected_location {
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| response.url().join(value).ok());
+        let expected = endpoint.resolve_url(expected).ok();
+        if location != expected {
+            return Some((
+                ErrorType::Redirect,
+                format!(
+                    "expected Location {}, got {}",
+                    display_url(expected.as_ref()),
+                    display_url(location.as_ref())
+                ),
+            ));
+        }
     }
+
+    if let Some(error) =
+        headers::check_expected_headers(&endpoint.expected_headers, response.headers())
+    {
+        return Some((ErrorType::HeaderMismatch, error));
+    }
+
+    if let Some(ref expected) = endpoint.expected_final_url {
+        let expected = endpoint.resolve_url(expected).ok();
+        if expected.as_ref() != Some(response.url()) {
+            return Some((
+                ErrorType::Redirect,
+                format!(
+                    "expected final URL {}, got {}",
+                    display_url(expected.as_ref()),
+                    response.url()
+                ),
+            ));
+        }
+    }
+
+    None
+}
+
+fn display_url(url: Option<&Url>) -> String {
+    url.map_or_else(|| "none".to_string(), ToString::to_string)
 }
 
 /// Check a single endpoint's availability with retries
 pub async fn check_endpoint(name: &str, endpoint: &Endpoint) -> CheckResult {
     let max_attempts = endpoint.retries + 1;
     let mut last_result = base_result(name, endpoint);
+    if !schedule::is_active(endpoint, Utc::now()) {
+        last_result.status = CheckStatus::NotMonitored;
+        last_result.message = Some("not monitored outside active hours".to_string());
+        return last_result;
+    }
+    let Some(checker) = registry::registry().get(endpoint.check_type.as_str()) else {
+        last_result.error = Some(format!(
+            "no checker is registered for check type '{}'",
+            endpoint.check_type.as_str()
+        ));
+        last_result.error_type = Some(ErrorType::Unknown);
+        return last_result;
+    };
 
     for attempt in 0..max_attempts {
         if attempt > 0 {
@@ -125,13 +713,12 @@ pub async fn check_endpoint(name: &str, endpoint: &Endpoint) -> CheckResult {
             tokio::time::sleep(Duration::from_secs(endpoint.retry_delay)).await;
         }
 
-        last_result = match endpoint.check_type {
-            CheckType::Http => check_http(name, endpoint).await,
-            CheckType::Tcp => check_tcp(name, endpoint).await,
-            CheckType::Dns => check_dns(name, endpoint).await,
-        };
+        let slot = limits::acquire(endpoint).await;
+        last_result = checker.check(name, endpoint).await;
+        drop(slot);
+        apply_latency_thresholds(&mut last_result, endpoint);
 
-        if last_result.is_up {
+        if last_result.is_up() {
             return last_result;
         }
     }
@@ -139,62 +726,226 @@ pub async fn check_endpoint(name: &str, endpoint: &Endpoint) -> CheckResult {
     last_result
 }
 
-/// Perform an HTTP health check
+/// Degrade or fail an up result whose response time crosses the endpoint's thresholds
+fn apply_latency_thresholds(result: &mut CheckResult, endpoint: &Endpoint) {
+    let Some(elapsed) = result.response_time_ms else {
+        return;
+    };
+    if !result.is_up() {
+        return;
+    }
+
+    if let Some(limit) = endpoint.down_after_ms
+        && elapsed >= limit
+    {
+        result.status = CheckStatus::Down;
+        result.error = Some(format!(
+            "response time {elapsed}ms exceeds down_after_ms ({limit}ms)"
+        ));
+        result.error_type = Some(ErrorType::SlowResponse);
+    } else if let Some(limit) = endpoint.degraded_after_ms
+        && elapsed >= limit
+    {
+        result.degrade(format!(
+            "response time {elapsed}ms exceeds degraded_after_ms ({limit}ms)"
+        ));
+    }
+}
+
+/// Read a response body, keeping it only when `keep` is set
+async fn read_body(mut response: Response, keep: bool) -> reqwest::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if keep {
+            body.extend_from_slice(&chunk);
+        }
+    }
+    Ok(body)
+}
+
+/// Read the body of an evaluated response so its download time is part of the
+/// timing breakdown, then record the outcome of the check
+async fn record_http_response(
+    result: &mut CheckResult,
+    endpoint: &Endpoint,
+    response: Response,
+    failure: Option<(ErrorType, String)>,
+    headers_after: Duration,
+    timings: &timing::TimingRecorder,
+) {
+    let findings = if endpoint.security_headers {
+        headers::security_header_findings(response.url(), response.headers())
+    } else {
+        Vec::new()
+    };
+
+    let download_start = std::time::Instant::now();
+    let body = read_body(response, endpoint.content_hash.is_enabled()).await;
+    result.timings = Some(timings.finish(Some(headers_after), Some(download_start.elapsed())));
+
+    if let Some((error_type, error)) = failure {
+        result.error = Some(error);
+        result.error_type = Some(error_type);
+    } else if let Err(ref e) = body {
+        result.error = Some(format!("failed to read response body: {e}"));
+        result.error_type = Some(classify_reqwest_error(e, endpoint.proxy.as_ref()));
+    } else {
+        result.status = CheckStatus::Up;
+        if endpoint.content_hash.is_enabled()
+            && let Ok(ref body) = body
+        {
+            let body = String::from_utf8_lossy(body);
+            result.content = Some(content::sample(&body, endpoint.content_hash.ignore()));
+        }
+        for finding in findings {
+            result.degrade(finding);
+        }
+    }
+}
+
+/// Perform an HTTP health check, once per selected address when `ip_version` or
+/// `all_addresses` is set
 async fn check_http(name: &str, endpoint: &Endpoint) -> CheckResult {
-    let mut result = base_result(name, endpoint);
+    let url = Url::parse(&endpoint.resolved_addr()).ok();
+    let Some(host) = url.as_ref().and_then(Url::host_str) else {
+        return check_http_at(name, endpoint, None).await;
+    };
+    if !addresses::pins_addresses(endpoint) || proxy::intercepts(endpoint.proxy.as_ref(), host) {
+        return check_http_at(name, endpoint, None).await;
+    }
 
-    let client = match Client::builder()
-        .timeout(Duration::from_secs(endpoint.timeout))
-        .danger_accept_invalid_certs(endpoint.skip_tls_verification)
-        .build()
+    let targets = match addresses::resolve(host)
+        .await
+        .and_then(|resolved| addresses::select(endpoint, host, &resolved))
     {
+        Ok(targets) => targets,
+        Err(e) => return addresses::unresolved(base_result(name, endpoint), e),
+    };
+    let results = futures::future::join_all(
+        targets
+            .into_iter()
+            .map(|ip| async move { (ip, check_http_at(name, endpoint, Some(ip)).await) }),
+    )
+    .await;
+
+    addresses::combine(results)
+}
+
+/// Perform an HTTP health check, connecting to `pinned` if given
+async fn check_http_at(name: &str, endpoint: &Endpoint, pinned: Option<IpAddr>) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let clients::CheckClient {
+        client,
+        url: resolved_addr,
+    } = match clients::check_client(name, endpoint, pinned).await {
         Ok(c) => c,
-        Err(e) => {
-            result.error = Some(format!("failed to build HTTP client: {e}"));
-            result.error_type = Some(ErrorType::ClientBuild);
+        Err((error_type, error)) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
             return result;
         }
     };
 
+    // Acquire an OAuth2 token before timing the check itself
+    let bearer_token = match endpoint.auth {
+        Some(ref auth) => match auth::bearer_token(&client, auth).await {
+            Ok(token) => token,
+            Err(e) => {
+                result.error = Some(format!("failed to acquire OAuth2 token: {e}"));
+                result.error_type = Some(ErrorType::AuthToken);
+                return result;
+            }
+        },
+        None => None,
+    };
+
+    let redirect_log = SharedRedirectLog::default();
+    let timings = timing::TimingRecorder::default();
+    let send = |request: reqwest::RequestBuilder| {
+        REDIRECT_LOG.scope(redirect_log.clone(), timings.record(request.send()))
+    };
     let start = std::time::Instant::now();
-    let resolved_addr = endpoint.resolved_addr();
 
-    // Build the request with method, headers, and body
-    let mut request = client.request(endpoint.method.as_reqwest_method(), &resolved_addr);
+    // Build the request with method, headers, body and credentials
+    let build_request = |authorization: Option<&str>| {
+        let mut request = client.request(endpoint.method.as_reqwest_method(), &resolved_addr);
 
-    // Add custom headers with env var substitution
-    for (key, value) in endpoint.resolved_headers() {
-        request = request.header(&key, &value);
-    }
+        // Add custom headers with env var substitution
+        for (key, value) in endpoint.resolved_headers() {
+            request = request.header(&key, &value);
+        }
+
+        // Add body if present
+        if let Some(body) = endpoint.resolved_body() {
+            request = request.body(body);
+        }
+
+        if let Some(AuthConfig::Basic { username, password }) = &endpoint.auth {
+            request = request.basic_auth(
+                substitute_env_vars(username),
+                password.as_deref().map(substitute_env_vars),
+            );
+        }
+        if let Some(ref token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+
+        request
+    };
+    let take_redirect_log =
+        || std::mem::take(&mut *redirect_log.lock().unwrap_or_else(PoisonError::into_inner));
 
-    // Add body if present
-    if let Some(body) = endpoint.resolved_body() {
-        request = request.body(body);
+    let mut response = send(build_request(None)).await;
+
+    // Answer a Digest challenge by repeating the request with credentials
+    if let (Some(auth), Ok(first)) = (&endpoint.auth, &response)
+        && let Some(authorization) =
+            auth::digest_challenge_response(auth, &endpoint.method.as_reqwest_method(), first)
+    {
+        take_redirect_log();
+        response = send(build_request(Some(&authorization))).await;
     }
 
-    match request.send().await {
+    let redirects = take_redirect_log();
+    result.redirects = redirects.chain.iter().map(ToString::to_string).collect();
+
+    match response {
         Ok(response) => {
             let elapsed = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
             let status = response.status().as_u16();
-            let is_up = status == endpoint.expected_status;
 
-            result.is_up = is_up;
+            // A rejected token may have been revoked early, fetch a new one next time
+            if status == 401
+                && let Some(ref auth) = endpoint.auth
+            {
+                auth::invalidate_token(auth);
+            }
+
             result.status_code = Some(status);
             result.response_time_ms = Some(elapsed);
 
-            if !is_up {
-                result.error = Some(format!(
-                    "expected status {}, got {}",
-                    endpoint.expected_status, status
-                ));
-                result.error_type = Some(ErrorType::StatusMismatch);
-            }
+            let failure = evaluate_http_response(endpoint, &response, redirects.refused);
+            let headers_after = start.elapsed();
+            record_http_response(
+                &mut result,
+                endpoint,
+                response,
+                failure,
+                headers_after,
+                &timings,
+            )
+            .await;
         }
         Err(e) => {
             let elapsed = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
             result.response_time_ms = Some(elapsed);
+            result.timings = Some(timings.finish(None, None));
             result.error = Some(e.to_string());
-            result.error_type = Some(classify_reqwest_error(&e));
+            result.error_type = Some(classify_reqwest_error(&e, endpoint.proxy.as_ref()));
         }
     }
 
@@ -212,12 +963,49 @@ async fn check_tcp(name: &str, endpoint: &Endpoint) -> CheckResult {
         .unwrap_or(&endpoint.resolved_addr())
         .to_string();
 
-    let start = std::time::Instant::now();
+    let timeout = Duration::from_secs(endpoint.timeout);
+
+    // Connect through the proxy unless the host is excluded by no_proxy
+    if let Some((host, port)) = split_host_port(&addr)
+        && proxy::intercepts(endpoint.proxy.as_ref(), host)
+        && let Some(ref proxy_config) = endpoint.proxy
+    {
+        return check_tcp_via_proxy(result, proxy_config, host, port, timeout).await;
+    }
+
+    if addresses::pins_addresses(endpoint)
+        && let Some((host, port)) = split_host_port(&addr)
+    {
+        let targets = match addresses::resolve(host)
+            .await
+            .and_then(|resolved| addresses::select(endpoint, host, &resolved))
+        {
+            Ok(targets) => targets,
+            Err(e) => return addresses::unresolved(result, e),
+        };
+        let results = futures::future::join_all(targets.into_iter().map(|ip| {
+            let result = result.clone();
+            async move {
+                let socket_addr = SocketAddr::new(ip, port);
+                (
+                    ip,
+                    connect_tcp(result, socket_addr, endpoint.local_address, timeout).await,
+                )
+            }
+        }))
+        .await;
+        return addresses::combine(results);
+    }
 
     // Resolve address first
-    let socket_addr = match addr.to_socket_addrs() {
+    let socket_addr = match tokio::net::lookup_host(addr.as_str()).await {
         Ok(mut addrs) => {
-            if let Some(a) = addrs.next() {
+            let matches_local = |a: &SocketAddr| {
+                endpoint
+                    .local_address
+                    .is_none_or(|local| local.is_ipv4() == a.is_ipv4())
+            };
+            if let Some(a) = addrs.find(matches_local) {
                 a
             } else {
                 result.error = Some(format!("no addresses found for '{addr}'"));
@@ -232,9 +1020,37 @@ async fn check_tcp(name: &str, endpoint: &Endpoint) -> CheckResult {
         }
     };
 
-    let timeout = Duration::from_secs(endpoint.timeout);
+    connect_tcp(result, socket_addr, endpoint.local_address, timeout).await
+}
+
+/// Open a TCP connection to `socket_addr`, optionally from `local_address`
+async fn tcp_connect(
+    socket_addr: SocketAddr,
+    local_address: Option<IpAddr>,
+) -> std::io::Result<TcpStream> {
+    let Some(local_address) = local_address else {
+        return TcpStream::connect(socket_addr).await;
+    };
+
+    let socket = if socket_addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.bind(SocketAddr::new(local_address, 0))?;
+    socket.connect(socket_addr).await
+}
+
+/// Check that a TCP connection to `socket_addr` can be established
+async fn connect_tcp(
+    mut result: CheckResult,
+    socket_addr: SocketAddr,
+    local_address: Option<IpAddr>,
+    timeout: Duration,
+) -> CheckResult {
+    let start = std::time::Instant::now();
 
-    match tokio::time::timeout(timeout, TcpStream::connect(socket_addr)).await {
+    match tokio::time::timeout(timeout, tcp_connect(socket_addr, local_address)).await {
         Ok(Ok(mut stream)) => {
             let elapsed = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
 
@@ -243,7 +1059,7 @@ async fn check_tcp(name: &str, endpoint: &Endpoint) -> CheckResult {
             let _ = stream.shutdown().await;
 
             if write_result.is_ok() {
-                result.is_up = true;
+                result.status = CheckStatus::Up;
                 result.response_time_ms = Some(elapsed);
             } else {
                 result.response_time_ms = Some(elapsed);
@@ -274,6 +1090,94 @@ async fn check_tcp(name: &str, endpoint: &Endpoint) -> CheckResult {
     result
 }
 
+/// Open a TCP connection to `host`, through the endpoint's proxy unless `no_proxy`
+/// excludes it, otherwise from `local_address` if set
+async fn open_connection(
+    endpoint: &Endpoint,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, (ErrorType, String)> {
+    if let Some(ref config) = endpoint.proxy
+        && proxy::intercepts(Some(config), host)
+    {
+        return proxy::connect(config, host, port).await;
+    }
+
+    let target = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| (ErrorType::Dns, format!("failed to resolve address: {e}")))?
+        .find(|a| {
+            endpoint
+                .local_address
+                .is_none_or(|local| local.is_ipv4() == a.is_ipv4())
+        })
+        .ok_or_else(|| (ErrorType::Dns, format!("no addresses found for '{host}'")))?;
+
+    tcp_connect(target, endpoint.local_address)
+        .await
+        .map_err(|e| {
+            let error_type = if e.kind() == std::io::ErrorKind::ConnectionRefused {
+                ErrorType::TcpRefused
+            } else {
+                ErrorType::Connection
+            };
+            (error_type, format!("failed to connect to {target}: {e}"))
+        })
+}
+
+/// Username and password for mail and broker logins, from `auth = { type = "basic" }`
+fn credentials(endpoint: &Endpoint) -> Option<(String, String)> {
+    match endpoint.auth {
+        Some(AuthConfig::Basic {
+            ref username,
+            ref password,
+        }) => Some((
+            substitute_env_vars(username),
+            password
+                .as_deref()
+                .map(substitute_env_vars)
+                .unwrap_or_default(),
+        )),
+        _ => None,
+    }
+}
+
+/// Split a `host:port` address, accepting bracketed IPv6 hosts
+fn split_host_port(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some((host, port.parse().ok()?))
+}
+
+/// Perform a TCP connectivity check through a proxy
+async fn check_tcp_via_proxy(
+    mut result: CheckResult,
+    proxy_config: &ProxyConfig,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> CheckResult {
+    let start = std::time::Instant::now();
+
+    match tokio::time::timeout(timeout, proxy::connect(proxy_config, host, port)).await {
+        Ok(Ok(mut stream)) => {
+            let _ = stream.shutdown().await;
+            result.status = CheckStatus::Up;
+        }
+        Ok(Err((error_type, error))) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
+        }
+        Err(_) => {
+            result.error = Some("connection timed out".to_string());
+            result.error_type = Some(ErrorType::Timeout);
+        }
+    }
+
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+    result
+}
+
 /// Perform a DNS resolution check
 async fn check_dns(name: &str, endpoint: &Endpoint) -> CheckResult {
     let mut result = base_result(name, endpoint);
@@ -287,12 +1191,7 @@ async fn check_dns(name: &str, endpoint: &Endpoint) -> CheckResult {
 
     let start = std::time::Instant::now();
 
-    // Create resolver
-    let resolver = Resolver::builder_with_config(
-        ResolverConfig::default(),
-        TokioConnectionProvider::default(),
-    )
-    .build();
+    let resolver = clients::dns_resolver(endpoint);
 
     let timeout = Duration::from_secs(endpoint.timeout);
     let lookup_future = resolver.lookup_ip(&hostname);
@@ -307,10 +1206,11 @@ async fn check_dns(name: &str, endpoint: &Endpoint) -> CheckResult {
             // If expected_records is specified, check if they match
             if endpoint.expected_records.is_empty() {
                 // No expected records, just check if resolution succeeded
-                result.is_up = !resolved_ips.is_empty();
                 if resolved_ips.is_empty() {
                     result.error = Some("DNS resolution returned no records".to_string());
                     result.error_type = Some(ErrorType::Dns);
+                } else {
+                    result.status = CheckStatus::Up;
                 }
             } else {
                 let all_found = endpoint
@@ -319,7 +1219,7 @@ async fn check_dns(name: &str, endpoint: &Endpoint) -> CheckResult {
                     .all(|expected| resolved_ips.contains(expected));
 
                 if all_found {
-                    result.is_up = true;
+                    result.status = CheckStatus::Up;
                 } else {
                     result.error = Some(format!(
                         "expected records {:?}, got {:?}",
@@ -354,7 +1254,8 @@ async fn check_dns(name: &str, endpoint: &Endpoint) -> CheckResult {
     result
 }
 
-/// Check all endpoints concurrently and return results sorted alphabetically by name
+/// Check all endpoints concurrently and return results sorted alphabetically by name.
+/// Down endpoints whose parent is down in the same batch are marked unreachable.
 pub async fn check_all_endpoints(endpoints: &HashMap<String, Endpoint>) -> Vec<CheckResult> {
     let futures: Vec<_> = endpoints
         .iter()
@@ -362,6 +1263,18 @@ pub async fn check_all_endpoints(endpoints: &HashMap<String, Endpoint>) -> Vec<C
         .collect();
 
     let mut results = futures::future::join_all(futures).await;
+    let statuses: HashMap<_, _> = results
+        .iter()
+        .map(|result| (result.name.clone(), result.status))
+        .collect();
+    for result in &mut results {
+        if let Some(endpoint) = endpoints.get(&result.name) {
+            dependencies::mark_unreachable(result, endpoint, |parent| {
+                statuses.get(parent).copied()
+            });
+        }
+    }
+
     results.sort_by_key(|a| a.name.to_lowercase());
     results
 }
@@ -374,23 +1287,60 @@ pub async fn get_sorted_results(state: &CheckResultsState) -> Vec<CheckResult> {
     sorted
 }
 
-/// Spawn a background checking task for a single endpoint
+/// Spawn a background checking task for a single endpoint. Checks run in the
+/// endpoint's staggered slots or at the matches of its schedule. The first one runs
+/// right away if `check_now` is set and the endpoint has no schedule, or else after
+/// the endpoint's startup delay.
 fn spawn_endpoint_checker(
     name: String,
     endpoint: Endpoint,
     state: CheckResultsState,
+    locations: LocationResults,
     db_pool: Option<PgPool>,
     cancel_token: CancellationToken,
+    check_now: bool,
 ) {
     tokio::spawn(async move {
-        let interval = Duration::from_secs(endpoint.interval);
+        let mut alerts = alert::AlertState::default();
+        let mut content = load_content_baseline(&name, &endpoint, db_pool.as_ref()).await;
+        let mut consecutive_down = 0;
+
+        if !check_now || endpoint.schedule.is_some() {
+            let delay = schedule::startup_delay(&name, &endpoint);
+            if !wait_for_next_check(&name, delay, &state, &cancel_token).await {
+                return;
+            }
+        }
 
         loop {
-            let result = check_endpoint(&name, &endpoint).await;
+            let mut result = check_endpoint(&name, &endpoint).await;
+            quorum::decide(
+                &mut result,
+                &endpoint,
+                locations.read().await.get(&name),
+                Utc::now(),
+            );
+            if !endpoint.depends_on.is_empty() {
+                let results = state.read().await;
+                dependencies::mark_unreachable(&mut result, &endpoint, |parent| {
+                    results.get(parent).map(|parent| parent.status)
+                });
+            }
+
+            if let Some(event) =
+                track_content(&name, &mut content, db_pool.as_ref(), &mut result).await
+            {
+                log_alert(&name, &endpoint, &result, &event);
+            }
+
+            for event in alerts.observe(&endpoint, &result) {
+                log_alert(&name, &endpoint, &result, &event);
+            }
 
             tracing::debug!(
                 endpoint = %name,
-                is_up = result.is_up,
+                is_up = result.is_up(),
+                is_degraded = result.is_degraded(),
                 response_time_ms = ?result.response_time_ms,
                 "endpoint check completed"
             );
@@ -402,56 +1352,148 @@ fn spawn_endpoint_checker(
                 tracing::warn!(endpoint = %name, error = %e, "failed to insert uptime event");
             }
 
+            consecutive_down = schedule::consecutive_down_after(result.status, consecutive_down);
+
             {
                 let mut results = state.write().await;
                 results.insert(name.clone(), result);
             }
 
-            tokio::select! {
-                () = tokio::time::sleep(interval) => {}
-                () = cancel_token.cancelled() => {
-                    tracing::debug!(endpoint = %name, "endpoint checker cancelled");
-                    break;
-                }
+            let delay = schedule::next_check_delay(&name, &endpoint, consecutive_down);
+            if !wait_for_next_check(&name, delay, &state, &cancel_token).await {
+                break;
             }
         }
     });
 }
 
-/// Perform initial check of all endpoints and populate state
-pub async fn initial_check(
-    endpoints: &HashMap<String, Endpoint>,
+/// Record when the endpoint is checked next and sleep until then. Returns false if
+/// the checker was cancelled in the meantime.
+async fn wait_for_next_check(
+    name: &str,
+    delay: Duration,
     state: &CheckResultsState,
+    cancel_token: &CancellationToken,
+) -> bool {
+    if let Some(result) = state.write().await.get_mut(name) {
+        result.next_check_at = chrono::TimeDelta::from_std(delay)
+            .ok()
+            .and_then(|delay| Utc::now().checked_add_signed(delay));
+    }
+
+    tokio::select! {
+        () = tokio::time::sleep(delay) => true,
+        () = cancel_token.cancelled() => {
+            tracing::debug!(endpoint = %name, "endpoint checker cancelled");
+            false
+        }
+    }
+}
+
+/// Start content tracking from the stored baseline, normalized with the current
+/// ignore patterns so changing the patterns doesn't report a change
+async fn load_content_baseline(
+    name: &str,
+    endpoint: &Endpoint,
     db_pool: Option<&PgPool>,
-) {
-    tracing::info!("performing initial endpoint checks");
+) -> content::ContentTracker {
+    let baseline = match db_pool {
+        Some(pool) if endpoint.content_hash.is_enabled() => {
+            match db::get_content_baseline(pool, name).await {
+                Ok(body) => body.map(|body| content::sample(&body, endpoint.content_hash.ignore())),
+                Err(e) => {
+                    tracing::warn!(endpoint = %name, error = %e, "failed to load content baseline");
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    content::ContentTracker::with_baseline(baseline)
+}
 
-    let results = check_all_endpoints(endpoints).await;
+/// Compare the content of a result with the baseline, degrading the result and
+/// recording the change when it differs
+async fn track_content(
+    name: &str,
+    tracker: &mut content::ContentTracker,
+    db_pool: Option<&PgPool>,
+    result: &mut CheckResult,
+) -> Option<alert::AlertEvent> {
+    let sample = result.content.as_ref()?;
+
+    let change = match tracker.observe(sample) {
+        content::Observation::Unchanged => return None,
+        content::Observation::Baseline => {
+            tracing::info!(endpoint = %name, hash = %sample.hash, "recorded content baseline");
+            None
+        }
+        content::Observation::Changed(change) => Some(change),
+    };
 
-    // Write initial results to database
     if let Some(pool) = db_pool {
-        for result in &results {
-            if let Err(e) = db::insert_uptime_event(pool, result).await {
-                tracing::warn!(endpoint = %result.name, error = %e, "failed to insert initial uptime event");
-            }
+        if let Some(ref change) = change
+            && let Err(e) = db::insert_content_change(pool, name, change).await
+        {
+            tracing::warn!(endpoint = %name, error = %e, "failed to insert content change");
+        }
+        if let Err(e) = db::save_content_baseline(pool, name, sample).await {
+            tracing::warn!(endpoint = %name, error = %e, "failed to save content baseline");
         }
     }
 
-    let mut state_guard = state.write().await;
-    for result in results {
-        state_guard.insert(result.name.clone(), result);
-    }
+    let change = change?;
+    result.degrade(format!(
+        "content changed since the last check ({} -> {})",
+        &change.old_hash[..12],
+        &change.new_hash[..12]
+    ));
+    Some(alert::AlertEvent::ContentChanged(change))
+}
 
-    tracing::info!("initial endpoint checks completed");
+/// Report an alert. Channels are recorded on the event until delivery is implemented.
+fn log_alert(name: &str, endpoint: &Endpoint, result: &CheckResult, event: &alert::AlertEvent) {
+    let channels = endpoint.alert_channels.join(",");
+    match event {
+        alert::AlertEvent::Down { consecutive } => tracing::warn!(
+            endpoint = %name,
+            channels = %channels,
+            consecutive,
+            error = ?result.error,
+            "endpoint is down"
+        ),
+        alert::AlertEvent::Degraded { consecutive } => tracing::warn!(
+            endpoint = %name,
+            channels = %channels,
+            consecutive,
+            warnings = ?result.warnings,
+            "endpoint is degraded"
+        ),
+        alert::AlertEvent::Recovered => {
+            tracing::info!(endpoint = %name, channels = %channels, "endpoint recovered");
+        }
+        alert::AlertEvent::ContentChanged(change) => tracing::warn!(
+            endpoint = %name,
+            channels = %channels,
+            old_hash = %change.old_hash,
+            new_hash = %change.new_hash,
+            diff = %change.diff,
+            "endpoint content changed"
+        ),
+    }
 }
 
-/// Apply config changes: cancel old tasks, start new ones, re-check all endpoints
+/// Apply config changes: cancel old tasks and start new ones, which check their
+/// endpoint right away. Unchanged endpoints keep their schedule and are only
+/// re-checked if `recheck_unchanged` is set.
 async fn apply_config_update(
     new_endpoints: &HashMap<String, Endpoint>,
     current_endpoints: &mut HashMap<String, Endpoint>,
     active_tasks: &ActiveTasks,
     state: &CheckResultsState,
+    locations: &LocationResults,
     db_pool: Option<PgPool>,
+    recheck_unchanged: bool,
 ) {
     let mut tasks = active_tasks.write().await;
     let mut results = state.write().await;
@@ -506,8 +1548,10 @@ async fn apply_config_update(
                 name.clone(),
                 endpoint.clone(),
                 Arc::clone(state),
+                Arc::clone(locations),
                 db_pool.clone(),
                 cancel_token.clone(),
+                true,
             );
             tasks.insert(name.clone(), cancel_token);
             tracing::info!(endpoint = %name, "updated endpoint");
@@ -522,8 +1566,10 @@ async fn apply_config_update(
                 name.clone(),
                 endpoint.clone(),
                 Arc::clone(state),
+                Arc::clone(locations),
                 db_pool.clone(),
                 cancel_token.clone(),
+                true,
             );
             tasks.insert(name.clone(), cancel_token);
             tracing::info!(endpoint = %name, "added endpoint");
@@ -534,10 +1580,11 @@ async fn apply_config_update(
     drop(tasks);
     drop(results);
 
-    // Re-check all endpoints (added, changed, and unchanged)
+    // Re-check unchanged endpoints on request; added and changed ones are checked by
+    // their new checkers
     let endpoints_to_check: HashMap<_, _> = new_endpoints
         .iter()
-        .filter(|(k, _)| added.contains(k) || changed.contains(k) || unchanged.contains(k))
+        .filter(|(k, _)| recheck_unchanged && unchanged.contains(k))
         .map(|(k, v)| (k.clone(), v.clone()))
         .collect();
 
@@ -546,7 +1593,8 @@ async fn apply_config_update(
             "re-checking {} endpoints after config reload",
             endpoints_to_check.len()
         );
-        let check_results = check_all_endpoints(&endpoints_to_check).await;
+        let mut check_results = check_all_endpoints(&endpoints_to_check).await;
+        decide_quorums(&mut check_results, &endpoints_to_check, locations).await;
 
         // Write to database
         if let Some(ref pool) = db_pool {
@@ -557,20 +1605,29 @@ async fn apply_config_update(
             }
         }
 
-        let mut results = state.write().await;
-        for result in check_results {
-            results.insert(result.name.clone(), result);
-        }
+        store_rechecked(&mut *state.write().await, check_results);
     }
 
     // Update current endpoints
     current_endpoints.clone_from(new_endpoints);
 }
 
+/// Store the results of a re-check outside the endpoint checkers, keeping the next
+/// check time the checkers recorded
+fn store_rechecked(results: &mut HashMap<String, CheckResult>, check_results: Vec<CheckResult>) {
+    for mut result in check_results {
+        if let Some(previous) = results.get(&result.name) {
+            result.next_check_at = previous.next_check_at;
+        }
+        results.insert(result.name.clone(), result);
+    }
+}
+
 /// Start all endpoint checkers and return the active tasks tracker
 async fn start_all_checkers(
     endpoints: &HashMap<String, Endpoint>,
     state: &CheckResultsState,
+    locations: &LocationResults,
     db_pool: Option<PgPool>,
 ) -> ActiveTasks {
     let active_tasks: ActiveTasks = Arc::default();
@@ -582,8 +1639,10 @@ async fn start_all_checkers(
             name.clone(),
             endpoint.clone(),
             Arc::clone(state),
+            Arc::clone(locations),
             db_pool.clone(),
             cancel_token.clone(),
+            false,
         );
 
         let mut tasks = active_tasks.write().await;
@@ -593,21 +1652,48 @@ async fn start_all_checkers(
     active_tasks
 }
 
-/// Spawn the config reloader and all endpoint checkers.
+/// Apply the endpoints' quorums to results checked outside the endpoint checkers
+async fn decide_quorums(
+    results: &mut [CheckResult],
+    endpoints: &HashMap<String, Endpoint>,
+    locations: &LocationResults,
+) {
+    let locations = locations.read().await;
+    let now = Utc::now();
+    for result in results {
+        if let Some(endpoint) = endpoints.get(&result.name) {
+            quorum::decide(result, endpoint, locations.get(&result.name), now);
+        }
+    }
+}
+
+/// Spawn the config reloader and all endpoint checkers. `locations` holds the
+/// results of agents, which endpoints with a quorum are decided on, and `endpoints`
+/// is kept in sync with the config.
 /// Returns a channel sender that can be used to trigger manual reloads.
 pub async fn spawn_background_tasks(
     config_path: PathBuf,
     initial_config: Config,
     state: CheckResultsState,
+    locations: LocationResults,
+    endpoints: EndpointsState,
     db_pool: Option<PgPool>,
 ) -> ReloadTrigger {
     let reload_interval = initial_config.server.reload_config_interval;
+    limits::configure(&initial_config.server);
 
     // Start initial endpoint checkers
-    let active_tasks = start_all_checkers(&initial_config.endpoints, &state, db_pool.clone()).await;
+    let active_tasks = start_all_checkers(
+        &initial_config.endpoints,
+        &state,
+        &locations,
+        db_pool.clone(),
+    )
+    .await;
 
     // Store current endpoints for comparison
-    let current_endpoints = Arc::new(RwLock::new(initial_config.endpoints));
+    let current_endpoints = endpoints;
+    *current_endpoints.write().await = initial_config.endpoints;
 
     // Create channel for manual reload triggers
     let (reload_tx, mut reload_rx) = mpsc::channel::<()>(1);
@@ -619,14 +1705,16 @@ pub async fn spawn_background_tasks(
 
         loop {
             // Wait for either timer or manual trigger
-            tokio::select! {
+            let manual = tokio::select! {
                 () = tokio::time::sleep(interval), if auto_reload => {
                     tracing::debug!("automatic config reload triggered");
+                    false
                 }
                 Some(()) = reload_rx.recv() => {
                     tracing::info!("manual config reload triggered");
+                    true
                 }
-            }
+            };
 
             // Reload config
             let new_config = match Config::load(&config_path) {
@@ -636,15 +1724,22 @@ pub async fn spawn_background_tasks(
                     continue;
                 }
             };
+            limits::configure(&new_config.server);
 
             // Get current endpoints for comparison
             let mut current = current_endpoints.write().await;
 
             // Check if anything changed
             if new_config.endpoints == *current {
+                // Automatic reloads leave the checkers to their schedule
+                if !manual {
+                    tracing::debug!("config unchanged");
+                    continue;
+                }
                 tracing::debug!("config unchanged, re-checking all endpoints");
                 // Even if config unchanged, re-check all endpoints on manual reload
-                let check_results = check_all_endpoints(&new_config.endpoints).await;
+                let mut check_results = check_all_endpoints(&new_config.endpoints).await;
+                decide_quorums(&mut check_results, &new_config.endpoints, &locations).await;
 
                 // Write to database
                 if let Some(ref pool) = db_pool {
@@ -655,10 +1750,7 @@ pub async fn spawn_background_tasks(
                     }
                 }
 
-                let mut results = state.write().await;
-                for result in check_results {
-                    results.insert(result.name.clone(), result);
-                }
+                store_rechecked(&mut *state.write().await, check_results);
                 continue;
             }
 
@@ -670,7 +1762,9 @@ pub async fn spawn_background_tasks(
                 &mut current,
                 &active_tasks,
                 &state,
+                &locations,
                 db_pool.clone(),
+                manual,
             )
             .await;
         }
@@ -686,7 +1780,7 @@ pub async fn spawn_background_tasks(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::HttpMethod;
+    use crate::config::{ContentHash, FollowRedirects, IpVersion, TlsFiles};
 
     // ============ ErrorType Tests ============
 
@@ -700,7 +1794,32 @@ mod tests {
         assert_eq!(ErrorType::TcpRefused.as_str(), "tcp_refused");
         assert_eq!(ErrorType::DnsNxdomain.as_str(), "dns_nxdomain");
         assert_eq!(ErrorType::DnsMismatch.as_str(), "dns_mismatch");
+        assert_eq!(ErrorType::BodyMismatch.as_str(), "body_mismatch");
+        assert_eq!(ErrorType::ExtractFailed.as_str(), "extract_failed");
+        assert_eq!(ErrorType::AuthToken.as_str(), "auth_token");
         assert_eq!(ErrorType::ClientBuild.as_str(), "client_build");
+        assert_eq!(ErrorType::Redirect.as_str(), "redirect");
+        assert_eq!(ErrorType::Proxy.as_str(), "proxy");
+        assert_eq!(ErrorType::HeaderMismatch.as_str(), "header_mismatch");
+        assert_eq!(ErrorType::SlowResponse.as_str(), "slow_response");
+        assert_eq!(ErrorType::DomainLookup.as_str(), "domain_lookup");
+        assert_eq!(ErrorType::DomainExpiring.as_str(), "domain_expiring");
+        assert_eq!(ErrorType::ReplyMismatch.as_str(), "reply_mismatch");
+        assert_eq!(ErrorType::PortUnreachable.as_str(), "port_unreachable");
+        assert_eq!(ErrorType::NtpInvalid.as_str(), "ntp_invalid");
+        assert_eq!(ErrorType::MailGreeting.as_str(), "mail_greeting");
+        assert_eq!(ErrorType::MailCapabilities.as_str(), "mail_capabilities");
+        assert_eq!(ErrorType::StartTls.as_str(), "starttls");
+        assert_eq!(ErrorType::MailLogin.as_str(), "mail_login");
+        assert_eq!(ErrorType::BrokerAuth.as_str(), "broker_auth");
+        assert_eq!(ErrorType::BrokerError.as_str(), "broker_error");
+        assert_eq!(ErrorType::ProbeLost.as_str(), "probe_lost");
+        assert_eq!(ErrorType::ProbeSlow.as_str(), "probe_slow");
+        assert_eq!(ErrorType::HostKeyMismatch.as_str(), "host_key_mismatch");
+        assert_eq!(ErrorType::SshAuth.as_str(), "ssh_auth");
+        assert_eq!(ErrorType::SshCommand.as_str(), "ssh_command");
+        assert_eq!(ErrorType::ScriptError.as_str(), "script_error");
+        assert_eq!(ErrorType::ScriptDown.as_str(), "script_down");
         assert_eq!(ErrorType::Unknown.as_str(), "unknown");
     }
 
@@ -720,25 +1839,15 @@ mod tests {
     // ============ base_result Tests ============
 
     fn make_test_endpoint() -> Endpoint {
-        Endpoint {
-            addr: "https://example.com/health".to_string(),
-            check_type: CheckType::Http,
-            description: Some("Test endpoint".to_string()),
-            group: Some("backend".to_string()),
-            tags: vec!["production".to_string(), "api".to_string()],
-            interval: 60,
-            timeout: 10,
-            expected_status: 200,
-            skip_tls_verification: false,
-            method: HttpMethod::Get,
-            headers: HashMap::new(),
-            body: None,
-            retries: 0,
-            retry_delay: 5,
-            alert_after_failures: 3,
-            alert_channels: vec![],
-            expected_records: vec![],
-        }
+        toml::from_str(
+            r#"
+            addr = "https://example.com/health"
+            description = "Test endpoint"
+            group = "backend"
+            tags = ["production", "api"]
+            "#,
+        )
+        .unwrap()
     }
 
     #[test]
@@ -758,11 +1867,12 @@ mod tests {
     }
 
     #[test]
-    fn base_result_initializes_is_up_to_false() {
+    fn base_result_initializes_status_to_down() {
         let endpoint = make_test_endpoint();
         let result = base_result("test", &endpoint);
 
-        assert!(!result.is_up);
+        assert_eq!(result.status, CheckStatus::Down);
+        assert!(!result.is_up());
     }
 
     #[test]
@@ -815,14 +1925,14 @@ mod tests {
     fn check_result_clone() {
         let endpoint = make_test_endpoint();
         let mut result = base_result("test", &endpoint);
-        result.is_up = true;
+        result.status = CheckStatus::Up;
         result.status_code = Some(200);
         result.response_time_ms = Some(150);
 
         let cloned = result.clone();
 
         assert_eq!(result.name, cloned.name);
-        assert_eq!(result.is_up, cloned.is_up);
+        assert_eq!(result.is_up(), cloned.is_up());
         assert_eq!(result.status_code, cloned.status_code);
         assert_eq!(result.response_time_ms, cloned.response_time_ms);
     }
@@ -869,6 +1979,610 @@ mod tests {
         assert_eq!(result.check_type, CheckType::Dns);
     }
 
+    // ============ Mutual TLS Tests ============
+
+    /// Certificates for a private CA, a server and a client, all PEM encoded
+    struct TestPki {
+        ca_cert: String,
+        ca_der: Vec<u8>,
+        server_cert: rcgen::Certificate,
+        server_key: rcgen::KeyPair,
+        client_cert: String,
+        client_key: String,
+    }
+
+    fn make_test_pki() -> TestPki {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = rcgen::KeyPair::generate().unwrap();
+        let server_cert = rcgen::CertificateParams::new(vec!["internal.test".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        let client_key = rcgen::KeyPair::generate().unwrap();
+        let client_cert = rcgen::CertificateParams::new(vec!["probe".to_string()])
+            .unwrap()
+            .signed_by(&client_key, &ca, &ca_key)
+            .unwrap();
+
+        TestPki {
+            ca_cert: ca.pem(),
+            ca_der: ca.der().to_vec(),
+            server_cert,
+            server_key,
+            client_cert: client_cert.pem(),
+            client_key: client_key.serialize_pem(),
+        }
+    }
+
+    const OK_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    /// Serve HTTPS on a random local port, requiring a client certificate from the test CA.
+    /// Every request is answered with the raw `response`.
+    async fn spawn_mtls_server(pki: &TestPki, response: &'static [u8]) -> std::net::SocketAddr {
+        use tokio::io::AsyncReadExt;
+        use tokio_rustls::rustls::{
+            RootCertStore, ServerConfig, crypto::ring, pki_types::PrivateKeyDer,
+            server::WebPkiClientVerifier,
+        };
+
+        let provider = Arc::new(ring::default_provider());
+        let mut roots = RootCertStore::empty();
+        roots.add(pki.ca_der.clone().into()).unwrap();
+        let verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .unwrap();
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                vec![pki.server_cert.der().clone()],
+                PrivateKeyDer::Pkcs8(pki.server_key.serialize_der().into()),
+            )
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut tls) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut buf = [0u8; 1024];
+                    let _ = tls.read(&mut buf).await;
+                    let _ = tls.write_all(response).await;
+                    let _ = tls.shutdown().await;
+                });
+            }
+        });
+        addr
+    }
+
+    fn make_mtls_endpoint(addr: std::net::SocketAddr, pki: &TestPki) -> Endpoint {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = format!("https://127.0.0.1:{}/health", addr.port());
+        endpoint.timeout = 5;
+        endpoint.tls_server_name = Some("internal.test".to_string());
+        endpoint.tls_files = TlsFiles {
+            client_cert: Some(pki.client_cert.clone().into_bytes()),
+            client_key: Some(pki.client_key.clone().into_bytes()),
+            ca_bundle: Some(pki.ca_cert.clone().into_bytes()),
+        };
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn check_http_uses_client_cert_ca_bundle_and_server_name() {
+        let pki = make_test_pki();
+        let addr = spawn_mtls_server(&pki, OK_RESPONSE).await;
+        let endpoint = make_mtls_endpoint(addr, &pki);
+
+        let result = check_http("mtls", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert_eq!(result.status_code, Some(200));
+    }
+
+    #[tokio::test]
+    async fn check_http_records_tls_handshake_timing() {
+        let pki = make_test_pki();
+        let addr = spawn_mtls_server(&pki, OK_RESPONSE).await;
+        let endpoint = make_mtls_endpoint(addr, &pki);
+
+        let result = check_http("mtls", &endpoint).await;
+        let timings = result.timings.unwrap();
+
+        assert!(timings.connect.is_some());
+        assert!(timings.tls.is_some());
+        assert!(timings.ttfb.is_some());
+        assert!(timings.download.is_some());
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_without_client_cert() {
+        let pki = make_test_pki();
+        let addr = spawn_mtls_server(&pki, OK_RESPONSE).await;
+        let mut endpoint = make_mtls_endpoint(addr, &pki);
+        endpoint.tls_files.client_cert = None;
+        endpoint.tls_files.client_key = None;
+
+        let result = check_http("mtls", &endpoint).await;
+
+        assert!(!result.is_up());
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_without_ca_bundle() {
+        let pki = make_test_pki();
+        let addr = spawn_mtls_server(&pki, OK_RESPONSE).await;
+        let mut endpoint = make_mtls_endpoint(addr, &pki);
+        endpoint.tls_files.ca_bundle = None;
+
+        let result = check_http("mtls", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert!(result.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn check_http_reports_invalid_client_identity() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.tls_files.client_cert = Some(b"not a certificate".to_vec());
+        endpoint.tls_files.client_key = Some(b"not a key".to_vec());
+
+        let result = check_http("mtls", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::ClientBuild));
+    }
+
+    // ============ Redirect Tests ============
+
+    async fn spawn_redirect_server() -> String {
+        use axum::{Router, response::Redirect, routing::get};
+
+        let app = Router::new()
+            .route("/start", get(|| async { Redirect::to("/middle") }))
+            .route("/middle", get(|| async { Redirect::to("/end") }))
+            .route("/end", get(|| async { "done" }))
+            .route(
+                "/admin",
+                get(|| async { Redirect::to("/login?next=/admin") }),
+            )
+            .route("/login", get(|| async { "please sign in" }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    fn make_redirect_endpoint(base: &str, path: &str) -> Endpoint {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = format!("{base}{path}");
+        endpoint.timeout = 5;
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn check_http_records_redirect_chain() {
+        let base = spawn_redirect_server().await;
+        let mut endpoint = make_redirect_endpoint(&base, "/start");
+        endpoint.expected_final_url = Some("/end".to_string());
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert_eq!(
+            result.redirects,
+            vec![format!("{base}/middle"), format!("{base}/end")]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_on_unexpected_final_url() {
+        let base = spawn_redirect_server().await;
+        let mut endpoint = make_redirect_endpoint(&base, "/start");
+        endpoint.expected_final_url = Some("/middle".to_string());
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Redirect));
+        assert!(result.error.unwrap().contains("expected final URL"));
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_when_exceeding_max_hops() {
+        let base = spawn_redirect_server().await;
+        let mut endpoint = make_redirect_endpoint(&base, "/start");
+        endpoint.follow_redirects = FollowRedirects::MaxHops(1);
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Redirect));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("exceeded maximum of 1 redirects")
+        );
+        assert_eq!(result.redirects, vec![format!("{base}/middle")]);
+    }
+
+    #[tokio::test]
+    async fn check_http_checks_location_when_not_following() {
+        let base = spawn_redirect_server().await;
+        let mut endpoint = make_redirect_endpoint(&base, "/start");
+        endpoint.follow_redirects = FollowRedirects::Enabled(false);
+        endpoint.expected_status = 303;
+        endpoint.expected_location = Some("/middle".to_string());
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert!(result.redirects.is_empty());
+
+        endpoint.expected_location = Some("/elsewhere".to_string());
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Redirect));
+        assert!(result.error.unwrap().contains("expected Location"));
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_on_redirect_to_login_page() {
+        let base = spawn_redirect_server().await;
+        let endpoint = make_redirect_endpoint(&base, "/admin");
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Redirect));
+        assert!(result.error.unwrap().contains("redirected to login page"));
+    }
+
+    #[tokio::test]
+    async fn check_http_allows_expected_login_redirect() {
+        let base = spawn_redirect_server().await;
+        let mut endpoint = make_redirect_endpoint(&base, "/admin");
+        endpoint.expected_final_url = Some("/login?next=/admin".to_string());
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_on_redirect_to_plain_http() {
+        let pki = make_test_pki();
+        let addr = spawn_mtls_server(
+            &pki,
+            b"HTTP/1.1 301 Moved Permanently\r\nlocation: http://internal.test/health\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        )
+        .await;
+        let endpoint = make_mtls_endpoint(addr, &pki);
+
+        let result = check_http("redirects", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Redirect));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("redirected from HTTPS to non-TLS URL http://internal.test/health")
+        );
+    }
+
+    #[test]
+    fn is_login_url_matches_login_paths() {
+        let login = |url: &str| is_login_url(&Url::parse(url).unwrap());
+
+        assert!(login("https://example.com/login"));
+        assert!(login("https://example.com/account/SignIn?next=/"));
+        assert!(login("https://example.com/login.php"));
+        assert!(login("https://sso.example.com/oauth2/authorize"));
+        assert!(!login("https://example.com/blogin"));
+        assert!(!login("https://example.com/health"));
+    }
+
+    // ============ Header Tests ============
+
+    async fn spawn_header_server() -> String {
+        use axum::{Router, routing::get};
+
+        let app = Router::new().route(
+            "/",
+            get(|| async {
+                (
+                    [
+                        ("content-type", "application/json"),
+                        ("set-cookie", "session=abc; HttpOnly"),
+                    ],
+                    "{}",
+                )
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn check_http_fails_on_header_mismatch() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server().await;
+        endpoint.expected_headers.insert(
+            "Content-Type".to_string(),
+            crate::config::HeaderAssertion::Exact("text/html".to_string()),
+        );
+
+        let result = check_http("headers", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::HeaderMismatch));
+    }
+
+    #[tokio::test]
+    async fn check_http_degrades_on_missing_security_headers() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server().await;
+        endpoint.security_headers = true;
+
+        let result = check_http("headers", &endpoint).await;
+
+        assert!(result.is_up());
+        assert!(result.is_degraded());
+        assert_eq!(
+            result.warnings,
+            vec![
+                "missing Content-Security-Policy header",
+                "missing X-Content-Type-Options: nosniff",
+                "cookie 'session' is set without the Secure flag",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_http_records_phase_timings_for_plain_http() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server().await;
+
+        let result = check_http("headers", &endpoint).await;
+        let timings = result.timings.unwrap();
+
+        // The address is an IP literal, so nothing is resolved
+        assert_eq!(timings.dns, None);
+        assert!(timings.connect.is_some());
+        assert_eq!(timings.tls, None);
+        assert!(timings.ttfb.is_some());
+        assert!(timings.download.is_some());
+    }
+
+    #[tokio::test]
+    async fn check_http_records_dns_timing_for_host_names() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server()
+            .await
+            .replace("127.0.0.1", "localhost");
+
+        let result = check_http("headers", &endpoint).await;
+
+        assert!(result.timings.unwrap().dns.is_some());
+    }
+
+    // ============ Latency Threshold Tests ============
+
+    fn timed_result(endpoint: &Endpoint, elapsed_ms: u64) -> CheckResult {
+        let mut result = base_result("test", endpoint);
+        result.status = CheckStatus::Up;
+        result.response_time_ms = Some(elapsed_ms);
+        result
+    }
+
+    #[test]
+    fn apply_latency_thresholds_keeps_fast_response_up() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.degraded_after_ms = Some(500);
+        endpoint.down_after_ms = Some(2000);
+        let mut result = timed_result(&endpoint, 120);
+
+        apply_latency_thresholds(&mut result, &endpoint);
+
+        assert_eq!(result.status, CheckStatus::Up);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn apply_latency_thresholds_degrades_slow_response() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.degraded_after_ms = Some(500);
+        endpoint.down_after_ms = Some(2000);
+        let mut result = timed_result(&endpoint, 900);
+
+        apply_latency_thresholds(&mut result, &endpoint);
+
+        assert_eq!(result.status, CheckStatus::Degraded);
+        assert_eq!(
+            result.warnings,
+            vec!["response time 900ms exceeds degraded_after_ms (500ms)"]
+        );
+    }
+
+    #[test]
+    fn apply_latency_thresholds_marks_very_slow_response_down() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.degraded_after_ms = Some(500);
+        endpoint.down_after_ms = Some(2000);
+        let mut result = timed_result(&endpoint, 2500);
+
+        apply_latency_thresholds(&mut result, &endpoint);
+
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(result.error_type, Some(ErrorType::SlowResponse));
+    }
+
+    #[test]
+    fn apply_latency_thresholds_ignores_failed_checks() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.down_after_ms = Some(2000);
+        let mut result = timed_result(&endpoint, 2500);
+        result.status = CheckStatus::Down;
+        result.error_type = Some(ErrorType::Timeout);
+
+        apply_latency_thresholds(&mut result, &endpoint);
+
+        assert_eq!(result.error_type, Some(ErrorType::Timeout));
+    }
+
+    #[test]
+    fn check_status_round_trips_through_str() {
+        for status in [
+            CheckStatus::Up,
+            CheckStatus::Degraded,
+            CheckStatus::Down,
+            CheckStatus::NotMonitored,
+            CheckStatus::Unreachable,
+            CheckStatus::PartialOutage,
+        ] {
+            assert_eq!(CheckStatus::from_str(status.as_str()), Some(status));
+        }
+        assert_eq!(CheckStatus::from_str("unknown"), None);
+    }
+
+    // ============ Address Selection Tests ============
+
+    async fn spawn_tcp_listener() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { while let Ok((_stream, _)) = listener.accept().await {} });
+        addr
+    }
+
+    #[tokio::test]
+    async fn check_tcp_checks_all_addresses() {
+        let addr = spawn_tcp_listener().await;
+        let mut endpoint = make_test_endpoint();
+        endpoint.check_type = CheckType::Tcp;
+        endpoint.addr = format!("tcp://{addr}");
+        endpoint.all_addresses = true;
+
+        let result = check_tcp("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.addresses.len(), 1);
+        assert_eq!(result.addresses[0].addr, addr.ip());
+    }
+
+    #[tokio::test]
+    async fn check_tcp_fails_without_address_of_requested_family() {
+        let addr = spawn_tcp_listener().await;
+        let mut endpoint = make_test_endpoint();
+        endpoint.check_type = CheckType::Tcp;
+        endpoint.addr = format!("tcp://{addr}");
+        endpoint.ip_version = IpVersion::V6;
+
+        let result = check_tcp("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Dns));
+        assert!(result.error.unwrap().contains("no IPv6 addresses found"));
+    }
+
+    #[tokio::test]
+    async fn check_tcp_binds_local_address() {
+        let addr = spawn_tcp_listener().await;
+        let mut endpoint = make_test_endpoint();
+        endpoint.check_type = CheckType::Tcp;
+        endpoint.addr = format!("tcp://{addr}");
+        endpoint.local_address = Some(IpAddr::from([127, 0, 0, 1]));
+
+        let result = check_tcp("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_http_pins_requested_ip_version() {
+        let url = spawn_header_server().await;
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = url.replace("127.0.0.1", "localhost");
+        endpoint.ip_version = IpVersion::V4;
+
+        let result = check_http("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        let checked: Vec<IpAddr> = result.addresses.iter().map(|a| a.addr).collect();
+        assert_eq!(checked, vec![IpAddr::from([127, 0, 0, 1])]);
+    }
+
+    #[tokio::test]
+    async fn check_http_binds_local_address() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server().await;
+        endpoint.local_address = Some(IpAddr::from([127, 0, 0, 1]));
+
+        let result = check_http("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    // ============ Content Hash Tests ============
+
+    #[tokio::test]
+    async fn check_http_samples_content_when_enabled() {
+        let mut endpoint = make_test_endpoint();
+        endpoint.addr = spawn_header_server().await;
+
+        let result = check_http("test", &endpoint).await;
+        assert!(result.content.is_none());
+
+        endpoint.content_hash = ContentHash::Enabled(true);
+        let result = check_http("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.content.unwrap().text, "{}");
+    }
+
+    #[tokio::test]
+    async fn track_content_degrades_result_on_change() {
+        let endpoint = make_test_endpoint();
+        let mut tracker = content::ContentTracker::default();
+        let mut result = base_result("test", &endpoint);
+        result.status = CheckStatus::Up;
+
+        result.content = Some(content::sample("Welcome", &[]));
+        assert!(
+            track_content("test", &mut tracker, None, &mut result)
+                .await
+                .is_none()
+        );
+        assert!(!result.is_degraded());
+
+        result.content = Some(content::sample("Hacked", &[]));
+        let event = track_content("test", &mut tracker, None, &mut result).await;
+
+        let Some(alert::AlertEvent::ContentChanged(change)) = event else {
+            panic!("expected a content change alert, got {event:?}");
+        };
+        assert_eq!(change.diff, "- Welcome\n+ Hacked\n");
+        assert!(result.is_degraded());
+        assert!(result.warnings[0].starts_with("content changed since the last check"));
+    }
+
     // ============ check_all_endpoints Tests ============
 
     #[tokio::test]
@@ -946,15 +2660,15 @@ mod tests {
             let endpoint = make_test_endpoint();
 
             let mut result1 = base_result("zebra", &endpoint);
-            result1.is_up = true;
+            result1.status = CheckStatus::Up;
             state_guard.insert("zebra".to_string(), result1);
 
             let mut result2 = base_result("alpha", &endpoint);
-            result2.is_up = false;
+            result2.status = CheckStatus::Down;
             state_guard.insert("alpha".to_string(), result2);
 
             let mut result3 = base_result("middle", &endpoint);
-            result3.is_up = true;
+            result3.status = CheckStatus::Up;
             state_guard.insert("middle".to_string(), result3);
         }
 
@@ -975,7 +2689,7 @@ mod tests {
             let endpoint = make_test_endpoint();
 
             let mut result = base_result("test", &endpoint);
-            result.is_up = true;
+            result.status = CheckStatus::Up;
             result.status_code = Some(200);
             result.response_time_ms = Some(42);
             state_guard.insert("test".to_string(), result);
@@ -985,7 +2699,7 @@ mod tests {
 
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].name, "test");
-        assert!(results[0].is_up);
+        assert!(results[0].is_up());
         assert_eq!(results[0].status_code, Some(200));
         assert_eq!(results[0].response_time_ms, Some(42));
     }
@@ -0,0 +1,234 @@
+use std::net::IpAddr;
+
+use crate::config::{Endpoint, IpVersion};
+
+use super::{AddressResult, CheckResult, ErrorType};
+
+/// Whether the endpoint's checks connect to explicitly selected addresses
+/// instead of leaving the choice to the resolver
+pub(super) fn pins_addresses(endpoint: &Endpoint) -> bool {
+    endpoint.ip_version != IpVersion::Any || endpoint.all_addresses
+}
+
+/// Resolve `host` to its IP addresses, in resolver order without duplicates
+pub(super) async fn resolve(host: &str) -> Result<Vec<IpAddr>, String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse() {
+        return Ok(vec![ip]);
+    }
+
+    let mut ips = Vec::new();
+    for addr in tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("failed to resolve address: {e}"))?
+    {
+        if !ips.contains(&addr.ip()) {
+            ips.push(addr.ip());
+        }
+    }
+    Ok(ips)
+}
+
+/// Pick the addresses to check from `resolved` according to `ip_version`,
+/// `all_addresses` and the family of `local_address`
+pub(super) fn select(
+    endpoint: &Endpoint,
+    host: &str,
+    resolved: &[IpAddr],
+) -> Result<Vec<IpAddr>, String> {
+    let candidates: Vec<IpAddr> = resolved
+        .iter()
+        .copied()
+        .filter(|ip| endpoint.ip_version.allows(*ip))
+        .filter(|ip| {
+            endpoint
+                .local_address
+                .is_none_or(|local| local.is_ipv4() == ip.is_ipv4())
+        })
+        .collect();
+
+    let first_v4 = candidates.iter().copied().find(IpAddr::is_ipv4);
+    let first_v6 = candidates.iter().copied().find(IpAddr::is_ipv6);
+    let missing = match endpoint.ip_version {
+        IpVersion::V4 | IpVersion::Both if first_v4.is_none() => Some("IPv4 "),
+        IpVersion::V6 | IpVersion::Both if first_v6.is_none() => Some("IPv6 "),
+        _ if candidates.is_empty() => Some(""),
+        _ => None,
+    };
+    if let Some(family) = missing {
+        return Err(format!("no {family}addresses found for '{host}'"));
+    }
+
+    Ok(if endpoint.all_addresses {
+        candidates
+    } else if endpoint.ip_version == IpVersion::Both {
+        first_v4.into_iter().chain(first_v6).collect()
+    } else {
+        candidates.into_iter().take(1).collect()
+    })
+}
+
+/// Result for a host whose addresses could not be resolved or selected
+pub(super) fn unresolved(mut result: CheckResult, error: String) -> CheckResult {
+    result.error = Some(error);
+    result.error_type = Some(ErrorType::Dns);
+    result
+}
+
+/// Merge the results of checking each address into one result. The check is down
+/// if any address is down and reports the first failure, prefixed with its address.
+pub(super) fn combine(results: Vec<(IpAddr, CheckResult)>) -> CheckResult {
+    let addresses: Vec<AddressResult> = results
+        .iter()
+        .map(|(addr, result)| AddressResult {
+            addr: *addr,
+            status: result.status,
+            response_time_ms: result.response_time_ms,
+            error: result.error.clone(),
+        })
+        .collect();
+    let slowest = results
+        .iter()
+        .filter_map(|(_, result)| result.response_time_ms)
+        .max();
+    let warnings: Vec<String> = results
+        .iter()
+        .flat_map(|(addr, result)| {
+            result
+                .warnings
+                .iter()
+                .map(move |warning| format!("{addr}: {warning}"))
+        })
+        .collect();
+
+    let reported = results
+        .iter()
+        .position(|(_, result)| !result.is_up())
+        .unwrap_or(0);
+    let (addr, mut combined) = results
+        .into_iter()
+        .nth(reported)
+        .expect("at least one address is checked");
+
+    combined.error = combined.error.map(|error| format!("{addr}: {error}"));
+    combined.response_time_ms = slowest;
+    combined.warnings.clear();
+    if combined.is_up() {
+        for warning in warnings {
+            combined.degrade(warning);
+        }
+    }
+    combined.addresses = addresses;
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::super::{CheckStatus, base_result};
+    use super::*;
+
+    const V4_A: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const V4_B: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const V6_A: IpAddr = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+
+    fn make_endpoint(ip_version: IpVersion, all_addresses: bool) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.ip_version = ip_version;
+        endpoint.all_addresses = all_addresses;
+        endpoint
+    }
+
+    fn result_with(status: CheckStatus, error: Option<&str>) -> CheckResult {
+        let mut result = base_result("test", &make_endpoint(IpVersion::Any, false));
+        result.status = status;
+        result.error = error.map(ToString::to_string);
+        result
+    }
+
+    #[test]
+    fn select_filters_by_ip_version() {
+        let resolved = [V6_A, V4_A, V4_B];
+
+        let v4 = make_endpoint(IpVersion::V4, false);
+        assert_eq!(select(&v4, "host", &resolved), Ok(vec![V4_A]));
+
+        let v6 = make_endpoint(IpVersion::V6, false);
+        assert_eq!(select(&v6, "host", &resolved), Ok(vec![V6_A]));
+
+        let both = make_endpoint(IpVersion::Both, false);
+        assert_eq!(select(&both, "host", &resolved), Ok(vec![V4_A, V6_A]));
+    }
+
+    #[test]
+    fn select_returns_every_address_when_checking_all() {
+        let resolved = [V6_A, V4_A, V4_B];
+
+        let all = make_endpoint(IpVersion::Any, true);
+        assert_eq!(select(&all, "host", &resolved), Ok(resolved.to_vec()));
+
+        let all_v4 = make_endpoint(IpVersion::V4, true);
+        assert_eq!(select(&all_v4, "host", &resolved), Ok(vec![V4_A, V4_B]));
+    }
+
+    #[test]
+    fn select_fails_when_a_family_is_missing() {
+        let both = make_endpoint(IpVersion::Both, false);
+        assert_eq!(
+            select(&both, "host", &[V4_A]),
+            Err("no IPv6 addresses found for 'host'".to_string())
+        );
+
+        let v4 = make_endpoint(IpVersion::V4, true);
+        assert_eq!(
+            select(&v4, "host", &[V6_A]),
+            Err("no IPv4 addresses found for 'host'".to_string())
+        );
+    }
+
+    #[test]
+    fn select_matches_local_address_family() {
+        let mut endpoint = make_endpoint(IpVersion::Any, true);
+        endpoint.local_address = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        assert_eq!(select(&endpoint, "host", &[V6_A, V4_A]), Ok(vec![V4_A]));
+    }
+
+    #[tokio::test]
+    async fn resolve_accepts_ip_literals() {
+        assert_eq!(resolve("192.0.2.1").await, Ok(vec![V4_A]));
+        assert_eq!(resolve("[2001:db8::1]").await, Ok(vec![V6_A]));
+    }
+
+    #[test]
+    fn combine_reports_first_failing_address() {
+        let mut up = result_with(CheckStatus::Up, None);
+        up.response_time_ms = Some(40);
+        let mut down = result_with(CheckStatus::Down, Some("connection refused"));
+        down.response_time_ms = Some(5);
+
+        let combined = combine(vec![(V4_A, up), (V6_A, down)]);
+
+        assert_eq!(combined.status, CheckStatus::Down);
+        assert_eq!(
+            combined.error.as_deref(),
+            Some("2001:db8::1: connection refused")
+        );
+        assert_eq!(combined.response_time_ms, Some(40));
+        assert_eq!(combined.addresses.len(), 2);
+        assert_eq!(combined.addresses[0].status, CheckStatus::Up);
+    }
+
+    #[test]
+    fn combine_degrades_with_warnings_of_any_address() {
+        let up = result_with(CheckStatus::Up, None);
+        let mut degraded = result_with(CheckStatus::Up, None);
+        degraded.degrade("slow".to_string());
+
+        let combined = combine(vec![(V4_A, up), (V4_B, degraded)]);
+
+        assert_eq!(combined.status, CheckStatus::Degraded);
+        assert_eq!(combined.warnings, vec!["192.0.2.2: slow"]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::Endpoint;
+
+use super::{CheckResult, CheckStatus, ContentChange};
+
+/// Alert raised when a streak of check results crosses a threshold
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum AlertEvent {
+    /// `alert_after_failures` consecutive checks were down
+    Down { consecutive: u32 },
+    /// `alert_after_degraded` consecutive checks were degraded
+    Degraded { consecutive: u32 },
+    /// The endpoint is up again after a down or degraded alert
+    Recovered,
+    /// The response body differs from the last check (with `content_hash`)
+    ContentChanged(ContentChange),
+}
+
+/// Tracks consecutive down and degraded results for one endpoint.
+/// Down and degraded streaks are counted separately so each has its own threshold.
+#[derive(Debug, Default)]
+pub(super) struct AlertState {
+    consecutive_down: u32,
+    consecutive_degraded: u32,
+    alerting: bool,
+}
+
+impl AlertState {
+    /// Record a check result and return the alerts it triggers
+    pub(super) fn observe(&mut self, endpoint: &Endpoint, result: &CheckResult) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+
+        match result.status {
+            CheckStatus::Down => {
+                self.consecutive_down += 1;
+                self.consecutive_degraded = 0;
+                if self.consecutive_down == endpoint.alert_after_failures {
+                    self.alerting = true;
+                    events.push(AlertEvent::Down {
+                        consecutive: self.consecutive_down,
+                    });
+                }
+            }
+            CheckStatus::Degraded => {
+                self.consecutive_down = 0;
+                self.consecutive_degraded += 1;
+                if self.consecutive_degraded == endpoint.alert_after_degraded {
+                    self.alerting = true;
+                    events.push(AlertEvent::Degraded {
+                        consecutive: self.consecutive_degraded,
+                    });
+                }
+            }
+            CheckStatus::Up => {
+                self.consecutive_down = 0;
+                self.consecutive_degraded = 0;
+                if self.alerting {
+                    self.alerting = false;
+                    events.push(AlertEvent::Recovered);
+                }
+            }
+            // Fewer locations than the quorum see it down: no longer a down streak,
+            // but not recovered either
+            CheckStatus::PartialOutage => {
+                self.consecutive_down = 0;
+            }
+            // Nothing was checked, or the failure belongs to a parent that alerts
+            // itself
+            CheckStatus::NotMonitored | CheckStatus::Unreachable => {}
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::base_result;
+    use super::*;
+
+    fn make_endpoint(alert_after_failures: u32, alert_after_degraded: u32) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.alert_after_failures = alert_after_failures;
+        endpoint.alert_after_degraded = alert_after_degraded;
+        endpoint
+    }
+
+    fn result_with(status: CheckStatus) -> CheckResult {
+        let mut result = base_result("test", &make_endpoint(0, 0));
+        result.status = status;
+        result
+    }
+
+    #[test]
+    fn observe_alerts_once_when_down_threshold_reached() {
+        let endpoint = make_endpoint(2, 0);
+        let mut state = AlertState::default();
+        let down = result_with(CheckStatus::Down);
+
+        assert!(state.observe(&endpoint, &down).is_empty());
+        assert_eq!(
+            state.observe(&endpoint, &down),
+            vec![AlertEvent::Down { consecutive: 2 }]
+        );
+        assert!(state.observe(&endpoint, &down).is_empty());
+        assert_eq!(
+            state.observe(&endpoint, &result_with(CheckStatus::Up)),
+            vec![AlertEvent::Recovered]
+        );
+    }
+
+    #[test]
+    fn observe_counts_degraded_separately_from_down() {
+        let endpoint = make_endpoint(2, 3);
+        let mut state = AlertState::default();
+        let degraded = result_with(CheckStatus::Degraded);
+
+        assert!(state.observe(&endpoint, &degraded).is_empty());
+        assert!(state.observe(&endpoint, &degraded).is_empty());
+        assert_eq!(
+            state.observe(&endpoint, &degraded),
+            vec![AlertEvent::Degraded { consecutive: 3 }]
+        );
+    }
+
+    #[test]
+    fn observe_does_not_alert_on_partial_outage() {
+        let endpoint = make_endpoint(2, 0);
+        let mut state = AlertState::default();
+        let down = result_with(CheckStatus::Down);
+        let partial = result_with(CheckStatus::PartialOutage);
+
+        assert!(state.observe(&endpoint, &down).is_empty());
+        assert!(state.observe(&endpoint, &partial).is_empty());
+        assert!(state.observe(&endpoint, &down).is_empty());
+        assert_eq!(
+            state.observe(&endpoint, &down),
+            vec![AlertEvent::Down { consecutive: 2 }]
+        );
+        // Still alerting until all locations see it up
+        assert!(state.observe(&endpoint, &partial).is_empty());
+        assert_eq!(
+            state.observe(&endpoint, &result_with(CheckStatus::Up)),
+            vec![AlertEvent::Recovered]
+        );
+    }
+
+    #[test]
+    fn observe_never_alerts_with_zero_thresholds() {
+        let endpoint = make_endpoint(0, 0);
+        let mut state = AlertState::default();
+
+        for status in [CheckStatus::Down, CheckStatus::Degraded, CheckStatus::Up] {
+            assert!(state.observe(&endpoint, &result_with(status)).is_empty());
+        }
+    }
+}
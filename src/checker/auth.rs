@@ -0,0 +1,519 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use md5::Md5;
+use reqwest::Client;
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::config::{AuthConfig, substitute_env_vars};
+
+/// Tokens are refreshed this long before they expire
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Lifetime assumed for tokens returned without `expires_in`
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_mins(5);
+
+/// Identifies a token by everything that was used to request it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TokenKey {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+}
+
+/// A token together with the moment it stops being usable
+#[derive(Debug, Clone)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+/// `OAuth2` tokens shared by all checks, keyed by their request parameters
+static TOKEN_CACHE: LazyLock<Mutex<HashMap<TokenKey, CachedToken>>> = LazyLock::new(Mutex::default);
+
+/// Lock the token cache. Entries are inserted and removed whole, so a panic while
+/// the lock was held can't leave one half-written.
+fn cached_tokens() -> MutexGuard<'static, HashMap<TokenKey, CachedToken>> {
+    TOKEN_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Successful response from an `OAuth2` token endpoint
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// Build the cache key for an `OAuth2` auth section, or `None` for other auth types
+fn token_key(auth: &AuthConfig) -> Option<TokenKey> {
+    match auth {
+        AuthConfig::OAuth2ClientCredentials {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        } => Some(TokenKey {
+            token_url: substitute_env_vars(token_url),
+            client_id: substitute_env_vars(client_id),
+            client_secret: substitute_env_vars(client_secret),
+            scope: scope.clone(),
+        }),
+        AuthConfig::Basic { .. } | AuthConfig::Digest { .. } | AuthConfig::SshKey { .. } => None,
+    }
+}
+
+/// Get a bearer token for an `OAuth2` auth section, from the cache when still valid.
+/// Returns `Ok(None)` for auth types that don't use tokens.
+pub(super) async fn bearer_token(
+    client: &Client,
+    auth: &AuthConfig,
+) -> Result<Option<String>, String> {
+    let Some(key) = token_key(auth) else {
+        return Ok(None);
+    };
+
+    if let Some(token) = cached_tokens()
+        .get(&key)
+        .filter(|t| t.expires_at > Instant::now())
+    {
+        return Ok(Some(token.access_token.clone()));
+    }
+
+    let token = fetch_token(client, &key).await?;
+    let access_token = token.access_token.clone();
+    cached_tokens().insert(key, token);
+
+    Ok(Some(access_token))
+}
+
+/// Drop a cached token, e.g. after the target rejected it
+pub(super) fn invalidate_token(auth: &AuthConfig) {
+    if let Some(key) = token_key(auth) {
+        cached_tokens().remove(&key);
+    }
+}
+
+/// Request a new token from the token endpoint
+async fn fetch_token(client: &Client, key: &TokenKey) -> Result<CachedToken, String> {
+    let form = {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "client_credentials");
+        form.append_pair("client_id", &key.client_id);
+        form.append_pair("client_secret", &key.client_secret);
+        if let Some(ref scope) = key.scope {
+            form.append_pair("scope", scope);
+        }
+        form.finish()
+    };
+
+    let requested_at = Instant::now();
+    let response = client
+        .post(&key.token_url)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(reqwest::header::ACCEPT, "application/json")
+        .body(form)
+        .send()
+        .await
+        .map_err(|e| format!("token request failed: {e}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "token endpoint returned status {}",
+            status.as_u16()
+        ));
+    }
+
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("invalid token response: {e}"))?;
+
+    let lifetime = token
+        .expires_in
+        .map_or(DEFAULT_TOKEN_LIFETIME, Duration::from_secs)
+        .saturating_sub(TOKEN_EXPIRY_MARGIN);
+
+    Ok(CachedToken {
+        access_token: token.access_token,
+        expires_at: requested_at + lifetime,
+    })
+}
+
+/// Build an `Authorization` header answering the Digest challenge in a 401 response.
+/// Returns `None` if Digest auth isn't configured or the response has no supported challenge.
+pub(super) fn digest_challenge_response(
+    auth: &AuthConfig,
+    method: &reqwest::Method,
+    response: &reqwest::Response,
+) -> Option<String> {
+    let AuthConfig::Digest { username, password } = auth else {
+        return None;
+    };
+    if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return None;
+    }
+
+    response
+        .headers()
+        .get_all(reqwest::header::WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(|challenge| {
+            digest_authorization(
+                &substitute_env_vars(username),
+                &substitute_env_vars(password),
+                method.as_str(),
+                response.url(),
+                challenge,
+            )
+        })
+}
+
+/// Build an `Authorization` header answering a Digest `WWW-Authenticate` challenge.
+/// Returns `None` if the challenge isn't a supported Digest challenge.
+fn digest_authorization(
+    username: &str,
+    password: &str,
+    method: &str,
+    url: &reqwest::Url,
+    challenge: &str,
+) -> Option<String> {
+    let cnonce = ulid::Ulid::new().to_string().to_lowercase();
+    digest_authorization_with_cnonce(username, password, method, url, challenge, &cnonce)
+}
+
+fn digest_authorization_with_cnonce(
+    username: &str,
+    password: &str,
+    method: &str,
+    url: &reqwest::Url,
+    challenge: &str,
+    cnonce: &str,
+) -> Option<String> {
+    let params = parse_digest_challenge(challenge)?;
+    let realm = params.get("realm")?;
+    let nonce = params.get("nonce")?;
+    let algorithm = params.get("algorithm").map_or("MD5", String::as_str);
+
+    let hash: fn(&str) -> String = match algorithm.to_ascii_uppercase().as_str() {
+        "MD5" | "MD5-SESS" => |s| hex(&Md5::digest(s.as_bytes())),
+        "SHA-256" | "SHA-256-SESS" => |s| hex(&Sha256::digest(s.as_bytes())),
+        _ => return None,
+    };
+
+    let uri = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let nc = "00000001";
+
+    let mut ha1 = hash(&format!("{username}:{realm}:{password}"));
+    if algorithm.to_ascii_uppercase().ends_with("-SESS") {
+        ha1 = hash(&format!("{ha1}:{nonce}:{cnonce}"));
+    }
+    let ha2 = hash(&format!("{method}:{uri}"));
+
+    // Only "auth" quality of protection is supported, fall back to RFC 2069 without it
+    let qop = params
+        .get("qop")
+        .filter(|q| q.split(',').any(|v| v.trim() == "auth"));
+    let response = match qop {
+        Some(_) => hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}")),
+        None => hash(&format!("{ha1}:{nonce}:{ha2}")),
+    };
+
+    let mut header = format!(
+        r#"Digest username="{username}", realm="{realm}", nonce="{nonce}", uri="{uri}", algorithm={algorithm}, response="{response}""#
+    );
+    if qop.is_some() {
+        let _ = write!(header, r#", qop=auth, nc={nc}, cnonce="{cnonce}""#);
+    }
+    if let Some(opaque) = params.get("opaque") {
+        let _ = write!(header, r#", opaque="{opaque}""#);
+    }
+
+    Some(header)
+}
+
+/// Parse the parameters of a `Digest ...` challenge
+fn parse_digest_challenge(challenge: &str) -> Option<HashMap<String, String>> {
+    let (scheme, rest) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("digest") {
+        return None;
+    }
+
+    let mut params = HashMap::new();
+    let mut rest = rest.trim();
+
+    while !rest.is_empty() {
+        let (key, after_key) = rest.split_once('=')?;
+        let key = key.trim().to_ascii_lowercase();
+
+        let (value, remaining) = if let Some(quoted) = after_key.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            after_key.split_once(',').unwrap_or((after_key, ""))
+        };
+
+        params.insert(key, value.trim().to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    Some(params)
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::checker::{ErrorType, check_http};
+    use crate::config::Endpoint;
+    use axum::{
+        Router,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+    };
+
+    // ============ Digest Tests ============
+
+    #[test]
+    fn parse_digest_challenge_reads_quoted_and_bare_values() {
+        let params = parse_digest_challenge(
+            r#"Digest realm="test, realm", qop="auth,auth-int", nonce="abc", algorithm=MD5, stale=false"#,
+        )
+        .unwrap();
+
+        assert_eq!(params.get("realm").unwrap(), "test, realm");
+        assert_eq!(params.get("qop").unwrap(), "auth,auth-int");
+        assert_eq!(params.get("nonce").unwrap(), "abc");
+        assert_eq!(params.get("algorithm").unwrap(), "MD5");
+        assert_eq!(params.get("stale").unwrap(), "false");
+    }
+
+    #[test]
+    fn parse_digest_challenge_rejects_other_schemes() {
+        assert!(parse_digest_challenge(r#"Basic realm="test""#).is_none());
+    }
+
+    #[test]
+    fn digest_authorization_matches_rfc_2617_example() {
+        let url = reqwest::Url::parse("http://www.nowhere.org/dir/index.html").unwrap();
+        let header = digest_authorization_with_cnonce(
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            &url,
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+            "0a4f113b",
+        )
+        .unwrap();
+
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+        assert!(header.contains(r#"uri="/dir/index.html""#));
+        assert!(header.contains(r#"cnonce="0a4f113b""#));
+        assert!(header.contains(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+    }
+
+    #[test]
+    fn digest_authorization_rejects_unknown_algorithm() {
+        let url = reqwest::Url::parse("http://example.com/").unwrap();
+        let header = digest_authorization(
+            "user",
+            "pass",
+            "GET",
+            &url,
+            r#"Digest realm="r", nonce="n", algorithm=SHA-512-256"#,
+        );
+        assert!(header.is_none());
+    }
+
+    // ============ check_http Auth Tests ============
+
+    fn make_auth_endpoint(addr: &str, auth: AuthConfig) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str(&format!(
+            r#"
+            addr = "{addr}"
+            timeout = 5
+            "#
+        ))
+        .unwrap();
+        endpoint.auth = Some(auth);
+        endpoint
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    fn oauth2(base: &str, client_id: &str) -> AuthConfig {
+        AuthConfig::OAuth2ClientCredentials {
+            token_url: format!("{base}/token"),
+            client_id: client_id.to_string(),
+            client_secret: "s3cret".to_string(),
+            scope: Some("read".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn check_http_fetches_and_caches_oauth2_token() {
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&token_requests);
+
+        let app = Router::new()
+            .route(
+                "/token",
+                post(move |body: String| {
+                    let counter = Arc::clone(&counter);
+                    async move {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        assert!(body.contains("grant_type=client_credentials"));
+                        assert!(body.contains("scope=read"));
+                        r#"{"access_token": "tok-1", "token_type": "Bearer", "expires_in": 3600}"#
+                    }
+                }),
+            )
+            .route(
+                "/api",
+                get(|headers: HeaderMap| async move {
+                    if headers
+                        .get("authorization")
+                        .is_some_and(|v| v == "Bearer tok-1")
+                    {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::UNAUTHORIZED
+                    }
+                }),
+            );
+        let base = serve(app).await;
+        let endpoint = make_auth_endpoint(&format!("{base}/api"), oauth2(&base, "cache-test"));
+
+        let first = check_http("api", &endpoint).await;
+        let second = check_http("api", &endpoint).await;
+
+        assert!(first.is_up(), "unexpected error: {:?}", first.error);
+        assert!(second.is_up(), "unexpected error: {:?}", second.error);
+        assert_eq!(token_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn check_http_reports_token_endpoint_failure() {
+        let app = Router::new().route("/token", post(|| async { StatusCode::BAD_REQUEST }));
+        let base = serve(app).await;
+        let endpoint = make_auth_endpoint(&format!("{base}/api"), oauth2(&base, "failure-test"));
+
+        let result = check_http("api", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::AuthToken));
+        assert!(result.error.unwrap().contains("status 400"));
+    }
+
+    #[tokio::test]
+    async fn check_http_sends_basic_auth() {
+        let app = Router::new().route(
+            "/",
+            get(|headers: HeaderMap| async move {
+                // "admin:secret" in base64
+                if headers
+                    .get("authorization")
+                    .is_some_and(|v| v == "Basic YWRtaW46c2VjcmV0")
+                {
+                    StatusCode::OK
+                } else {
+                    StatusCode::UNAUTHORIZED
+                }
+            }),
+        );
+        let base = serve(app).await;
+        let endpoint = make_auth_endpoint(
+            &format!("{base}/"),
+            AuthConfig::Basic {
+                username: "admin".to_string(),
+                password: Some("secret".to_string()),
+            },
+        );
+
+        let result = check_http("basic", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_http_answers_digest_challenge() {
+        let app = Router::new().route(
+            "/private",
+            get(|headers: HeaderMap| async move {
+                let challenge = r#"Digest realm="test", qop="auth", nonce="n0nce", opaque="op""#;
+                match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                    Some(auth) if auth.starts_with("Digest ") => {
+                        // Recompute the expected response from the client's cnonce
+                        let params = parse_digest_challenge(auth).unwrap();
+                        let url = reqwest::Url::parse("http://localhost/private").unwrap();
+                        let expected = digest_authorization_with_cnonce(
+                            "admin",
+                            "secret",
+                            "GET",
+                            &url,
+                            challenge,
+                            params.get("cnonce").unwrap(),
+                        )
+                        .unwrap();
+                        let expected = parse_digest_challenge(&expected).unwrap();
+                        if params.get("response") == expected.get("response") {
+                            (StatusCode::OK, HeaderMap::new())
+                        } else {
+                            (StatusCode::FORBIDDEN, HeaderMap::new())
+                        }
+                    }
+                    _ => {
+                        let mut response_headers = HeaderMap::new();
+                        response_headers.insert("www-authenticate", challenge.parse().unwrap());
+                        (StatusCode::UNAUTHORIZED, response_headers)
+                    }
+                }
+            }),
+        );
+        let base = serve(app).await;
+        let endpoint = make_auth_endpoint(
+            &format!("{base}/private"),
+            AuthConfig::Digest {
+                username: "admin".to_string(),
+                password: "secret".to_string(),
+            },
+        );
+
+        let result = check_http("digest", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert_eq!(result.status_code, Some(200));
+    }
+}
@@ -0,0 +1,280 @@
+mod amqp;
+mod kafka;
+mod mqtt;
+
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use ulid::Ulid;
+use url::Url;
+
+use super::registry::{self, Checker, Validation};
+use super::{
+    CertificateInfo, CheckResult, CheckStatus, ErrorType, base_result, credentials,
+    open_connection, tls,
+};
+use crate::config::{CheckType, Endpoint};
+
+type BrokerError = (ErrorType, String);
+
+/// Step of a broker check, each reported with its own error type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Connect,
+    Handshake,
+    Authenticate,
+    Subscribe,
+    Publish,
+    Consume,
+}
+
+impl Step {
+    fn error_type(self) -> ErrorType {
+        match self {
+            Step::Connect => ErrorType::Connection,
+            Step::Handshake | Step::Subscribe | Step::Publish => ErrorType::BrokerError,
+            Step::Authenticate => ErrorType::BrokerAuth,
+            Step::Consume => ErrorType::ProbeLost,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Step::Connect => "connect",
+            Step::Handshake => "handshake",
+            Step::Authenticate => "authentication",
+            Step::Subscribe => "subscribe",
+            Step::Publish => "publish",
+            Step::Consume => "consume",
+        }
+    }
+
+    /// Error of this step
+    fn fail(self, message: impl std::fmt::Display) -> BrokerError {
+        (self.error_type(), format!("{}: {message}", self.describe()))
+    }
+}
+
+/// Options of a broker check, from its `[mqtt]`, `[amqp]` or `[kafka]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BrokerConfig {
+    /// Topic (MQTT, Kafka) or `amq.topic` routing key (AMQP) to publish a probe
+    /// message to and consume it back from
+    probe_topic: Option<String>,
+    /// Fail the check when the probe round trip takes longer than this (ms)
+    max_round_trip_ms: Option<u64>,
+}
+
+/// mqtt, amqp and kafka
+pub(super) struct BrokerChecker;
+
+impl Checker for BrokerChecker {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let mut validation = Validation::default();
+
+        // Broker addresses are URLs, the scheme with an "s" suffix selects TLS
+        let resolved_addr = endpoint.resolved_addr();
+        let scheme = endpoint.check_type.as_str();
+        match Url::parse(&resolved_addr) {
+            Ok(url)
+                if (url.scheme() == scheme || url.scheme() == format!("{scheme}s"))
+                    && url.host_str().is_some() => {}
+            Ok(_) => validation.errors.push(format!(
+                "broker address '{}' should be a {scheme}:// or {scheme}s:// URL with a host",
+                endpoint.addr
+            )),
+            Err(e) => validation
+                .errors
+                .push(format!("invalid URL '{resolved_addr}': {e}")),
+        }
+
+        let config = match endpoint.section::<BrokerConfig>() {
+            Ok(config) => config.unwrap_or_default(),
+            Err(e) => {
+                validation.errors.push(e);
+                return validation;
+            }
+        };
+        if let Some(ref topic) = config.probe_topic {
+            if topic.is_empty() {
+                validation
+                    .errors
+                    .push("probe_topic must not be empty".to_string());
+            } else if endpoint.check_type == CheckType::Mqtt && topic.contains(['+', '#']) {
+                validation.errors.push(format!(
+                    "probe_topic '{topic}' must not contain MQTT wildcards (+ or #)"
+                ));
+            }
+        }
+        let timeout_ms = endpoint.timeout.saturating_mul(1000);
+        if let Some(max) = config.max_round_trip_ms {
+            if max >= timeout_ms {
+                validation.errors.push(format!(
+                    "max_round_trip_ms ({max}) must be less than the timeout ({timeout_ms}ms)"
+                ));
+            }
+            if config.probe_topic.is_none() {
+                validation
+                    .warnings
+                    .push("max_round_trip_ms is only used with probe_topic".to_string());
+            }
+        }
+
+        validation
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(async move {
+            match endpoint.section() {
+                Ok(config) => check_broker(name, endpoint, &config.unwrap_or_default()).await,
+                Err(e) => registry::section_error(name, endpoint, e),
+            }
+        })
+    }
+}
+
+/// Broker address and options of a check
+struct Target<'a> {
+    endpoint: &'a Endpoint,
+    url: Url,
+    host: String,
+    port: u16,
+    /// Whether the address uses the TLS scheme (mqtts, amqps, kafkas)
+    tls: bool,
+    credentials: Option<(String, String)>,
+    probe_topic: Option<String>,
+}
+
+impl<'a> Target<'a> {
+    fn parse(endpoint: &'a Endpoint, config: &BrokerConfig) -> Result<Self, String> {
+        let addr = endpoint.resolved_addr();
+        let url = Url::parse(&addr).map_err(|e| format!("invalid URL '{addr}': {e}"))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("broker address '{addr}' has no host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let tls = url.scheme().ends_with('s');
+        let port = url
+            .port()
+            .unwrap_or_else(|| default_port(&endpoint.check_type, tls));
+
+        Ok(Self {
+            endpoint,
+            url,
+            host,
+            port,
+            tls,
+            credentials: credentials(endpoint),
+            probe_topic: config.probe_topic.clone(),
+        })
+    }
+
+    /// Open a connection to `host:port`, with TLS if the address asks for it
+    async fn open(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<(Box<dyn tls::Stream>, Option<CertificateInfo>), BrokerError> {
+        let stream = open_connection(self.endpoint, host, port).await?;
+        if !self.tls {
+            return Ok((Box::new(stream), None));
+        }
+        let (stream, certificate) = tls::connect(stream, self.endpoint, host).await?;
+        Ok((Box::new(stream), certificate))
+    }
+}
+
+/// Default port of a broker protocol
+fn default_port(check_type: &CheckType, tls: bool) -> u16 {
+    match check_type {
+        CheckType::Mqtt if tls => 8883,
+        CheckType::Mqtt => 1883,
+        CheckType::Amqp if tls => 5671,
+        CheckType::Amqp => 5672,
+        _ if tls => 9093,
+        _ => 9092,
+    }
+}
+
+/// Unique body of a probe message, so a check only accepts its own message
+fn probe_payload() -> String {
+    format!("uptime-forge probe {}", Ulid::new())
+}
+
+/// Perform an MQTT, AMQP or Kafka check: connect and authenticate, then publish a
+/// probe message to `probe_topic` and consume it back if set
+async fn check_broker(name: &str, endpoint: &Endpoint, config: &BrokerConfig) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let target = match Target::parse(endpoint, config) {
+        Ok(target) => target,
+        Err(e) => {
+            result.error = Some(e);
+            result.error_type = Some(ErrorType::Connection);
+            return result;
+        }
+    };
+
+    let start = Instant::now();
+    let mut step = Step::Connect;
+    let outcome = tokio::time::timeout(
+        Duration::from_secs(endpoint.timeout),
+        converse(&target, &mut step),
+    )
+    .await;
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+
+    match outcome {
+        Ok(Ok((round_trip, certificate))) => {
+            result.status = CheckStatus::Up;
+            result.certificate = certificate;
+            result.round_trip_ms =
+                round_trip.map(|rtt| u64::try_from(rtt.as_millis()).unwrap_or(u64::MAX));
+            if let (Some(elapsed), Some(limit)) = (result.round_trip_ms, config.max_round_trip_ms)
+                && elapsed >= limit
+            {
+                result.status = CheckStatus::Down;
+                result.error = Some(format!(
+                    "probe round trip {elapsed}ms exceeds max_round_trip_ms ({limit}ms)"
+                ));
+                result.error_type = Some(ErrorType::ProbeSlow);
+            }
+        }
+        Ok(Err((error_type, error))) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
+        }
+        Err(_) if step == Step::Consume => {
+            result.error = Some(format!(
+                "probe message not received within {}s",
+                endpoint.timeout
+            ));
+            result.error_type = Some(ErrorType::ProbeLost);
+        }
+        Err(_) => {
+            result.error = Some(format!("timed out during {}", step.describe()));
+            result.error_type = Some(ErrorType::Timeout);
+        }
+    }
+
+    result
+}
+
+/// Connect and run the protocol, keeping `step` up to date so a timeout can tell
+/// where the broker stopped answering. Returns the probe round trip, if any.
+async fn converse(
+    target: &Target<'_>,
+    step: &mut Step,
+) -> Result<(Option<Duration>, Option<CertificateInfo>), BrokerError> {
+    let (stream, certificate) = target.open(&target.host, target.port).await?;
+    let round_trip = match target.endpoint.check_type {
+        CheckType::Mqtt => mqtt::check(stream, target, step).await?,
+        CheckType::Amqp => amqp::check(stream, target, step).await?,
+        _ => kafka::check(stream, target, step).await?,
+    };
+    Ok((round_trip, certificate))
+}
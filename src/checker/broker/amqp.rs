@@ -0,0 +1,596 @@
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+use super::{BrokerError, Step, Target, probe_payload};
+use crate::checker::tls;
+
+const PROTOCOL_HEADER: &[u8] = b"AMQP\x00\x00\x09\x01";
+const FRAME_END: u8 = 0xce;
+
+const FRAME_METHOD: u8 = 1;
+const FRAME_HEADER: u8 = 2;
+const FRAME_BODY: u8 = 3;
+
+const CONNECTION_START: (u16, u16) = (10, 10);
+const CONNECTION_START_OK: (u16, u16) = (10, 11);
+const CONNECTION_TUNE: (u16, u16) = (10, 30);
+const CONNECTION_TUNE_OK: (u16, u16) = (10, 31);
+const CONNECTION_OPEN: (u16, u16) = (10, 40);
+const CONNECTION_OPEN_OK: (u16, u16) = (10, 41);
+const CONNECTION_CLOSE: (u16, u16) = (10, 50);
+const CHANNEL_OPEN: (u16, u16) = (20, 10);
+const CHANNEL_OPEN_OK: (u16, u16) = (20, 11);
+const CHANNEL_CLOSE: (u16, u16) = (20, 40);
+const QUEUE_DECLARE: (u16, u16) = (50, 10);
+const QUEUE_DECLARE_OK: (u16, u16) = (50, 11);
+const QUEUE_BIND: (u16, u16) = (50, 20);
+const QUEUE_BIND_OK: (u16, u16) = (50, 21);
+const BASIC_CONSUME: (u16, u16) = (60, 20);
+const BASIC_CONSUME_OK: (u16, u16) = (60, 21);
+const BASIC_PUBLISH: (u16, u16) = (60, 40);
+const BASIC_DELIVER: (u16, u16) = (60, 60);
+
+/// Exchange the probe queue is bound to, present on every broker
+const PROBE_EXCHANGE: &str = "amq.topic";
+
+/// Largest frame accepted from the broker
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+struct Frame {
+    kind: u8,
+    channel: u16,
+    payload: Vec<u8>,
+}
+
+/// Reads the fields of a frame payload
+struct Fields<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (head, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn shortstr(&mut self) -> Option<String> {
+        let len = self.u8()?;
+        Some(String::from_utf8_lossy(self.take(usize::from(len))?).into_owned())
+    }
+
+    /// Long string or field table, which share the same length prefix
+    fn longstr(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.u32()?).ok()?;
+        self.take(len)
+    }
+}
+
+fn put_shortstr(buf: &mut Vec<u8>, value: &str) {
+    let len = u8::try_from(value.len()).unwrap_or(u8::MAX);
+    buf.push(len);
+    buf.extend_from_slice(&value.as_bytes()[..usize::from(len)]);
+}
+
+fn put_longstr(buf: &mut Vec<u8>, value: &[u8]) {
+    let len = u32::try_from(value.len()).unwrap_or(u32::MAX);
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// AMQP 0-9-1 connection over a connected stream
+struct Connection {
+    stream: BufReader<Box<dyn tls::Stream>>,
+}
+
+impl Connection {
+    async fn send_frame(
+        &mut self,
+        step: Step,
+        kind: u8,
+        channel: u16,
+        payload: &[u8],
+    ) -> Result<(), BrokerError> {
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&channel.to_be_bytes());
+        put_longstr(&mut frame, payload);
+        frame.push(FRAME_END);
+        self.stream
+            .get_mut()
+            .write_all(&frame)
+            .await
+            .map_err(|e| step.fail(e))
+    }
+
+    async fn send_method(
+        &mut self,
+        step: Step,
+        channel: u16,
+        (class, method): (u16, u16),
+        arguments: &[u8],
+    ) -> Result<(), BrokerError> {
+        let mut payload = Vec::with_capacity(4 + arguments.len());
+        payload.extend_from_slice(&class.to_be_bytes());
+        payload.extend_from_slice(&method.to_be_bytes());
+        payload.extend_from_slice(arguments);
+        self.send_frame(step, FRAME_METHOD, channel, &payload).await
+    }
+
+    /// Read the next frame, skipping heartbeats
+    async fn receive(&mut self, step: Step) -> Result<Frame, BrokerError> {
+        let closed = |e: std::io::Error| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                step.fail("connection closed by broker")
+            } else {
+                step.fail(e)
+            }
+        };
+        loop {
+            let mut header = [0; 7];
+            self.stream.read_exact(&mut header).await.map_err(closed)?;
+            let len = usize::try_from(u32::from_be_bytes([
+                header[3], header[4], header[5], header[6],
+            ]))
+            .unwrap_or(usize::MAX);
+            if len > MAX_FRAME_LEN {
+                return Err(step.fail(format!("frame of {len} bytes is too large")));
+            }
+            let mut payload = vec![0; len + 1];
+            self.stream.read_exact(&mut payload).await.map_err(closed)?;
+            if payload.pop() != Some(FRAME_END) {
+                return Err(step.fail("malformed frame"));
+            }
+            let kind = header[0];
+            if kind == FRAME_METHOD || kind == FRAME_HEADER || kind == FRAME_BODY {
+                return Ok(Frame {
+                    kind,
+                    channel: u16::from_be_bytes([header[1], header[2]]),
+                    payload,
+                });
+            }
+        }
+    }
+
+    /// Read method frames until `expected` arrives, returning its arguments.
+    /// A close from the broker is reported with its reply code and text.
+    async fn expect(&mut self, step: Step, expected: (u16, u16)) -> Result<Vec<u8>, BrokerError> {
+        loop {
+            let frame = self.receive(step).await?;
+            if frame.kind != FRAME_METHOD {
+                continue;
+            }
+            let mut fields = Fields {
+                buf: &frame.payload,
+            };
+            let method = fields
+                .u16()
+                .zip(fields.u16())
+                .ok_or_else(|| step.fail("malformed method frame"))?;
+            if method == expected {
+                return Ok(fields.buf.to_vec());
+            }
+            if method == CONNECTION_CLOSE || method == CHANNEL_CLOSE {
+                let code = fields.u16().unwrap_or_default();
+                let text = fields.shortstr().unwrap_or_default();
+                return Err(step.fail(format!("broker closed the connection: {code} {text}")));
+            }
+            if frame.channel == 0 && method.0 == 10 {
+                return Err(step.fail(format!("unexpected method {}.{}", method.0, method.1)));
+            }
+        }
+    }
+}
+
+/// Arguments of connection.start-ok, logging in with PLAIN
+fn start_ok(username: &str, password: &str) -> Vec<u8> {
+    let mut properties = Vec::new();
+    put_shortstr(&mut properties, "product");
+    properties.push(b'S');
+    put_longstr(&mut properties, b"uptime-forge");
+
+    let mut arguments = Vec::new();
+    put_longstr(&mut arguments, &properties);
+    put_shortstr(&mut arguments, "PLAIN");
+    put_longstr(
+        &mut arguments,
+        format!("\0{username}\0{password}").as_bytes(),
+    );
+    put_shortstr(&mut arguments, "en_US");
+    arguments
+}
+
+/// Virtual host from the address path, where "/" is the default and "/%2f" spells it out
+fn virtual_host(target: &Target<'_>) -> String {
+    let path = target.url.path().trim_start_matches('/');
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    url::form_urlencoded::parse(format!("v={path}").as_bytes())
+        .next()
+        .map_or_else(|| path.to_string(), |(_, value)| value.into_owned())
+}
+
+/// Connect and log in, then publish to `probe_topic` on the `amq.topic` exchange
+/// and wait for the message on a temporary queue bound to it
+pub(super) async fn check(
+    stream: Box<dyn tls::Stream>,
+    target: &Target<'_>,
+    step: &mut Step,
+) -> Result<Option<Duration>, BrokerError> {
+    let mut connection = Connection {
+        stream: BufReader::new(stream),
+    };
+
+    *step = Step::Handshake;
+    connection
+        .stream
+        .get_mut()
+        .write_all(PROTOCOL_HEADER)
+        .await
+        .map_err(|e| step.fail(e))?;
+    let start = connection.expect(*step, CONNECTION_START).await?;
+    let mut fields = Fields { buf: &start };
+    let mechanisms = fields
+        .take(2)
+        .and_then(|_| fields.longstr())
+        .and_then(|_| fields.longstr())
+        .ok_or_else(|| step.fail("malformed connection.start"))?;
+    if !String::from_utf8_lossy(mechanisms)
+        .split_whitespace()
+        .any(|mechanism| mechanism == "PLAIN")
+    {
+        return Err(step.fail("broker does not offer PLAIN authentication"));
+    }
+
+    *step = Step::Authenticate;
+    let (username, password) = target
+        .credentials
+        .as_ref()
+        .map_or(("guest", "guest"), |(u, p)| (u.as_str(), p.as_str()));
+    connection
+        .send_method(*step, 0, CONNECTION_START_OK, &start_ok(username, password))
+        .await?;
+    let tune = connection.expect(*step, CONNECTION_TUNE).await?;
+    let mut fields = Fields { buf: &tune };
+    let channel_max = fields.u16().unwrap_or_default();
+    let frame_max = fields.u32().unwrap_or_default();
+    let mut tune_ok = Vec::new();
+    tune_ok.extend_from_slice(&channel_max.to_be_bytes());
+    tune_ok.extend_from_slice(&frame_max.to_be_bytes());
+    tune_ok.extend_from_slice(&0u16.to_be_bytes()); // no heartbeats
+    connection
+        .send_method(*step, 0, CONNECTION_TUNE_OK, &tune_ok)
+        .await?;
+
+    let mut open = Vec::new();
+    put_shortstr(&mut open, &virtual_host(target));
+    open.extend_from_slice(&[0, 0]);
+    connection
+        .send_method(*step, 0, CONNECTION_OPEN, &open)
+        .await?;
+    connection.expect(*step, CONNECTION_OPEN_OK).await?;
+
+    let round_trip = match target.probe_topic {
+        Some(ref topic) => Some(probe(&mut connection, topic, step).await?),
+        None => None,
+    };
+
+    let mut close = Vec::new();
+    close.extend_from_slice(&200u16.to_be_bytes());
+    put_shortstr(&mut close, "bye");
+    close.extend_from_slice(&[0, 0, 0, 0]);
+    connection
+        .send_method(*step, 0, CONNECTION_CLOSE, &close)
+        .await?;
+    Ok(round_trip)
+}
+
+async fn probe(
+    connection: &mut Connection,
+    topic: &str,
+    step: &mut Step,
+) -> Result<Duration, BrokerError> {
+    *step = Step::Subscribe;
+    connection.send_method(*step, 1, CHANNEL_OPEN, &[0]).await?;
+    connection.expect(*step, CHANNEL_OPEN_OK).await?;
+
+    let mut declare = vec![0, 0];
+    put_shortstr(&mut declare, "");
+    declare.push(0x0c); // exclusive, auto-delete
+    put_longstr(&mut declare, &[]);
+    connection
+        .send_method(*step, 1, QUEUE_DECLARE, &declare)
+        .await?;
+    let declared = connection.expect(*step, QUEUE_DECLARE_OK).await?;
+    let queue = Fields { buf: &declared }
+        .shortstr()
+        .ok_or_else(|| step.fail("malformed queue.declare-ok"))?;
+
+    let mut bind = vec![0, 0];
+    put_shortstr(&mut bind, &queue);
+    put_shortstr(&mut bind, PROBE_EXCHANGE);
+    put_shortstr(&mut bind, topic);
+    bind.push(0);
+    put_longstr(&mut bind, &[]);
+    connection.send_method(*step, 1, QUEUE_BIND, &bind).await?;
+    connection.expect(*step, QUEUE_BIND_OK).await?;
+
+    let mut consume = vec![0, 0];
+    put_shortstr(&mut consume, &queue);
+    put_shortstr(&mut consume, "");
+    consume.push(0x02); // no-ack
+    put_longstr(&mut consume, &[]);
+    connection
+        .send_method(*step, 1, BASIC_CONSUME, &consume)
+        .await?;
+    connection.expect(*step, BASIC_CONSUME_OK).await?;
+
+    *step = Step::Publish;
+    let payload = probe_payload();
+    let mut publish = vec![0, 0];
+    put_shortstr(&mut publish, PROBE_EXCHANGE);
+    put_shortstr(&mut publish, topic);
+    publish.push(0);
+    let mut header = Vec::new();
+    header.extend_from_slice(&BASIC_PUBLISH.0.to_be_bytes());
+    header.extend_from_slice(&[0, 0]);
+    header.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    header.extend_from_slice(&[0, 0]); // no properties
+    let start = Instant::now();
+    connection
+        .send_method(*step, 1, BASIC_PUBLISH, &publish)
+        .await?;
+    connection
+        .send_frame(*step, FRAME_HEADER, 1, &header)
+        .await?;
+    connection
+        .send_frame(*step, FRAME_BODY, 1, payload.as_bytes())
+        .await?;
+
+    *step = Step::Consume;
+    loop {
+        connection.expect(*step, BASIC_DELIVER).await?;
+        let header = connection.receive(*step).await?;
+        let mut fields = Fields {
+            buf: &header.payload,
+        };
+        let size = fields
+            .take(4)
+            .and_then(|_| fields.u64())
+            .filter(|_| header.kind == FRAME_HEADER)
+            .ok_or_else(|| step.fail("expected content header after basic.deliver"))?;
+        let mut body = Vec::new();
+        while (body.len() as u64) < size {
+            let frame = connection.receive(*step).await?;
+            if frame.kind != FRAME_BODY {
+                return Err(step.fail("expected content body after content header"));
+            }
+            body.extend_from_slice(&frame.payload);
+        }
+        if body == payload.as_bytes() {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::checker::ErrorType;
+    use crate::checker::broker::{BrokerConfig, check_broker};
+    use crate::config::{AuthConfig, Endpoint};
+
+    /// How the fake broker treats the connection
+    #[derive(Clone, Copy)]
+    enum Behavior {
+        /// Accept guest logins and deliver published messages
+        Deliver,
+        /// Accept guest logins but never deliver published messages
+        Drop,
+    }
+
+    async fn read_frame(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let mut header = [0; 7];
+        stream.read_exact(&mut header).await.ok()?;
+        let len = u32::from_be_bytes([header[3], header[4], header[5], header[6]]);
+        let mut payload = vec![0; usize::try_from(len).unwrap() + 1];
+        stream.read_exact(&mut payload).await.ok()?;
+        payload.pop();
+        Some((header[0], payload))
+    }
+
+    async fn write_method(stream: &mut TcpStream, channel: u16, method: (u16, u16), args: &[u8]) {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&method.0.to_be_bytes());
+        payload.extend_from_slice(&method.1.to_be_bytes());
+        payload.extend_from_slice(args);
+        write_frame(stream, FRAME_METHOD, channel, &payload).await;
+    }
+
+    async fn write_frame(stream: &mut TcpStream, kind: u8, channel: u16, payload: &[u8]) {
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&channel.to_be_bytes());
+        put_longstr(&mut frame, payload);
+        frame.push(FRAME_END);
+        stream.write_all(&frame).await.unwrap();
+    }
+
+    /// AMQP broker on a random local port serving one connection, which accepts
+    /// user "guest" with password "guest"
+    async fn spawn_broker(behavior: Behavior) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut header = [0; 8];
+            stream.read_exact(&mut header).await.unwrap();
+            let mut start = vec![0, 9];
+            put_longstr(&mut start, &[]);
+            put_longstr(&mut start, b"AMQPLAIN PLAIN");
+            put_longstr(&mut start, b"en_US");
+            write_method(&mut stream, 0, CONNECTION_START, &start).await;
+
+            let mut delivering = false;
+            while let Some((kind, payload)) = read_frame(&mut stream).await {
+                if kind == FRAME_BODY {
+                    if delivering {
+                        let mut deliver = Vec::new();
+                        put_shortstr(&mut deliver, "ctag");
+                        deliver.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0]);
+                        put_shortstr(&mut deliver, PROBE_EXCHANGE);
+                        put_shortstr(&mut deliver, "uptime.probe");
+                        write_method(&mut stream, 1, BASIC_DELIVER, &deliver).await;
+                        let mut header = vec![0, 60, 0, 0];
+                        header.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+                        header.extend_from_slice(&[0, 0]);
+                        write_frame(&mut stream, FRAME_HEADER, 1, &header).await;
+                        write_frame(&mut stream, FRAME_BODY, 1, &payload).await;
+                    }
+                    continue;
+                }
+                if kind != FRAME_METHOD {
+                    continue;
+                }
+                let method = (
+                    u16::from_be_bytes([payload[0], payload[1]]),
+                    u16::from_be_bytes([payload[2], payload[3]]),
+                );
+                match method {
+                    CONNECTION_START_OK => {
+                        if !payload.windows(12).any(|w| w == b"\0guest\0guest") {
+                            let mut close = 403u16.to_be_bytes().to_vec();
+                            put_shortstr(&mut close, "ACCESS_REFUSED");
+                            close.extend_from_slice(&[0, 10, 0, 11]);
+                            write_method(&mut stream, 0, CONNECTION_CLOSE, &close).await;
+                            return;
+                        }
+                        write_method(&mut stream, 0, CONNECTION_TUNE, &[0, 0, 0, 2, 0, 0, 0, 60])
+                            .await;
+                    }
+                    CONNECTION_OPEN => {
+                        write_method(&mut stream, 0, CONNECTION_OPEN_OK, &[0]).await;
+                    }
+                    CHANNEL_OPEN => {
+                        write_method(&mut stream, 1, CHANNEL_OPEN_OK, &[0, 0, 0, 0]).await;
+                    }
+                    QUEUE_DECLARE => {
+                        let mut ok = Vec::new();
+                        put_shortstr(&mut ok, "amq.gen-probe");
+                        ok.extend_from_slice(&[0; 8]);
+                        write_method(&mut stream, 1, QUEUE_DECLARE_OK, &ok).await;
+                    }
+                    QUEUE_BIND => write_method(&mut stream, 1, QUEUE_BIND_OK, &[]).await,
+                    BASIC_CONSUME => {
+                        let mut ok = Vec::new();
+                        put_shortstr(&mut ok, "ctag");
+                        write_method(&mut stream, 1, BASIC_CONSUME_OK, &ok).await;
+                    }
+                    BASIC_PUBLISH => delivering = matches!(behavior, Behavior::Deliver),
+                    _ => {}
+                }
+            }
+        });
+        format!("amqp://{addr}")
+    }
+
+    fn make_endpoint(addr: &str) -> Endpoint {
+        let mut endpoint: Endpoint =
+            toml::from_str(&format!("addr = \"{addr}\"\ntype = \"amqp\"")).unwrap();
+        endpoint.timeout = 1;
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn connects_as_guest_without_probe() {
+        let addr = spawn_broker(Behavior::Deliver).await;
+        let endpoint = make_endpoint(&addr);
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.round_trip_ms, None);
+    }
+
+    #[tokio::test]
+    async fn probe_round_trip() {
+        let addr = spawn_broker(Behavior::Deliver).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime.probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.round_trip_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn refused_login_is_auth_error() {
+        let addr = spawn_broker(Behavior::Deliver).await;
+        let mut endpoint = make_endpoint(&addr);
+        endpoint.auth = Some(AuthConfig::Basic {
+            username: "probe".to_string(),
+            password: Some("wrong".to_string()),
+        });
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::BrokerAuth));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("authentication: broker closed the connection: 403 ACCESS_REFUSED")
+        );
+    }
+
+    #[tokio::test]
+    async fn undelivered_probe_is_lost() {
+        let addr = spawn_broker(Behavior::Drop).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime.probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ProbeLost));
+    }
+
+    #[test]
+    fn virtual_host_comes_from_path() {
+        let endpoint: Endpoint = toml::from_str("addr = \"amqp://mq\"\ntype = \"amqp\"").unwrap();
+        let target = Target::parse(&endpoint, &BrokerConfig::default()).unwrap();
+        assert_eq!(virtual_host(&target), "/");
+
+        let endpoint: Endpoint =
+            toml::from_str("addr = \"amqp://mq/%2fprod\"\ntype = \"amqp\"").unwrap();
+        let target = Target::parse(&endpoint, &BrokerConfig::default()).unwrap();
+        assert_eq!(virtual_host(&target), "/prod");
+    }
+}
@@ -0,0 +1,516 @@
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, Bytes, BytesMut};
+use kafka_protocol::{
+    ResponseError,
+    indexmap::IndexMap,
+    messages::{
+        FetchRequest, MetadataRequest, ProduceRequest, RequestHeader, ResponseHeader,
+        SaslAuthenticateRequest, SaslHandshakeRequest, TopicName,
+        fetch_request::{FetchPartition, FetchTopic},
+        metadata_request::MetadataRequestTopic,
+        produce_request::{PartitionProduceData, TopicProduceData},
+    },
+    protocol::{Decodable, Encodable, HeaderVersion, Request, StrBytes},
+    records::{
+        Compression, NO_PARTITION_LEADER_EPOCH, NO_PRODUCER_EPOCH, NO_PRODUCER_ID, NO_SEQUENCE,
+        Record, RecordBatchDecoder, RecordBatchEncoder, RecordEncodeOptions, TimestampType,
+    },
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{BrokerError, Step, Target, probe_payload};
+use crate::checker::tls;
+
+/// Lowest request versions still served by current brokers
+const METADATA_VERSION: i16 = 4;
+const PRODUCE_VERSION: i16 = 3;
+const FETCH_VERSION: i16 = 4;
+
+/// The probe is always written to and read from the first partition
+const PROBE_PARTITION: i32 = 0;
+
+/// Largest response accepted from the broker
+const MAX_RESPONSE_LEN: usize = 4 << 20;
+
+/// Description of a Kafka error code
+fn describe_error(code: i16) -> String {
+    ResponseError::try_from_code(code).map_or_else(
+        || format!("error code {code}"),
+        |error| format!("{error} ({code})"),
+    )
+}
+
+/// Kafka connection sending length-prefixed requests
+struct Connection {
+    stream: Box<dyn tls::Stream>,
+    correlation_id: i32,
+}
+
+impl Connection {
+    async fn call<R: Request>(
+        &mut self,
+        step: Step,
+        request: &R,
+        version: i16,
+    ) -> Result<R::Response, BrokerError> {
+        self.correlation_id += 1;
+        let header = RequestHeader::default()
+            .with_request_api_key(R::KEY)
+            .with_request_api_version(version)
+            .with_correlation_id(self.correlation_id)
+            .with_client_id(Some(StrBytes::from_static_str("uptime-forge")));
+
+        let mut frame = BytesMut::from(&[0; 4][..]);
+        header
+            .encode(&mut frame, R::header_version(version))
+            .and_then(|()| request.encode(&mut frame, version))
+            .map_err(|e| step.fail(format!("failed to encode request: {e}")))?;
+        let len = u32::try_from(frame.len() - 4).unwrap_or(u32::MAX);
+        frame[..4].copy_from_slice(&len.to_be_bytes());
+
+        let closed = |e: std::io::Error| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                step.fail("connection closed by broker")
+            } else {
+                step.fail(e)
+            }
+        };
+        self.stream.write_all(&frame).await.map_err(closed)?;
+        let len =
+            usize::try_from(self.stream.read_u32().await.map_err(closed)?).unwrap_or(usize::MAX);
+        if len > MAX_RESPONSE_LEN {
+            return Err(step.fail(format!("response of {len} bytes is too large")));
+        }
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await.map_err(closed)?;
+
+        let mut body = Bytes::from(body);
+        let malformed = |e| step.fail(format!("malformed response: {e}"));
+        let header = ResponseHeader::decode(&mut body, R::Response::header_version(version))
+            .map_err(malformed)?;
+        if header.correlation_id != self.correlation_id {
+            return Err(step.fail("response does not match the request"));
+        }
+        R::Response::decode(&mut body, version).map_err(malformed)
+    }
+}
+
+/// Log in with SASL PLAIN if credentials are set
+async fn authenticate(
+    stream: Box<dyn tls::Stream>,
+    target: &Target<'_>,
+    step: &mut Step,
+) -> Result<Connection, BrokerError> {
+    let mut connection = Connection {
+        stream,
+        correlation_id: 0,
+    };
+    let Some((ref username, ref password)) = target.credentials else {
+        return Ok(connection);
+    };
+
+    *step = Step::Authenticate;
+    let handshake =
+        SaslHandshakeRequest::default().with_mechanism(StrBytes::from_static_str("PLAIN"));
+    let response = connection.call(*step, &handshake, 1).await?;
+    if response.error_code != 0 {
+        let offered: Vec<&str> = response.mechanisms.iter().map(StrBytes::as_str).collect();
+        return Err(step.fail(format!(
+            "broker does not offer SASL PLAIN (offers {})",
+            offered.join(", ")
+        )));
+    }
+
+    let request = SaslAuthenticateRequest::default()
+        .with_auth_bytes(Bytes::from(format!("\0{username}\0{password}")));
+    let response = connection.call(*step, &request, 0).await?;
+    if response.error_code != 0 {
+        return Err(step.fail(response.error_message.map_or_else(
+            || describe_error(response.error_code),
+            |message| message.to_string(),
+        )));
+    }
+    Ok(connection)
+}
+
+/// Connect and log in, then produce to `probe_topic` and fetch the message back
+/// from the partition leader
+pub(super) async fn check(
+    stream: Box<dyn tls::Stream>,
+    target: &Target<'_>,
+    step: &mut Step,
+) -> Result<Option<Duration>, BrokerError> {
+    let mut connection = authenticate(stream, target, step).await?;
+
+    *step = Step::Handshake;
+    let topics = target.probe_topic.iter().map(|topic| {
+        MetadataRequestTopic::default()
+            .with_name(Some(TopicName(StrBytes::from_string(topic.clone()))))
+    });
+    let metadata = MetadataRequest::default()
+        .with_topics(Some(topics.collect()))
+        .with_allow_auto_topic_creation(false);
+    let metadata = connection.call(*step, &metadata, METADATA_VERSION).await?;
+    let Some(ref topic) = target.probe_topic else {
+        return Ok(None);
+    };
+
+    let partition = metadata
+        .topics
+        .first()
+        .ok_or_else(|| step.fail(format!("no metadata for topic '{topic}'")))
+        .and_then(|t| {
+            if t.error_code == 0 {
+                Ok(t)
+            } else {
+                Err(step.fail(format!("topic '{topic}': {}", describe_error(t.error_code))))
+            }
+        })?
+        .partitions
+        .iter()
+        .find(|p| p.partition_index == PROBE_PARTITION)
+        .ok_or_else(|| step.fail(format!("topic '{topic}' has no partition 0")))?;
+    let leader = metadata
+        .brokers
+        .iter()
+        .find(|b| b.node_id == partition.leader_id)
+        .ok_or_else(|| step.fail(format!("topic '{topic}' has no leader for partition 0")))?;
+    let leader_port = u16::try_from(leader.port).unwrap_or_default();
+    if leader.host.as_str() != target.host || leader_port != target.port {
+        *step = Step::Connect;
+        let (stream, _) = target.open(leader.host.as_str(), leader_port).await?;
+        connection = authenticate(stream, target, step).await?;
+    }
+
+    probe(&mut connection, target, topic, step).await.map(Some)
+}
+
+async fn probe(
+    connection: &mut Connection,
+    target: &Target<'_>,
+    topic: &str,
+    step: &mut Step,
+) -> Result<Duration, BrokerError> {
+    *step = Step::Publish;
+    let payload = probe_payload();
+    let record = Record {
+        transactional: false,
+        control: false,
+        delete_horizon: false,
+        partition_leader_epoch: NO_PARTITION_LEADER_EPOCH,
+        producer_id: NO_PRODUCER_ID,
+        producer_epoch: NO_PRODUCER_EPOCH,
+        timestamp_type: TimestampType::Creation,
+        offset: 0,
+        sequence: NO_SEQUENCE,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        key: None,
+        value: Some(Bytes::from(payload.clone())),
+        headers: IndexMap::default(),
+    };
+    let mut records = BytesMut::new();
+    RecordBatchEncoder::encode(
+        &mut records,
+        [&record],
+        &RecordEncodeOptions {
+            version: 2,
+            compression: Compression::None,
+        },
+    )
+    .map_err(|e| step.fail(format!("failed to encode probe message: {e}")))?;
+
+    let topic_name = TopicName(StrBytes::from_string(topic.to_string()));
+    let produce = ProduceRequest::default()
+        .with_acks(1)
+        .with_timeout_ms(i32::try_from(target.endpoint.timeout * 1000).unwrap_or(i32::MAX))
+        .with_topic_data(vec![
+            TopicProduceData::default()
+                .with_name(topic_name.clone())
+                .with_partition_data(vec![
+                    PartitionProduceData::default()
+                        .with_index(PROBE_PARTITION)
+                        .with_records(Some(records.freeze())),
+                ]),
+        ]);
+    let start = Instant::now();
+    let response = connection.call(*step, &produce, PRODUCE_VERSION).await?;
+    let produced = response
+        .responses
+        .first()
+        .and_then(|t| t.partition_responses.first())
+        .ok_or_else(|| step.fail("empty produce response"))?;
+    if produced.error_code != 0 {
+        return Err(step.fail(describe_error(produced.error_code)));
+    }
+
+    *step = Step::Consume;
+    let mut offset = produced.base_offset;
+    loop {
+        let fetch = FetchRequest::default()
+            .with_max_wait_ms(500)
+            .with_min_bytes(1)
+            .with_topics(vec![
+                FetchTopic::default()
+                    .with_topic(topic_name.clone())
+                    .with_partitions(vec![
+                        FetchPartition::default()
+                            .with_partition(PROBE_PARTITION)
+                            .with_fetch_offset(offset)
+                            .with_partition_max_bytes(1 << 20),
+                    ]),
+            ]);
+        let response = connection.call(*step, &fetch, FETCH_VERSION).await?;
+        let fetched = response
+            .responses
+            .first()
+            .and_then(|t| t.partitions.first())
+            .ok_or_else(|| step.fail("empty fetch response"))?;
+        if fetched.error_code != 0 {
+            return Err(step.fail(describe_error(fetched.error_code)));
+        }
+
+        let mut batches = fetched.records.clone().unwrap_or_default();
+        // A batch cut off at the size limit is fetched again from its offset
+        while batches.has_remaining() {
+            let Ok(batch) = RecordBatchDecoder::decode(&mut batches) else {
+                break;
+            };
+            for record in batch.records {
+                if record.value.as_deref() == Some(payload.as_bytes()) {
+                    return Ok(start.elapsed());
+                }
+                offset = offset.max(record.offset + 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kafka_protocol::{
+        messages::{
+            ApiKey, BrokerId, FetchResponse, MetadataResponse, ProduceResponse,
+            SaslAuthenticateResponse, SaslHandshakeResponse,
+            fetch_response::{FetchableTopicResponse, PartitionData},
+            metadata_response::{
+                MetadataResponseBroker, MetadataResponsePartition, MetadataResponseTopic,
+            },
+            produce_response::{PartitionProduceResponse, TopicProduceResponse},
+        },
+        protocol::decode_request_header_from_buffer,
+    };
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::checker::ErrorType;
+    use crate::checker::broker::{BrokerConfig, check_broker};
+    use crate::config::{AuthConfig, Endpoint};
+
+    /// How the fake broker treats the session
+    #[derive(Clone, Copy, PartialEq)]
+    enum Behavior {
+        /// Store produced records and return them to fetches
+        Echo,
+        /// Accept produced records but never return them
+        Drop,
+        /// Report every topic as unknown
+        UnknownTopic,
+    }
+
+    async fn respond<R: Encodable + HeaderVersion>(
+        stream: &mut TcpStream,
+        correlation_id: i32,
+        response: &R,
+        version: i16,
+    ) {
+        let mut frame = BytesMut::from(&[0; 4][..]);
+        ResponseHeader::default()
+            .with_correlation_id(correlation_id)
+            .encode(&mut frame, R::header_version(version))
+            .unwrap();
+        response.encode(&mut frame, version).unwrap();
+        let len = u32::try_from(frame.len() - 4).unwrap();
+        frame[..4].copy_from_slice(&len.to_be_bytes());
+        stream.write_all(&frame).await.unwrap();
+    }
+
+    /// Kafka broker on a random local port serving one connection, which accepts
+    /// SASL PLAIN logins of user "probe" with password "secret"
+    async fn spawn_broker(behavior: Behavior) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut stored = Bytes::new();
+            while let Ok(len) = stream.read_u32().await {
+                let mut body = vec![0; usize::try_from(len).unwrap()];
+                stream.read_exact(&mut body).await.unwrap();
+                let mut body = Bytes::from(body);
+                let header = decode_request_header_from_buffer(&mut body).unwrap();
+                let (id, version) = (header.correlation_id, header.request_api_version);
+                match ApiKey::try_from(header.request_api_key).unwrap() {
+                    ApiKey::SaslHandshake => {
+                        let response = SaslHandshakeResponse::default()
+                            .with_mechanisms(vec![StrBytes::from_static_str("PLAIN")]);
+                        respond(&mut stream, id, &response, version).await;
+                    }
+                    ApiKey::SaslAuthenticate => {
+                        let request = SaslAuthenticateRequest::decode(&mut body, version).unwrap();
+                        let response = if &request.auth_bytes[..] == b"\0probe\0secret" {
+                            SaslAuthenticateResponse::default()
+                        } else {
+                            SaslAuthenticateResponse::default()
+                                .with_error_code(58)
+                                .with_error_message(Some(StrBytes::from_static_str(
+                                    "Authentication failed: Invalid username or password",
+                                )))
+                        };
+                        respond(&mut stream, id, &response, version).await;
+                    }
+                    ApiKey::Metadata => {
+                        let request = MetadataRequest::decode(&mut body, version).unwrap();
+                        let topics = request.topics.unwrap_or_default().into_iter().map(|t| {
+                            let topic = MetadataResponseTopic::default().with_name(t.name);
+                            if behavior == Behavior::UnknownTopic {
+                                topic.with_error_code(3)
+                            } else {
+                                topic.with_partitions(vec![
+                                    MetadataResponsePartition::default()
+                                        .with_leader_id(BrokerId(1)),
+                                ])
+                            }
+                        });
+                        let response = MetadataResponse::default()
+                            .with_brokers(vec![
+                                MetadataResponseBroker::default()
+                                    .with_node_id(BrokerId(1))
+                                    .with_host(StrBytes::from_static_str("127.0.0.1"))
+                                    .with_port(i32::from(addr.port())),
+                            ])
+                            .with_topics(topics.collect());
+                        respond(&mut stream, id, &response, version).await;
+                    }
+                    ApiKey::Produce => {
+                        let request = ProduceRequest::decode(&mut body, version).unwrap();
+                        let data = &request.topic_data[0];
+                        if behavior == Behavior::Echo {
+                            stored = data.partition_data[0].records.clone().unwrap();
+                        }
+                        let response = ProduceResponse::default().with_responses(vec![
+                            TopicProduceResponse::default()
+                                .with_name(data.name.clone())
+                                .with_partition_responses(
+                                    vec![PartitionProduceResponse::default()],
+                                ),
+                        ]);
+                        respond(&mut stream, id, &response, version).await;
+                    }
+                    ApiKey::Fetch => {
+                        let request = FetchRequest::decode(&mut body, version).unwrap();
+                        let response = FetchResponse::default().with_responses(vec![
+                            FetchableTopicResponse::default()
+                                .with_topic(request.topics[0].topic.clone())
+                                .with_partitions(vec![
+                                    PartitionData::default().with_records(Some(stored.clone())),
+                                ]),
+                        ]);
+                        respond(&mut stream, id, &response, version).await;
+                    }
+                    _ => return,
+                }
+            }
+        });
+        format!("kafka://{addr}")
+    }
+
+    fn make_endpoint(addr: &str) -> Endpoint {
+        let mut endpoint: Endpoint =
+            toml::from_str(&format!("addr = \"{addr}\"\ntype = \"kafka\"")).unwrap();
+        endpoint.timeout = 1;
+        endpoint
+    }
+
+    fn probe_login(password: &str) -> AuthConfig {
+        AuthConfig::Basic {
+            username: "probe".to_string(),
+            password: Some(password.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_metadata_without_probe() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let endpoint = make_endpoint(&addr);
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.round_trip_ms, None);
+    }
+
+    #[tokio::test]
+    async fn probe_round_trip_with_sasl_login() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let mut endpoint = make_endpoint(&addr);
+        endpoint.auth = Some(probe_login("secret"));
+        let config = BrokerConfig {
+            probe_topic: Some("uptime-probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.round_trip_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn rejected_login_is_auth_error() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let mut endpoint = make_endpoint(&addr);
+        endpoint.auth = Some(probe_login("wrong"));
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::BrokerAuth));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("authentication: Authentication failed: Invalid username or password")
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_probe_topic_is_broker_error() {
+        let addr = spawn_broker(Behavior::UnknownTopic).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("missing".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::BrokerError));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("handshake: topic 'missing': UnknownTopicOrPartition (3)")
+        );
+    }
+
+    #[tokio::test]
+    async fn unfetched_probe_is_lost() {
+        let addr = spawn_broker(Behavior::Drop).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime-probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ProbeLost));
+    }
+}
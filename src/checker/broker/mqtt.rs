@@ -0,0 +1,388 @@
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use ulid::Ulid;
+
+use super::{BrokerError, Step, Target, probe_payload};
+use crate::checker::tls;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const DISCONNECT: u8 = 0xe0;
+
+/// Largest packet accepted from the broker
+const MAX_PACKET_LEN: usize = 1 << 20;
+
+/// MQTT 3.1.1 session over a connected stream
+struct Session {
+    stream: BufReader<Box<dyn tls::Stream>>,
+}
+
+impl Session {
+    async fn send(&mut self, step: Step, header: u8, body: &[u8]) -> Result<(), BrokerError> {
+        let mut packet = vec![header];
+        let mut len = body.len();
+        loop {
+            let byte = u8::try_from(len % 128).unwrap_or_default();
+            len /= 128;
+            packet.push(if len > 0 { byte | 0x80 } else { byte });
+            if len == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(body);
+        self.stream
+            .get_mut()
+            .write_all(&packet)
+            .await
+            .map_err(|e| step.fail(e))
+    }
+
+    /// Read the next packet as its header byte and body
+    async fn receive(&mut self, step: Step) -> Result<(u8, Vec<u8>), BrokerError> {
+        let closed = |e: std::io::Error| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                step.fail("connection closed by broker")
+            } else {
+                step.fail(e)
+            }
+        };
+        let header = self.stream.read_u8().await.map_err(closed)?;
+        let mut len = 0;
+        for shift in [0, 7, 14, 21] {
+            let byte = self.stream.read_u8().await.map_err(closed)?;
+            len |= usize::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if len > MAX_PACKET_LEN {
+            return Err(step.fail(format!("packet of {len} bytes is too large")));
+        }
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await.map_err(closed)?;
+        Ok((header, body))
+    }
+}
+
+/// Append a length-prefixed UTF-8 string
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    let len = u16::try_from(value.len()).unwrap_or(u16::MAX);
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(&value.as_bytes()[..usize::from(len)]);
+}
+
+fn connect_packet(client_id: &str, credentials: Option<&(String, String)>) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+    body.push(match credentials {
+        Some(_) => 0xc2, // username, password, clean session
+        None => 0x02,
+    });
+    body.extend_from_slice(&30u16.to_be_bytes()); // keep alive
+    put_str(&mut body, client_id);
+    if let Some((username, password)) = credentials {
+        put_str(&mut body, username);
+        put_str(&mut body, password);
+    }
+    body
+}
+
+/// Meaning of a CONNACK return code
+fn connack_error(code: u8) -> (Step, String) {
+    let reason = match code {
+        1 => "unacceptable protocol version",
+        2 => "client identifier rejected",
+        3 => "server unavailable",
+        4 => "bad username or password",
+        5 => "not authorized",
+        _ => "unknown return code",
+    };
+    let step = if matches!(code, 4 | 5) {
+        Step::Authenticate
+    } else {
+        Step::Handshake
+    };
+    (step, format!("connection refused ({code}): {reason}"))
+}
+
+/// Incoming PUBLISH packet
+#[derive(Debug, PartialEq)]
+struct Publish<'a> {
+    topic: &'a [u8],
+    /// Present for quality of service levels 1 and 2
+    packet_id: Option<[u8; 2]>,
+    payload: &'a [u8],
+}
+
+fn parse_publish(flags: u8, body: &[u8]) -> Option<Publish<'_>> {
+    let len = usize::from(u16::from_be_bytes([*body.first()?, *body.get(1)?]));
+    let topic = body.get(2..2 + len)?;
+    let rest = &body[2 + len..];
+    if flags & 0x06 == 0 {
+        return Some(Publish {
+            topic,
+            packet_id: None,
+            payload: rest,
+        });
+    }
+    Some(Publish {
+        topic,
+        packet_id: Some([*rest.first()?, *rest.get(1)?]),
+        payload: &rest[2..],
+    })
+}
+
+/// Connect and log in, then publish to `probe_topic` and wait for the message to
+/// come back through a subscription
+pub(super) async fn check(
+    stream: Box<dyn tls::Stream>,
+    target: &Target<'_>,
+    step: &mut Step,
+) -> Result<Option<Duration>, BrokerError> {
+    let mut session = Session {
+        stream: BufReader::new(stream),
+    };
+
+    // Client identifiers of up to 23 characters are accepted by every broker
+    let ulid = Ulid::new().to_string();
+    let client_id = format!("uptime-{}", &ulid[ulid.len() - 16..]);
+    *step = Step::Handshake;
+    session
+        .send(
+            *step,
+            CONNECT,
+            &connect_packet(&client_id, target.credentials.as_ref()),
+        )
+        .await?;
+    let (header, body) = session.receive(*step).await?;
+    if header != CONNACK || body.len() < 2 {
+        return Err(step.fail(format!("expected CONNACK, got packet type {}", header >> 4)));
+    }
+    if body[1] != 0 {
+        let (failed, message) = connack_error(body[1]);
+        return Err(failed.fail(message));
+    }
+
+    let Some(ref topic) = target.probe_topic else {
+        session.send(*step, DISCONNECT, &[]).await?;
+        return Ok(None);
+    };
+
+    *step = Step::Subscribe;
+    let mut subscribe = vec![0, 1];
+    put_str(&mut subscribe, topic);
+    subscribe.push(0); // QoS 0
+    session.send(*step, SUBSCRIBE, &subscribe).await?;
+    loop {
+        let (header, body) = session.receive(*step).await?;
+        if header != SUBACK {
+            continue;
+        }
+        if body.get(2).is_none_or(|&code| code == 0x80) {
+            return Err(step.fail(format!("broker refused subscription to '{topic}'")));
+        }
+        break;
+    }
+
+    *step = Step::Publish;
+    let payload = probe_payload();
+    let mut publish = Vec::new();
+    put_str(&mut publish, topic);
+    publish.extend_from_slice(payload.as_bytes());
+    let start = Instant::now();
+    session.send(*step, PUBLISH, &publish).await?;
+
+    *step = Step::Consume;
+    loop {
+        let (header, body) = session.receive(*step).await?;
+        if header & 0xf0 != PUBLISH {
+            continue;
+        }
+        let Some(message) = parse_publish(header & 0x0f, &body) else {
+            return Err(step.fail("malformed PUBLISH packet"));
+        };
+        if let Some(packet_id) = message.packet_id {
+            session.send(*step, PUBACK, &packet_id).await?;
+        }
+        if message.topic == topic.as_bytes() && message.payload == payload.as_bytes() {
+            let round_trip = start.elapsed();
+            session.send(*step, DISCONNECT, &[]).await?;
+            return Ok(Some(round_trip));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::checker::ErrorType;
+    use crate::checker::broker::{BrokerConfig, check_broker};
+    use crate::config::{AuthConfig, Endpoint};
+
+    /// How the fake broker treats the session
+    #[derive(Clone, Copy)]
+    enum Behavior {
+        /// Accept anyone and echo published messages to subscribers
+        Echo,
+        /// Refuse the CONNECT with the given return code
+        Refuse(u8),
+        /// Accept publishes but never deliver them
+        Drop,
+    }
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = stream.read_u8().await.ok()?;
+        let mut len = 0;
+        for shift in [0, 7, 14, 21] {
+            let byte = stream.read_u8().await.ok()?;
+            len |= usize::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    /// MQTT broker on a random local port serving one connection
+    async fn spawn_broker(behavior: Behavior) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            while let Some((header, body)) = read_packet(&mut stream).await {
+                let reply = match (header & 0xf0, behavior) {
+                    (CONNECT, Behavior::Refuse(code)) => vec![CONNACK, 2, 0, code],
+                    (CONNECT, _) => vec![CONNACK, 2, 0, 0],
+                    (0x80, _) => vec![SUBACK, 3, body[0], body[1], 0],
+                    (PUBLISH, Behavior::Echo) => {
+                        let mut packet = vec![header, u8::try_from(body.len()).unwrap()];
+                        packet.extend_from_slice(&body);
+                        packet
+                    }
+                    _ => continue,
+                };
+                if stream.write_all(&reply).await.is_err() {
+                    return;
+                }
+            }
+        });
+        format!("mqtt://{addr}")
+    }
+
+    fn make_endpoint(addr: &str) -> Endpoint {
+        let mut endpoint: Endpoint =
+            toml::from_str(&format!("addr = \"{addr}\"\ntype = \"mqtt\"")).unwrap();
+        endpoint.timeout = 1;
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn connects_without_probe() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let endpoint = make_endpoint(&addr);
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.round_trip_ms, None);
+    }
+
+    #[tokio::test]
+    async fn probe_round_trip() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime/probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.round_trip_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn refused_credentials_are_auth_error() {
+        let addr = spawn_broker(Behavior::Refuse(5)).await;
+        let mut endpoint = make_endpoint(&addr);
+        endpoint.auth = Some(AuthConfig::Basic {
+            username: "probe".to_string(),
+            password: Some("wrong".to_string()),
+        });
+
+        let result = check_broker("test", &endpoint, &BrokerConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::BrokerAuth));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("authentication: connection refused (5): not authorized")
+        );
+    }
+
+    #[tokio::test]
+    async fn undelivered_probe_is_lost() {
+        let addr = spawn_broker(Behavior::Drop).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime/probe".to_string()),
+            ..BrokerConfig::default()
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ProbeLost));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("probe message not received within 1s")
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_round_trip_is_down() {
+        let addr = spawn_broker(Behavior::Echo).await;
+        let endpoint = make_endpoint(&addr);
+        let config = BrokerConfig {
+            probe_topic: Some("uptime/probe".to_string()),
+            max_round_trip_ms: Some(0),
+        };
+
+        let result = check_broker("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ProbeSlow));
+        assert!(result.round_trip_ms.is_some());
+    }
+
+    #[test]
+    fn parse_publish_reads_packet_id_for_qos1() {
+        let body = [0, 1, b't', 0, 7, b'h', b'i'];
+        assert_eq!(
+            parse_publish(0x02, &body),
+            Some(Publish {
+                topic: b"t",
+                packet_id: Some([0, 7]),
+                payload: b"hi",
+            })
+        );
+        assert_eq!(
+            parse_publish(0x00, &body[..3]),
+            Some(Publish {
+                topic: b"t",
+                packet_id: None,
+                payload: b"",
+            })
+        );
+    }
+}
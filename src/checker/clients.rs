@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use hickory_resolver::{
+    Resolver, TokioResolver, config::ResolverConfig, name_server::TokioConnectionProvider,
+};
+use reqwest::{Client, ClientBuilder};
+use tokio::runtime::{self, Handle};
+
+use super::{ErrorType, http_client, http_client_builder, redirect_policy};
+use crate::config::{ConnectionMode, Endpoint, ProxyConfig, TlsFiles};
+
+/// Cached clients are rebuilt after this long, which also re-resolves the addresses
+/// pinned for `tls_server_name`
+const MAX_CLIENT_AGE: Duration = Duration::from_mins(5);
+
+/// Clients kept between checks. Keys include the runtime, since pooled connections
+/// belong to the runtime that opened them.
+struct ClientCache<K, V>(LazyLock<Mutex<CacheEntries<K, V>>>);
+
+/// Values with the time they were cached
+type CacheEntries<K, V> = HashMap<(runtime::Id, K), (Instant, V)>;
+
+impl<K: Eq + Hash, V: Clone> ClientCache<K, V> {
+    const fn new() -> Self {
+        ClientCache(LazyLock::new(Mutex::default))
+    }
+
+    fn entries(&self) -> MutexGuard<'_, CacheEntries<K, V>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Cached value for `key`, unless it has expired or `usable` rejects it
+    fn get(&self, key: K, usable: impl FnOnce(&V) -> bool) -> Option<V> {
+        self.entries()
+            .get(&(Handle::current().id(), key))
+            .filter(|(created, value)| created.elapsed() < MAX_CLIENT_AGE && usable(value))
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Cache `value` for `key` and drop expired entries, e.g. of removed endpoints
+    fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries();
+        entries.retain(|_, (created, _)| created.elapsed() < MAX_CLIENT_AGE);
+        entries.insert((Handle::current().id(), key), (Instant::now(), value));
+    }
+}
+
+/// Client of an HTTP check. Its redirect policy and timing instrumentation record
+/// into the redirect log and timing recorder of the check sending the request.
+#[derive(Clone)]
+pub(super) struct CheckClient {
+    pub(super) client: Client,
+    /// URL to request, pointed at `tls_server_name` if set
+    pub(super) url: String,
+}
+
+/// Clients of HTTP checks by endpoint name and pinned address, with the endpoint
+/// settings they were built for
+static CHECK_CLIENTS: ClientCache<(String, Option<IpAddr>), (Endpoint, CheckClient)> =
+    ClientCache::new();
+
+/// Client for an HTTP check of `endpoint`, reused from earlier checks with the same
+/// settings unless `connection = "fresh"`
+pub(super) async fn check_client(
+    name: &str,
+    endpoint: &Endpoint,
+    pinned: Option<IpAddr>,
+) -> Result<CheckClient, (ErrorType, String)> {
+    let key = (name.to_string(), pinned);
+    if endpoint.connection == ConnectionMode::Reuse
+        && let Some((_, client)) =
+            CHECK_CLIENTS.get(key.clone(), |(built_for, _)| built_for == endpoint)
+    {
+        return Ok(client);
+    }
+
+    let (client, url) = http_client(endpoint, redirect_policy(endpoint), pinned).await?;
+    let client = CheckClient { client, url };
+
+    if endpoint.connection == ConnectionMode::Reuse {
+        CHECK_CLIENTS.insert(key, (endpoint.clone(), client.clone()));
+    }
+    Ok(client)
+}
+
+/// Everything `http_client_builder` reads from an endpoint
+#[derive(PartialEq, Eq, Hash)]
+struct ClientSettings {
+    timeout: u64,
+    skip_tls_verification: bool,
+    tls_files: TlsFiles,
+    proxy: Option<ProxyConfig>,
+    local_address: Option<IpAddr>,
+}
+
+/// Plain clients by their settings, shared between endpoints
+static SHARED_CLIENTS: ClientCache<ClientSettings, Client> = ClientCache::new();
+
+/// Client with the endpoint's timeout, TLS and proxy settings, shared with other
+/// checks using the same settings unless `connection = "fresh"`
+pub(super) fn shared_client(endpoint: &Endpoint) -> reqwest::Result<Client> {
+    if endpoint.connection == ConnectionMode::Fresh {
+        return http_client_builder(endpoint)?.build();
+    }
+
+    let settings = || ClientSettings {
+        timeout: endpoint.timeout,
+        skip_tls_verification: endpoint.skip_tls_verification,
+        tls_files: endpoint.tls_files.clone(),
+        proxy: endpoint.proxy.clone(),
+        local_address: endpoint.local_address,
+    };
+    if let Some(client) = SHARED_CLIENTS.get(settings(), |_| true) {
+        return Ok(client);
+    }
+    let client = http_client_builder(endpoint).and_then(ClientBuilder::build)?;
+    SHARED_CLIENTS.insert(settings(), client.clone());
+    Ok(client)
+}
+
+/// Resolver shared by DNS checks, so answers are cached for their TTL
+static RESOLVERS: ClientCache<(), TokioResolver> = ClientCache::new();
+
+/// Resolver for a DNS check, shared with other DNS checks unless `connection = "fresh"`,
+/// in which case nothing is cached
+pub(super) fn dns_resolver(endpoint: &Endpoint) -> TokioResolver {
+    let build = || {
+        Resolver::builder_with_config(
+            ResolverConfig::default(),
+            TokioConnectionProvider::default(),
+        )
+        .build()
+    };
+    if endpoint.connection == ConnectionMode::Fresh {
+        return build();
+    }
+
+    RESOLVERS.get((), |_| true).unwrap_or_else(|| {
+        let resolver = build();
+        RESOLVERS.insert((), resolver.clone());
+        resolver
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::checker::check_endpoint;
+
+    /// HTTP server on a random local port that keeps connections alive, with the
+    /// number of connections accepted so far
+    async fn spawn_keep_alive_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    while let Ok(len) = stream.read(&mut request).await {
+                        if len == 0 {
+                            return;
+                        }
+                        let reply = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        if stream.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (format!("http://{addr}/health"), connections)
+    }
+
+    fn make_endpoint(addr: &str) -> Endpoint {
+        toml::from_str(&format!("addr = \"{addr}\"")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_checks_reuse_connections() {
+        let (addr, connections) = spawn_keep_alive_server().await;
+        let endpoint = make_endpoint(&addr);
+
+        let first = check_endpoint("reuse", &endpoint).await;
+        let second = check_endpoint("reuse", &endpoint).await;
+
+        assert!(first.is_up(), "{:?}", first.error);
+        assert!(second.is_up(), "{:?}", second.error);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        // A reused connection has no setup phases
+        assert!(first.timings.is_some_and(|t| t.connect.is_some()));
+        assert!(second.timings.is_some_and(|t| t.connect.is_none()));
+    }
+
+    #[tokio::test]
+    async fn overlapping_checks_keep_their_own_timings() {
+        let (addr, connections) = spawn_keep_alive_server().await;
+        let endpoint = make_endpoint(&addr);
+        check_endpoint("overlapping", &endpoint).await;
+
+        let (first, second) = tokio::join!(
+            check_endpoint("overlapping", &endpoint),
+            check_endpoint("overlapping", &endpoint)
+        );
+
+        // One check reuses the idle connection, the other opens a new one, and only
+        // that one records a connect phase
+        assert!(first.is_up() && second.is_up());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        let connected = [first, second]
+            .iter()
+            .filter(|result| result.timings.is_some_and(|t| t.connect.is_some()))
+            .count();
+        assert_eq!(connected, 1);
+    }
+
+    #[tokio::test]
+    async fn fresh_connections_are_not_reused() {
+        let (addr, connections) = spawn_keep_alive_server().await;
+        let mut endpoint = make_endpoint(&addr);
+        endpoint.connection = ConnectionMode::Fresh;
+
+        let first = check_endpoint("fresh", &endpoint).await;
+        let second = check_endpoint("fresh", &endpoint).await;
+
+        assert!(first.is_up() && second.is_up());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        assert!(second.timings.is_some_and(|t| t.connect.is_some()));
+    }
+
+    #[tokio::test]
+    async fn changed_settings_build_a_new_client() {
+        let (addr, connections) = spawn_keep_alive_server().await;
+        let mut endpoint = make_endpoint(&addr);
+
+        check_endpoint("changed", &endpoint).await;
+        endpoint.timeout += 1;
+        check_endpoint("changed", &endpoint).await;
+
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn shared_clients_are_keyed_by_settings() {
+        let mut endpoint = make_endpoint("https://example.com");
+        shared_client(&endpoint).unwrap();
+        shared_client(&endpoint).unwrap();
+        endpoint.skip_tls_verification = true;
+        shared_client(&endpoint).unwrap();
+
+        let runtime = Handle::current().id();
+        let cached = SHARED_CLIENTS
+            .entries()
+            .keys()
+            .filter(|(id, _)| *id == runtime)
+            .count();
+        assert_eq!(cached, 2);
+    }
+}
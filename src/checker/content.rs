@@ -0,0 +1,198 @@
+use std::fmt::Write as _;
+
+use regex::Regex;
+use sha2::{Digest as _, Sha256};
+
+use super::auth::hex;
+use super::{ContentChange, ContentSample};
+
+/// Lines of unchanged context shown around a change
+const CONTEXT_LINES: usize = 2;
+/// Maximum number of lines in a diff snippet
+const MAX_DIFF_LINES: usize = 40;
+/// Characters shown before the first difference of a changed line
+const EXCERPT_BEFORE: usize = 60;
+/// Maximum characters shown of a single line
+const EXCERPT_LEN: usize = 200;
+
+/// Remove the regions matched by `ignore` from `body` and hash the rest
+pub(super) fn sample(body: &str, ignore: &[String]) -> ContentSample {
+    let mut text = body.to_string();
+    // Invalid patterns are reported by config validation
+    for regex in ignore.iter().filter_map(|pattern| Regex::new(pattern).ok()) {
+        text = regex.replace_all(&text, "").into_owned();
+    }
+
+    ContentSample {
+        hash: hex(&Sha256::digest(text.as_bytes())),
+        text,
+    }
+}
+
+/// Outcome of comparing a sample with the stored baseline
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Observation {
+    /// No baseline existed, the sample became the baseline
+    Baseline,
+    Unchanged,
+    /// The content differs from the baseline, which is replaced by the sample
+    Changed(ContentChange),
+}
+
+/// Tracks the content baseline of one endpoint
+#[derive(Debug, Default)]
+pub(super) struct ContentTracker {
+    baseline: Option<ContentSample>,
+}
+
+impl ContentTracker {
+    pub(super) fn with_baseline(baseline: Option<ContentSample>) -> Self {
+        Self { baseline }
+    }
+
+    /// Compare a sample with the baseline and make it the new baseline
+    pub(super) fn observe(&mut self, sample: &ContentSample) -> Observation {
+        let observation = match self.baseline {
+            None => Observation::Baseline,
+            Some(ref baseline) if baseline.hash == sample.hash => return Observation::Unchanged,
+            Some(ref baseline) => Observation::Changed(ContentChange {
+                old_hash: baseline.hash.clone(),
+                new_hash: sample.hash.clone(),
+                diff: diff_snippet(&baseline.text, &sample.text),
+            }),
+        };
+        self.baseline = Some(sample.clone());
+        observation
+    }
+}
+
+/// Short line-based diff of the region that changed between `old` and `new`,
+/// with `-` for removed lines, `+` for added lines and a little context
+pub(super) fn diff_snippet(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let removed = &old[prefix..old.len() - suffix];
+    let added = &new[prefix..new.len() - suffix];
+
+    let mut lines = Vec::new();
+    for line in &old[prefix.saturating_sub(CONTEXT_LINES)..prefix] {
+        lines.push(format!("  {}", excerpt(line, 0)));
+    }
+    for (i, line) in removed.iter().enumerate() {
+        let at = added
+            .get(i)
+            .map_or(0, |other| first_difference(line, other));
+        lines.push(format!("- {}", excerpt(line, at)));
+    }
+    for (i, line) in added.iter().enumerate() {
+        let at = removed
+            .get(i)
+            .map_or(0, |other| first_difference(line, other));
+        lines.push(format!("+ {}", excerpt(line, at)));
+    }
+    for line in new[new.len() - suffix..].iter().take(CONTEXT_LINES) {
+        lines.push(format!("  {}", excerpt(line, 0)));
+    }
+
+    let mut snippet = String::new();
+    for line in lines.iter().take(MAX_DIFF_LINES) {
+        let _ = writeln!(snippet, "{line}");
+    }
+    if lines.len() > MAX_DIFF_LINES {
+        let _ = writeln!(snippet, "… {} more lines", lines.len() - MAX_DIFF_LINES);
+    }
+    snippet
+}
+
+/// Character index of the first difference between two lines
+fn first_difference(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
+/// Part of a long line around character `at`, so changes in minified pages stay visible
+fn excerpt(line: &str, at: usize) -> String {
+    let start = at.saturating_sub(EXCERPT_BEFORE);
+    let total = line.chars().count();
+    let mut excerpt: String = line.chars().skip(start).take(EXCERPT_LEN).collect();
+    if start > 0 {
+        excerpt.insert(0, '…');
+    }
+    if start + EXCERPT_LEN < total {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_ignores_dynamic_regions() {
+        let ignore = vec![r#"name="csrf" value="[^"]*""#.to_string()];
+
+        let first = sample(r#"<input name="csrf" value="abc"> Welcome"#, &ignore);
+        let second = sample(r#"<input name="csrf" value="xyz"> Welcome"#, &ignore);
+        let changed = sample(r#"<input name="csrf" value="xyz"> Hacked"#, &ignore);
+
+        assert_eq!(first.hash, second.hash);
+        assert_ne!(first.hash, changed.hash);
+        assert_eq!(first.hash.len(), 64);
+    }
+
+    #[test]
+    fn tracker_reports_changes_against_the_latest_baseline() {
+        let mut tracker = ContentTracker::default();
+        let original = sample("Welcome", &[]);
+        let defaced = sample("Hacked", &[]);
+
+        assert_eq!(tracker.observe(&original), Observation::Baseline);
+        assert_eq!(tracker.observe(&original), Observation::Unchanged);
+        let Observation::Changed(change) = tracker.observe(&defaced) else {
+            panic!("expected a change");
+        };
+        assert_eq!(change.old_hash, original.hash);
+        assert_eq!(change.new_hash, defaced.hash);
+        assert_eq!(tracker.observe(&defaced), Observation::Unchanged);
+    }
+
+    #[test]
+    fn diff_snippet_shows_changed_lines_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng";
+        let new = "a\nb\nc\nX\ne\nf\ng";
+
+        assert_eq!(diff_snippet(old, new), "  b\n  c\n- d\n+ X\n  e\n  f\n");
+    }
+
+    #[test]
+    fn diff_snippet_shows_excerpt_of_long_lines() {
+        let old = format!("{}old{}", "x".repeat(500), "y".repeat(500));
+        let new = format!("{}new{}", "x".repeat(500), "y".repeat(500));
+
+        let diff = diff_snippet(&old, &new);
+
+        let removed = diff.lines().next().unwrap();
+        assert!(removed.starts_with("- …"));
+        assert!(removed.contains("old"));
+        assert!(removed.ends_with('…'));
+        assert!(diff.lines().nth(1).unwrap().contains("new"));
+    }
+
+    #[test]
+    fn diff_snippet_truncates_large_changes() {
+        let new: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+
+        let diff = diff_snippet("", &new.join("\n"));
+
+        assert_eq!(diff.lines().count(), MAX_DIFF_LINES + 1);
+        assert!(diff.ends_with("… 60 more lines\n"));
+    }
+}
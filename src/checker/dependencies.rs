@@ -0,0 +1,107 @@
+use super::{CheckResult, CheckStatus};
+use crate::config::Endpoint;
+
+/// Mark a down result as unreachable when one of the endpoint's parents is down or
+/// unreachable itself, so the failure is attributed to the parent. `parent_status`
+/// looks up the latest status of a parent by name.
+pub(super) fn mark_unreachable(
+    result: &mut CheckResult,
+    endpoint: &Endpoint,
+    parent_status: impl Fn(&str) -> Option<CheckStatus>,
+) {
+    if result.status != CheckStatus::Down {
+        return;
+    }
+
+    let down_parents: Vec<_> = endpoint
+        .depends_on
+        .iter()
+        .filter(|parent| {
+            matches!(
+                parent_status(parent),
+                Some(CheckStatus::Down | CheckStatus::Unreachable)
+            )
+        })
+        .map(String::as_str)
+        .collect();
+    if down_parents.is_empty() {
+        return;
+    }
+
+    result.status = CheckStatus::Unreachable;
+    let reason = format!("unreachable (parent down: {})", down_parents.join(", "));
+    result.error = Some(match result.error.take() {
+        Some(error) => format!("{reason}: {error}"),
+        None => reason,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::super::base_result;
+    use super::*;
+
+    fn child() -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://api.example.com\"").unwrap();
+        endpoint.depends_on = vec!["gateway".to_string(), "router".to_string()];
+        endpoint
+    }
+
+    fn down_result(endpoint: &Endpoint) -> CheckResult {
+        let mut result = base_result("api", endpoint);
+        result.error = Some("connection refused".to_string());
+        result
+    }
+
+    #[test]
+    fn down_parent_makes_child_unreachable() {
+        let endpoint = child();
+        let mut result = down_result(&endpoint);
+        let statuses = HashMap::from([("gateway", CheckStatus::Down), ("router", CheckStatus::Up)]);
+
+        mark_unreachable(&mut result, &endpoint, |name| statuses.get(name).copied());
+
+        assert_eq!(result.status, CheckStatus::Unreachable);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("unreachable (parent down: gateway): connection refused")
+        );
+        assert!(!result.is_up());
+    }
+
+    #[test]
+    fn unreachable_parent_propagates() {
+        let endpoint = child();
+        let mut result = down_result(&endpoint);
+
+        mark_unreachable(&mut result, &endpoint, |name| {
+            (name == "router").then_some(CheckStatus::Unreachable)
+        });
+
+        assert_eq!(result.status, CheckStatus::Unreachable);
+    }
+
+    #[test]
+    fn child_stays_down_while_parents_are_up() {
+        let endpoint = child();
+        let mut result = down_result(&endpoint);
+
+        mark_unreachable(&mut result, &endpoint, |_| Some(CheckStatus::Up));
+
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(result.error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn up_child_is_left_alone() {
+        let endpoint = child();
+        let mut result = base_result("api", &endpoint);
+        result.status = CheckStatus::Up;
+
+        mark_unreachable(&mut result, &endpoint, |_| Some(CheckStatus::Down));
+
+        assert_eq!(result.status, CheckStatus::Up);
+    }
+}
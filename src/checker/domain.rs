@@ -0,0 +1,675 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::OnceCell,
+};
+use url::Url;
+
+use super::registry::{self, Checker, Validation};
+use super::{
+    CheckResult, CheckStatus, DomainInfo, ErrorType, base_result, clients, proxy, split_host_port,
+};
+use crate::config::Endpoint;
+
+/// IANA registry mapping top-level domains to their RDAP servers
+const RDAP_BOOTSTRAP_URL: &str = "https://data.iana.org/rdap/dns.json";
+/// WHOIS server that refers to the WHOIS server of each top-level domain
+const IANA_WHOIS_SERVER: &str = "whois.iana.org";
+const WHOIS_PORT: u16 = 43;
+/// Maximum size of a WHOIS response that is read
+const MAX_WHOIS_RESPONSE: u64 = 256 * 1024;
+
+/// WHOIS keys holding the expiry date, lowercase. Registries disagree on the name.
+const WHOIS_EXPIRY_KEYS: [&str; 8] = [
+    "registry expiry date",
+    "registrar registration expiration date",
+    "expiration date",
+    "expiry date",
+    "expires on",
+    "expires",
+    "paid-till",
+    "renewal date",
+];
+
+/// RDAP base URLs by top-level domain, fetched once from the IANA bootstrap registry
+static RDAP_BOOTSTRAP: OnceCell<HashMap<String, String>> = OnceCell::const_new();
+
+/// Options of a domain check, from its `[domain]` section
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DomainConfig {
+    /// RDAP base URL (default: from the IANA bootstrap registry)
+    rdap_server: Option<String>,
+    /// WHOIS server (`host` or `host:port`) used when RDAP fails (default: referral from whois.iana.org)
+    whois_server: Option<String>,
+    /// Mark the check degraded this many days before the domain expires
+    expiry_degraded_days: u32,
+    /// Mark the check down this many days before the domain expires
+    expiry_down_days: u32,
+}
+
+impl Default for DomainConfig {
+    fn default() -> Self {
+        DomainConfig {
+            rdap_server: None,
+            whois_server: None,
+            expiry_degraded_days: 30,
+            expiry_down_days: 7,
+        }
+    }
+}
+
+pub(super) struct DomainChecker;
+
+impl Checker for DomainChecker {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let mut validation = Validation::default();
+
+        // Domain addresses should be registered domain names
+        let addr = endpoint
+            .addr
+            .strip_prefix("domain://")
+            .unwrap_or(&endpoint.addr);
+        if addr.contains('/') || addr.contains(':') || !addr.contains('.') {
+            validation.errors.push(format!(
+                "domain address '{}' should be a domain name (e.g., 'example.com')",
+                endpoint.addr
+            ));
+        }
+
+        match endpoint.section::<DomainConfig>() {
+            Ok(config) => {
+                let config = config.unwrap_or_default();
+                if let Some(ref server) = config.rdap_server
+                    && let Err(e) = Url::parse(server)
+                {
+                    validation
+                        .errors
+                        .push(format!("invalid rdap_server URL '{server}': {e}"));
+                }
+                if config.expiry_down_days >= config.expiry_degraded_days {
+                    validation.errors.push(format!(
+                        "expiry_down_days ({}) must be less than expiry_degraded_days ({})",
+                        config.expiry_down_days, config.expiry_degraded_days
+                    ));
+                }
+            }
+            Err(e) => validation.errors.push(e),
+        }
+
+        // Registration data rarely changes, so frequent checks only load the registries
+        if endpoint.interval < 3600 {
+            validation.warnings.push(format!(
+                "interval ({}) is short for a domain check, registration data rarely changes, consider >= 3600 seconds",
+                endpoint.interval
+            ));
+        }
+
+        validation
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(async move {
+            match endpoint.section() {
+                Ok(config) => check_domain(name, endpoint, &config.unwrap_or_default()).await,
+                Err(e) => registry::section_error(name, endpoint, e),
+            }
+        })
+    }
+}
+
+/// Look up a domain's registration over RDAP, falling back to WHOIS, and
+/// check how long until it expires
+async fn check_domain(name: &str, endpoint: &Endpoint, config: &DomainConfig) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+    let addr = endpoint.resolved_addr();
+    let domain = addr
+        .strip_prefix("domain://")
+        .unwrap_or(&addr)
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    let start = Instant::now();
+    let lookup = match rdap_lookup(endpoint, config, &domain).await {
+        Ok(info) => Ok(info),
+        Err(rdap_error) => whois_lookup(endpoint, config, &domain)
+            .await
+            .map_err(|whois_error| format!("RDAP: {rdap_error}; WHOIS: {whois_error}")),
+    };
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+
+    match lookup {
+        Ok(info) => evaluate_expiry(&mut result, config, info, Utc::now()),
+        Err(e) => {
+            result.error = Some(format!("failed to look up domain '{domain}': {e}"));
+            result.error_type = Some(ErrorType::DomainLookup);
+        }
+    }
+
+    result
+}
+
+/// Mark the result down or degraded depending on the days left until `info.expires`
+fn evaluate_expiry(
+    result: &mut CheckResult,
+    config: &DomainConfig,
+    info: DomainInfo,
+    now: DateTime<Utc>,
+) {
+    let expires = info.expires.format("%Y-%m-%d");
+    let days_left = (info.expires - now).num_days();
+
+    if info.expires <= now {
+        result.error = Some(format!("domain expired on {expires}"));
+        result.error_type = Some(ErrorType::DomainExpiring);
+    } else if days_left < i64::from(config.expiry_down_days) {
+        result.error = Some(format!("domain expires in {days_left} days ({expires})"));
+        result.error_type = Some(ErrorType::DomainExpiring);
+    } else {
+        result.status = CheckStatus::Up;
+        if days_left < i64::from(config.expiry_degraded_days) {
+            result.degrade(format!("domain expires in {days_left} days ({expires})"));
+        }
+    }
+
+    result.domain = Some(info);
+}
+
+/// Query the RDAP server of the domain's registry
+async fn rdap_lookup(
+    endpoint: &Endpoint,
+    config: &DomainConfig,
+    domain: &str,
+) -> Result<DomainInfo, String> {
+    let client = clients::shared_client(endpoint)
+        .map_err(|e| format!("failed to build HTTP client: {e}"))?;
+
+    let base = match config.rdap_server {
+        Some(ref server) => server.clone(),
+        None => bootstrap_server(&client, domain).await?,
+    };
+    let url = format!("{}/domain/{domain}", base.trim_end_matches('/'));
+
+    let response = client
+        .get(&url)
+        .header(reqwest::header::ACCEPT, "application/rdap+json")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{url} returned status {}", response.status()));
+    }
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("invalid RDAP response: {e}"))?;
+
+    parse_rdap(&body)
+}
+
+/// RDAP base URL for the domain's top-level domain from the IANA bootstrap registry
+async fn bootstrap_server(client: &Client, domain: &str) -> Result<String, String> {
+    let servers = RDAP_BOOTSTRAP
+        .get_or_try_init(|| async {
+            let registry: Value = client
+                .get(RDAP_BOOTSTRAP_URL)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| format!("failed to fetch RDAP bootstrap registry: {e}"))?
+                .json()
+                .await
+                .map_err(|e| format!("invalid RDAP bootstrap registry: {e}"))?;
+            Ok::<_, String>(parse_bootstrap(&registry))
+        })
+        .await?;
+
+    let tld = domain.rsplit('.').next().unwrap_or(domain);
+    servers
+        .get(tld)
+        .cloned()
+        .ok_or_else(|| format!("no RDAP server known for .{tld}"))
+}
+
+/// Map each top-level domain of an RFC 9224 bootstrap registry to its first
+/// HTTPS server (or first server if none uses HTTPS)
+fn parse_bootstrap(registry: &Value) -> HashMap<String, String> {
+    let mut servers = HashMap::new();
+
+    for service in registry["services"].as_array().into_iter().flatten() {
+        let urls: Vec<&str> = service[1]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let Some(url) = urls
+            .iter()
+            .find(|url| url.starts_with("https://"))
+            .or(urls.first())
+        else {
+            continue;
+        };
+        for tld in service[0].as_array().into_iter().flatten() {
+            if let Some(tld) = tld.as_str() {
+                servers.insert(tld.to_lowercase(), (*url).to_string());
+            }
+        }
+    }
+
+    servers
+}
+
+/// Extract the expiration event and registrar name from an RDAP domain object
+fn parse_rdap(body: &Value) -> Result<DomainInfo, String> {
+    let expiration = body["events"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|event| event["eventAction"] == "expiration")
+        .and_then(|event| event["eventDate"].as_str())
+        .ok_or("RDAP response has no expiration event")?;
+    let expires =
+        parse_date(expiration).ok_or_else(|| format!("invalid expiration date '{expiration}'"))?;
+
+    let registrar = body["entities"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|entity| {
+            entity["roles"]
+                .as_array()
+                .is_some_and(|roles| roles.iter().any(|role| role == "registrar"))
+        })
+        .and_then(vcard_name);
+
+    Ok(DomainInfo {
+        expires,
+        registrar,
+        source: "rdap",
+    })
+}
+
+/// Formatted name (`fn` property) of an RDAP entity's jCard
+fn vcard_name(entity: &Value) -> Option<String> {
+    entity["vcardArray"][1]
+        .as_array()?
+        .iter()
+        .find(|property| property[0] == "fn")
+        .and_then(|property| property[3].as_str())
+        .map(ToString::to_string)
+}
+
+/// Query WHOIS for the domain, asking whois.iana.org for the registry's server
+/// unless `whois_server` is set
+async fn whois_lookup(
+    endpoint: &Endpoint,
+    config: &DomainConfig,
+    domain: &str,
+) -> Result<DomainInfo, String> {
+    let lookup = async {
+        let server = if let Some(ref server) = config.whois_server {
+            server.clone()
+        } else {
+            let tld = domain.rsplit('.').next().unwrap_or(domain);
+            let referral = whois_query(endpoint, IANA_WHOIS_SERVER, tld).await?;
+            whois_referral(&referral).ok_or_else(|| format!("no WHOIS server known for .{tld}"))?
+        };
+        whois_query(endpoint, &server, domain).await
+    };
+
+    let response = tokio::time::timeout(Duration::from_secs(endpoint.timeout), lookup)
+        .await
+        .map_err(|_| "lookup timed out".to_string())??;
+
+    parse_whois(&response)
+}
+
+/// Send a WHOIS query to `server` (`host` or `host:port`) and read the whole response
+async fn whois_query(endpoint: &Endpoint, server: &str, query: &str) -> Result<String, String> {
+    let (host, port) = split_host_port(server).unwrap_or((server, WHOIS_PORT));
+
+    let mut stream = match endpoint.proxy {
+        Some(ref config) if proxy::intercepts(Some(config), host) => {
+            proxy::connect(config, host, port)
+                .await
+                .map_err(|(_, e)| e)?
+        }
+        _ => TcpStream::connect((host, port))
+            .await
+            .map_err(|e| format!("failed to connect to {host}:{port}: {e}"))?,
+    };
+
+    stream
+        .write_all(format!("{query}\r\n").as_bytes())
+        .await
+        .map_err(|e| format!("failed to send query to {host}:{port}: {e}"))?;
+    let mut response = Vec::new();
+    stream
+        .take(MAX_WHOIS_RESPONSE)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| format!("failed to read response from {host}:{port}: {e}"))?;
+
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Key/value pairs of a WHOIS response, with lowercase keys
+fn whois_fields(response: &str) -> impl Iterator<Item = (String, &str)> {
+    response.lines().filter_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let value = value.trim();
+        (!value.is_empty()).then(|| (key.trim().to_lowercase(), value))
+    })
+}
+
+/// WHOIS server named in a whois.iana.org response
+fn whois_referral(response: &str) -> Option<String> {
+    whois_fields(response)
+        .find(|(key, _)| key == "refer" || key == "whois")
+        .map(|(_, value)| value.to_string())
+}
+
+/// Extract the expiry date and registrar from a WHOIS response
+fn parse_whois(response: &str) -> Result<DomainInfo, String> {
+    let mut expires = None;
+    let mut registrar = None;
+
+    for (key, value) in whois_fields(response) {
+        if expires.is_none() && WHOIS_EXPIRY_KEYS.contains(&key.as_str()) {
+            expires = parse_date(value);
+        }
+        if registrar.is_none() && key == "registrar" {
+            registrar = Some(value.to_string());
+        }
+    }
+
+    Ok(DomainInfo {
+        expires: expires.ok_or("WHOIS response has no expiry date")?,
+        registrar,
+        source: "whois",
+    })
+}
+
+/// Parse the date formats used by RDAP and common WHOIS servers
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    const DATE_TIME_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+    const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%Y.%m.%d", "%Y/%m/%d", "%d-%b-%Y", "%d.%m.%Y"];
+
+    // Some servers append a time zone name or a comment, retry with the first word
+    let candidates = [value.trim(), value.split_whitespace().next().unwrap_or("")];
+    candidates.into_iter().find_map(|value| {
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|date| date.with_timezone(&Utc))
+            .or_else(|| {
+                DATE_TIME_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                    .map(|date| date.and_utc())
+            })
+            .or_else(|| {
+                DATE_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                    .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::config::CheckType;
+
+    const RDAP_RESPONSE: &str = r#"{
+        "objectClassName": "domain",
+        "ldhName": "EXAMPLE.COM",
+        "events": [
+            {"eventAction": "registration", "eventDate": "1995-08-14T04:00:00Z"},
+            {"eventAction": "expiration", "eventDate": "2027-08-13T04:00:00Z"}
+        ],
+        "entities": [
+            {"roles": ["registrant"], "vcardArray": ["vcard", [["fn", {}, "text", "Someone"]]]},
+            {"roles": ["registrar"], "vcardArray": ["vcard", [
+                ["version", {}, "text", "4.0"],
+                ["fn", {}, "text", "RESERVED-Internet Assigned Numbers Authority"]
+            ]]}
+        ]
+    }"#;
+
+    const WHOIS_RESPONSE: &str = "   Domain Name: EXAMPLE.COM\r\n\
+        \x20  Registrar: RESERVED-Internet Assigned Numbers Authority\r\n\
+        \x20  Updated Date: 2024-08-14T07:01:34Z\r\n\
+        \x20  Registry Expiry Date: 2027-08-13T04:00:00Z\r\n\
+        >>> Last update of whois database: 2026-10-18T08:00:00Z <<<\r\n";
+
+    fn make_endpoint() -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"domain://example.com\"").unwrap();
+        endpoint.check_type = CheckType::Domain;
+        endpoint.timeout = 5;
+        endpoint
+    }
+
+    fn info_expiring(expires: DateTime<Utc>) -> DomainInfo {
+        DomainInfo {
+            expires,
+            registrar: None,
+            source: "rdap",
+        }
+    }
+
+    async fn spawn_rdap_server(status: u16, body: &'static str) -> String {
+        use axum::{Router, http::StatusCode, routing::get};
+
+        let status = StatusCode::from_u16(status).unwrap();
+        let app = Router::new().route("/domain/{name}", get(move || async move { (status, body) }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}/")
+    }
+
+    async fn spawn_whois_server(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut query = [0; 256];
+                let _ = stream.read(&mut query).await;
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr.to_string()
+    }
+
+    #[test]
+    fn parse_rdap_extracts_expiry_and_registrar() {
+        let info = parse_rdap(&serde_json::from_str(RDAP_RESPONSE).unwrap()).unwrap();
+
+        assert_eq!(
+            info.expires,
+            Utc.with_ymd_and_hms(2027, 8, 13, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            info.registrar.as_deref(),
+            Some("RESERVED-Internet Assigned Numbers Authority")
+        );
+        assert_eq!(info.source, "rdap");
+    }
+
+    #[test]
+    fn parse_rdap_fails_without_expiration_event() {
+        let body = serde_json::json!({"events": [{"eventAction": "registration", "eventDate": "1995-08-14T04:00:00Z"}]});
+
+        assert_eq!(
+            parse_rdap(&body),
+            Err("RDAP response has no expiration event".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_bootstrap_prefers_https_servers() {
+        let registry = serde_json::json!({"services": [
+            [["com", "net"], ["http://rdap.verisign.com/com/v1/", "https://rdap.verisign.com/com/v1/"]],
+            [["org"], ["https://rdap.publicinterestregistry.org/rdap/"]]
+        ]});
+
+        let servers = parse_bootstrap(&registry);
+
+        assert_eq!(servers["net"], "https://rdap.verisign.com/com/v1/");
+        assert_eq!(
+            servers["org"],
+            "https://rdap.publicinterestregistry.org/rdap/"
+        );
+    }
+
+    #[test]
+    fn parse_whois_extracts_expiry_and_registrar() {
+        let info = parse_whois(WHOIS_RESPONSE).unwrap();
+
+        assert_eq!(
+            info.expires,
+            Utc.with_ymd_and_hms(2027, 8, 13, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            info.registrar.as_deref(),
+            Some("RESERVED-Internet Assigned Numbers Authority")
+        );
+        assert_eq!(info.source, "whois");
+    }
+
+    #[test]
+    fn whois_referral_reads_iana_refer_field() {
+        let response =
+            "% IANA WHOIS server\n\ndomain:       COM\n\nrefer:        whois.verisign-grs.com\n";
+
+        assert_eq!(
+            whois_referral(response).as_deref(),
+            Some("whois.verisign-grs.com")
+        );
+    }
+
+    #[test]
+    fn parse_date_accepts_common_formats() {
+        let expected = Utc.with_ymd_and_hms(2027, 8, 13, 0, 0, 0).unwrap();
+
+        for value in [
+            "2027-08-13T00:00:00Z",
+            "2027-08-13T00:00:00.0Z",
+            "2027-08-13 00:00:00",
+            "2027-08-13",
+            "2027.08.13",
+            "13-Aug-2027",
+            "13.08.2027",
+            "2027-08-13 UTC",
+        ] {
+            assert_eq!(parse_date(value), Some(expected), "{value}");
+        }
+        assert_eq!(parse_date("next year"), None);
+    }
+
+    #[test]
+    fn evaluate_expiry_applies_day_thresholds() {
+        let endpoint = make_endpoint();
+        let config = DomainConfig::default();
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap();
+        let evaluate = |days: i64| {
+            let mut result = base_result("test", &endpoint);
+            let expires = now + chrono::Duration::days(days);
+            evaluate_expiry(&mut result, &config, info_expiring(expires), now);
+            result
+        };
+
+        assert_eq!(evaluate(90).status, CheckStatus::Up);
+
+        let degraded = evaluate(20);
+        assert_eq!(degraded.status, CheckStatus::Degraded);
+        assert_eq!(
+            degraded.warnings,
+            vec!["domain expires in 20 days (2026-11-07)"]
+        );
+
+        let down = evaluate(3);
+        assert_eq!(down.status, CheckStatus::Down);
+        assert_eq!(down.error_type, Some(ErrorType::DomainExpiring));
+
+        let expired = evaluate(-1);
+        assert_eq!(
+            expired.error.as_deref(),
+            Some("domain expired on 2026-10-17")
+        );
+        assert!(expired.domain.is_some());
+    }
+
+    #[tokio::test]
+    async fn check_domain_uses_rdap_server() {
+        let endpoint = make_endpoint();
+        let config = DomainConfig {
+            rdap_server: Some(spawn_rdap_server(200, RDAP_RESPONSE).await),
+            whois_server: Some("127.0.0.1:1".to_string()),
+            ..DomainConfig::default()
+        };
+
+        let result = check_domain("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.domain.unwrap().source, "rdap");
+    }
+
+    #[tokio::test]
+    async fn check_domain_falls_back_to_whois() {
+        let endpoint = make_endpoint();
+        let config = DomainConfig {
+            rdap_server: Some(spawn_rdap_server(404, "{}").await),
+            whois_server: Some(spawn_whois_server(WHOIS_RESPONSE).await),
+            ..DomainConfig::default()
+        };
+
+        let result = check_domain("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        let info = result.domain.unwrap();
+        assert_eq!(info.source, "whois");
+        assert_eq!(
+            info.registrar.as_deref(),
+            Some("RESERVED-Internet Assigned Numbers Authority")
+        );
+    }
+
+    #[tokio::test]
+    async fn check_domain_reports_both_lookup_failures() {
+        let endpoint = make_endpoint();
+        let config = DomainConfig {
+            rdap_server: Some(spawn_rdap_server(404, "{}").await),
+            whois_server: Some(spawn_whois_server("No match for \"EXAMPLE.COM\".\r\n").await),
+            ..DomainConfig::default()
+        };
+
+        let result = check_domain("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::DomainLookup));
+        let error = result.error.unwrap();
+        assert!(error.contains("RDAP: "), "{error}");
+        assert!(error.contains("returned status 404"), "{error}");
+        assert!(
+            error.contains("WHOIS: WHOIS response has no expiry date"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,494 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use reqwest::Client;
+use serde_json::Value;
+use url::Url;
+
+use super::{
+    CheckResult, CheckStatus, ErrorType, StepResult, base_result, classify_reqwest_error, clients,
+};
+use crate::config::{
+    Endpoint, Extractor, FlowStep, ProxyConfig, substitute_env_vars, substitute_flow_vars,
+};
+
+/// Variables extracted so far in a flow, keyed by step name then variable name
+type FlowVars = HashMap<String, HashMap<String, String>>;
+
+/// A failed step, with the classification used for the overall result
+struct StepFailure {
+    status_code: Option<u16>,
+    error_type: ErrorType,
+    message: String,
+}
+
+impl StepFailure {
+    fn new(status_code: Option<u16>, error_type: ErrorType, message: String) -> Self {
+        Self {
+            status_code,
+            error_type,
+            message,
+        }
+    }
+}
+
+/// Perform a multi-step HTTP flow check.
+/// Steps run in order and the check fails at the first failing step.
+pub(super) async fn check_flow(name: &str, endpoint: &Endpoint) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let client = match clients::shared_client(endpoint) {
+        Ok(c) => c,
+        Err(e) => {
+            result.error = Some(format!("failed to build HTTP client: {e}"));
+            result.error_type = Some(ErrorType::ClientBuild);
+            return result;
+        }
+    };
+
+    let resolved_addr = endpoint.resolved_addr();
+    let base = match Url::parse(&resolved_addr) {
+        Ok(u) => u,
+        Err(e) => {
+            result.error = Some(format!("invalid URL '{resolved_addr}': {e}"));
+            result.error_type = Some(ErrorType::ClientBuild);
+            return result;
+        }
+    };
+
+    let start = Instant::now();
+    // The endpoint timeout bounds the whole flow, not each step
+    let deadline = start + Duration::from_secs(endpoint.timeout);
+    let mut vars = FlowVars::new();
+
+    for step in &endpoint.steps {
+        let step_start = Instant::now();
+        let outcome = run_step(
+            &client,
+            endpoint.proxy.as_ref(),
+            &base,
+            step,
+            &vars,
+            deadline,
+        )
+        .await;
+        let elapsed = u64::try_from(step_start.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        match outcome {
+            Ok((status, extracted)) => {
+                vars.insert(step.name.clone(), extracted);
+                result.steps.push(StepResult {
+                    name: step.name.clone(),
+                    status_code: Some(status),
+                    response_time_ms: Some(elapsed),
+                    error: None,
+                });
+            }
+            Err(failure) => {
+                result.steps.push(StepResult {
+                    name: step.name.clone(),
+                    status_code: failure.status_code,
+                    response_time_ms: Some(elapsed),
+                    error: Some(failure.message.clone()),
+                });
+                result.status_code = failure.status_code;
+                result.response_time_ms =
+                    Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+                result.error = Some(format!("step '{}': {}", step.name, failure.message));
+                result.error_type = Some(failure.error_type);
+                return result;
+            }
+        }
+    }
+
+    result.status = CheckStatus::Up;
+    result.status_code = result.steps.last().and_then(|s| s.status_code);
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+    result
+}
+
+/// Fill in a configured URL, header or body: environment variables first, then
+/// flow variables. Extracted values come from responses, so environment variables
+/// in them are never expanded.
+fn resolve_template(template: &str, vars: &FlowVars) -> String {
+    substitute_flow_vars(&substitute_env_vars(template), vars)
+}
+
+/// Run a single flow step and return its status code and extracted variables
+async fn run_step(
+    client: &Client,
+    proxy: Option<&ProxyConfig>,
+    base: &Url,
+    step: &FlowStep,
+    vars: &FlowVars,
+    deadline: Instant,
+) -> Result<(u16, HashMap<String, String>), StepFailure> {
+    let resolve = |input: &str| resolve_template(input, vars);
+
+    let url = base.join(&resolve(&step.url)).map_err(|e| {
+        StepFailure::new(
+            None,
+            ErrorType::ClientBuild,
+            format!("invalid URL '{}': {e}", step.url),
+        )
+    })?;
+
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .ok_or_else(|| {
+            StepFailure::new(
+                None,
+                ErrorType::Timeout,
+                "flow timed out before step started".to_string(),
+            )
+        })?;
+
+    let mut request = client
+        .request(step.method.as_reqwest_method(), url)
+        .timeout(remaining);
+
+    for (key, value) in &step.headers {
+        request = request.header(key, resolve(value));
+    }
+
+    if let Some(ref body) = step.body {
+        request = request.body(resolve(body));
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| StepFailure::new(None, classify_reqwest_error(&e, proxy), e.to_string()))?;
+
+    let status = response.status().as_u16();
+    if status != step.expected_status {
+        return Err(StepFailure::new(
+            Some(status),
+            ErrorType::StatusMismatch,
+            format!("expected status {}, got {status}", step.expected_status),
+        ));
+    }
+
+    let headers = response.headers().clone();
+    let body = response.text().await.map_err(|e| {
+        StepFailure::new(
+            Some(status),
+            classify_reqwest_error(&e, proxy),
+            format!("failed to read response body: {e}"),
+        )
+    })?;
+
+    if let Some(ref expected) = step.body_contains
+        && !body.contains(expected.as_str())
+    {
+        return Err(StepFailure::new(
+            Some(status),
+            ErrorType::BodyMismatch,
+            format!("response body does not contain '{expected}'"),
+        ));
+    }
+
+    let mut extracted = HashMap::new();
+    for (var, extractor) in &step.extract {
+        let value = extract_value(extractor, &headers, &body).map_err(|message| {
+            StepFailure::new(
+                Some(status),
+                ErrorType::ExtractFailed,
+                format!("failed to extract '{var}': {message}"),
+            )
+        })?;
+        extracted.insert(var.clone(), value);
+    }
+
+    Ok((status, extracted))
+}
+
+/// Extract a single value from a step response
+fn extract_value(
+    extractor: &Extractor,
+    headers: &reqwest::header::HeaderMap,
+    body: &str,
+) -> Result<String, String> {
+    match extractor {
+        Extractor::Json(path) => {
+            let json: Value =
+                serde_json::from_str(body).map_err(|e| format!("body is not JSON: {e}"))?;
+            let value = json_path(&json, path).ok_or_else(|| format!("no value at '{path}'"))?;
+            match value {
+                Value::String(s) => Ok(s.clone()),
+                Value::Null => Err(format!("value at '{path}' is null")),
+                other => Ok(other.to_string()),
+            }
+        }
+        Extractor::Header(name) => headers
+            .get(name)
+            .ok_or_else(|| format!("header '{name}' not present"))?
+            .to_str()
+            .map(str::to_string)
+            .map_err(|e| format!("header '{name}' is not valid text: {e}")),
+        Extractor::Regex(pattern) => {
+            let re = Regex::new(pattern).map_err(|e| format!("invalid regex: {e}"))?;
+            re.captures(body)
+                .and_then(|caps| caps.get(1))
+                .map(|m| m.as_str().to_string())
+                .ok_or_else(|| format!("regex '{pattern}' did not match"))
+        }
+    }
+}
+
+/// Look up a value by a simple JSON path such as `$.data.items[0].id`
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        // Split "items[0][1]" into the key "items" and the indices [0, 1]
+        let (key, indices) = segment
+            .find('[')
+            .map_or((segment, ""), |i| segment.split_at(i));
+
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+
+        for index in indices.split('[').filter(|s| !s.is_empty()) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            current = current.get(index)?;
+        }
+    }
+
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HttpMethod;
+    use axum::{Router, http::HeaderMap as AxumHeaderMap, routing::get, routing::post};
+
+    // ============ json_path Tests ============
+
+    #[test]
+    fn json_path_reads_nested_fields() {
+        let json: Value = serde_json::json!({"data": {"token": "abc"}});
+        assert_eq!(
+            json_path(&json, "$.data.token"),
+            Some(&Value::String("abc".to_string()))
+        );
+        assert_eq!(
+            json_path(&json, "data.token"),
+            Some(&Value::String("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn json_path_reads_array_indices() {
+        let json: Value = serde_json::json!({"items": [{"id": 1}, {"id": 2}], "grid": [[0, 7]]});
+        assert_eq!(json_path(&json, "$.items[1].id"), Some(&Value::from(2)));
+        assert_eq!(json_path(&json, "$.grid[0][1]"), Some(&Value::from(7)));
+    }
+
+    #[test]
+    fn json_path_returns_none_for_missing_values() {
+        let json: Value = serde_json::json!({"items": []});
+        assert!(json_path(&json, "$.missing").is_none());
+        assert!(json_path(&json, "$.items[0]").is_none());
+        assert!(json_path(&json, "$.items[x]").is_none());
+    }
+
+    // ============ extract_value Tests ============
+
+    #[test]
+    fn extract_value_stringifies_non_string_json() {
+        let headers = reqwest::header::HeaderMap::new();
+        let value = extract_value(
+            &Extractor::Json("$.count".to_string()),
+            &headers,
+            r#"{"count": 42}"#,
+        );
+        assert_eq!(value.unwrap(), "42");
+    }
+
+    #[test]
+    fn extract_value_reads_header_and_regex() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-session", "s-123".parse().unwrap());
+
+        let header = extract_value(&Extractor::Header("X-Session".to_string()), &headers, "");
+        assert_eq!(header.unwrap(), "s-123");
+
+        let regex = extract_value(
+            &Extractor::Regex(r#"csrf" value="([^"]+)""#.to_string()),
+            &headers,
+            r#"<input name="csrf" value="tok42">"#,
+        );
+        assert_eq!(regex.unwrap(), "tok42");
+    }
+
+    #[test]
+    fn extract_value_fails_on_missing_header() {
+        let headers = reqwest::header::HeaderMap::new();
+        let value = extract_value(&Extractor::Header("x-missing".to_string()), &headers, "");
+        assert!(value.unwrap_err().contains("not present"));
+    }
+
+    // ============ resolve_template Tests ============
+
+    #[test]
+    fn resolve_template_expands_env_vars_only_in_the_template() {
+        // SAFETY: Tests are run single-threaded with --test-threads=1 or are isolated
+        unsafe {
+            std::env::set_var("FLOW_TEST_API_KEY", "configured-key");
+            std::env::set_var("FLOW_TEST_DB_PASSWORD", "hunter2");
+        }
+        let vars: FlowVars = HashMap::from([(
+            "login".to_string(),
+            HashMap::from([("token".to_string(), "${FLOW_TEST_DB_PASSWORD}".to_string())]),
+        )]);
+
+        let resolved = resolve_template("key=${FLOW_TEST_API_KEY}&token=${login.token}", &vars);
+
+        assert_eq!(
+            resolved,
+            "key=configured-key&token=${FLOW_TEST_DB_PASSWORD}"
+        );
+    }
+
+    // ============ check_flow Tests ============
+
+    /// Serve a login + authenticated API pair on a random local port
+    async fn spawn_test_server() -> String {
+        let app = Router::new()
+            .route(
+                "/login",
+                post(|| async { r#"{"access_token": "secret-token"}"# }),
+            )
+            .route(
+                "/me",
+                get(|headers: AxumHeaderMap| async move {
+                    let authorized = headers
+                        .get("authorization")
+                        .is_some_and(|v| v == "Bearer secret-token");
+                    if authorized {
+                        (axum::http::StatusCode::OK, "hello user")
+                    } else {
+                        (axum::http::StatusCode::UNAUTHORIZED, "denied")
+                    }
+                }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    fn make_step(name: &str, url: &str) -> FlowStep {
+        FlowStep {
+            name: name.to_string(),
+            url: url.to_string(),
+            method: HttpMethod::Get,
+            headers: HashMap::new(),
+            body: None,
+            expected_status: 200,
+            body_contains: None,
+            extract: HashMap::new(),
+        }
+    }
+
+    fn make_flow_endpoint(addr: &str, steps: Vec<FlowStep>) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str(&format!(
+            r#"
+            addr = "{addr}"
+            type = "flow"
+            timeout = 5
+            "#
+        ))
+        .unwrap();
+        endpoint.steps = steps;
+        endpoint
+    }
+
+    fn login_steps(token_ref: &str) -> Vec<FlowStep> {
+        let mut login = make_step("login", "/login");
+        login.method = HttpMethod::Post;
+        login.extract.insert(
+            "token".to_string(),
+            Extractor::Json("$.access_token".to_string()),
+        );
+
+        let mut me = make_step("me", "/me");
+        me.headers
+            .insert("Authorization".to_string(), format!("Bearer {token_ref}"));
+        me.body_contains = Some("hello".to_string());
+
+        vec![login, me]
+    }
+
+    #[tokio::test]
+    async fn check_flow_passes_extracted_values_between_steps() {
+        let base = spawn_test_server().await;
+        let endpoint = make_flow_endpoint(&base, login_steps("${login.token}"));
+
+        let result = check_flow("flow", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.steps.len(), 2);
+        assert!(result.steps.iter().all(|s| s.error.is_none()));
+        assert!(result.steps.iter().all(|s| s.response_time_ms.is_some()));
+    }
+
+    #[tokio::test]
+    async fn check_flow_fails_at_first_failing_step() {
+        let base = spawn_test_server().await;
+        let endpoint = make_flow_endpoint(&base, login_steps("wrong-token"));
+
+        let result = check_flow("flow", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.status_code, Some(401));
+        assert_eq!(result.error_type, Some(ErrorType::StatusMismatch));
+        assert!(result.error.unwrap().starts_with("step 'me':"));
+        assert_eq!(result.steps.len(), 2);
+        assert!(result.steps[0].error.is_none());
+        assert!(result.steps[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn check_flow_reports_extraction_failures() {
+        let base = spawn_test_server().await;
+        let mut steps = login_steps("${login.token}");
+        steps[0].extract.insert(
+            "missing".to_string(),
+            Extractor::Json("$.refresh_token".to_string()),
+        );
+        let endpoint = make_flow_endpoint(&base, steps);
+
+        let result = check_flow("flow", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::ExtractFailed));
+        assert!(result.error.unwrap().starts_with("step 'login':"));
+        // The flow stops before running the second step
+        assert_eq!(result.steps.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn check_flow_reports_body_mismatch() {
+        let base = spawn_test_server().await;
+        let mut steps = login_steps("${login.token}");
+        steps[1].body_contains = Some("goodbye".to_string());
+        let endpoint = make_flow_endpoint(&base, steps);
+
+        let result = check_flow("flow", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::BodyMismatch));
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use reqwest::{
+    Url,
+    header::{
+        CONTENT_SECURITY_POLICY, HeaderMap, SET_COOKIE, STRICT_TRANSPORT_SECURITY,
+        X_CONTENT_TYPE_OPTIONS,
+    },
+};
+
+use crate::config::HeaderAssertion;
+
+/// Check response headers against the expected assertions and return the first failure
+pub(super) fn check_expected_headers(
+    expected: &HashMap<String, HeaderAssertion>,
+    headers: &HeaderMap,
+) -> Option<String> {
+    // Sorted so the reported failure is stable between checks
+    let mut names: Vec<_> = expected.keys().collect();
+    names.sort();
+
+    names.into_iter().find_map(|name| {
+        let values: Vec<&str> = headers
+            .get_all(name.as_str())
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+
+        match &expected[name] {
+            HeaderAssertion::Present { present: false } if !values.is_empty() => {
+                Some(format!("header '{name}' should be absent"))
+            }
+            HeaderAssertion::Present { present: false } => None,
+            _ if values.is_empty() => Some(format!("header '{name}' is missing")),
+            HeaderAssertion::Present { present: true } => None,
+            HeaderAssertion::Exact(expected) => (!values.contains(&expected.as_str())).then(|| {
+                format!(
+                    "header '{name}': expected '{expected}', got '{}'",
+                    values.join(", ")
+                )
+            }),
+            HeaderAssertion::Regex { regex } => match Regex::new(regex) {
+                Ok(re) => (!values.iter().any(|value| re.is_match(value))).then(|| {
+                    format!(
+                        "header '{name}': '{}' does not match /{regex}/",
+                        values.join(", ")
+                    )
+                }),
+                Err(e) => Some(format!("header '{name}': invalid regex '{regex}': {e}")),
+            },
+        }
+    })
+}
+
+/// Audit the response for common security headers. Each finding degrades the check.
+pub(super) fn security_header_findings(url: &Url, headers: &HeaderMap) -> Vec<String> {
+    let mut findings = Vec::new();
+
+    // HSTS is ignored by browsers over plain HTTP
+    if url.scheme() == "https" && !headers.contains_key(STRICT_TRANSPORT_SECURITY) {
+        findings.push("missing Strict-Transport-Security header".to_string());
+    }
+    if !headers.contains_key(CONTENT_SECURITY_POLICY) {
+        findings.push("missing Content-Security-Policy header".to_string());
+    }
+    let nosniff = headers
+        .get(X_CONTENT_TYPE_OPTIONS)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("nosniff"));
+    if !nosniff {
+        findings.push("missing X-Content-Type-Options: nosniff".to_string());
+    }
+
+    for cookie in headers.get_all(SET_COOKIE) {
+        let cookie = String::from_utf8_lossy(cookie.as_bytes());
+        let mut parts = cookie.split(';').map(str::trim);
+        let name = parts
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .map_or("", |(name, _)| name);
+        if !parts.any(|attribute| attribute.eq_ignore_ascii_case("secure")) {
+            findings.push(format!("cookie '{name}' is set without the Secure flag"));
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn header_map(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn assertions(pairs: Vec<(&str, HeaderAssertion)>) -> HashMap<String, HeaderAssertion> {
+        pairs
+            .into_iter()
+            .map(|(name, assertion)| (name.to_string(), assertion))
+            .collect()
+    }
+
+    // ============ Expected Header Tests ============
+
+    #[test]
+    fn check_expected_headers_passes_matching_assertions() {
+        let headers = header_map(&[
+            ("content-type", "application/json"),
+            ("cache-control", "max-age=60"),
+        ]);
+        let expected = assertions(vec![
+            (
+                "Content-Type",
+                HeaderAssertion::Exact("application/json".to_string()),
+            ),
+            (
+                "Cache-Control",
+                HeaderAssertion::Regex {
+                    regex: r"max-age=\d+".to_string(),
+                },
+            ),
+            ("cache-control", HeaderAssertion::Present { present: true }),
+            ("server", HeaderAssertion::Present { present: false }),
+        ]);
+
+        assert_eq!(check_expected_headers(&expected, &headers), None);
+    }
+
+    #[test]
+    fn check_expected_headers_reports_wrong_value() {
+        let headers = header_map(&[("content-type", "text/html")]);
+        let expected = assertions(vec![(
+            "content-type",
+            HeaderAssertion::Exact("application/json".to_string()),
+        )]);
+
+        assert_eq!(
+            check_expected_headers(&expected, &headers).as_deref(),
+            Some("header 'content-type': expected 'application/json', got 'text/html'")
+        );
+    }
+
+    #[test]
+    fn check_expected_headers_reports_regex_mismatch() {
+        let headers = header_map(&[("cache-control", "no-store")]);
+        let expected = assertions(vec![(
+            "cache-control",
+            HeaderAssertion::Regex {
+                regex: "max-age".to_string(),
+            },
+        )]);
+
+        assert!(
+            check_expected_headers(&expected, &headers)
+                .unwrap()
+                .contains("does not match /max-age/")
+        );
+    }
+
+    #[test]
+    fn check_expected_headers_reports_missing_and_unexpected_headers() {
+        let headers = header_map(&[("server", "nginx/1.2.3")]);
+
+        let missing = assertions(vec![("etag", HeaderAssertion::Present { present: true })]);
+        assert_eq!(
+            check_expected_headers(&missing, &headers).as_deref(),
+            Some("header 'etag' is missing")
+        );
+
+        let absent = assertions(vec![(
+            "Server",
+            HeaderAssertion::Present { present: false },
+        )]);
+        assert_eq!(
+            check_expected_headers(&absent, &headers).as_deref(),
+            Some("header 'Server' should be absent")
+        );
+    }
+
+    // ============ Security Header Tests ============
+
+    #[test]
+    fn security_header_findings_empty_for_hardened_response() {
+        let url = Url::parse("https://example.com").unwrap();
+        let headers = header_map(&[
+            ("strict-transport-security", "max-age=31536000"),
+            ("content-security-policy", "default-src 'self'"),
+            ("x-content-type-options", "nosniff"),
+            ("set-cookie", "session=abc; Path=/; Secure; HttpOnly"),
+        ]);
+
+        assert!(security_header_findings(&url, &headers).is_empty());
+    }
+
+    #[test]
+    fn security_header_findings_reports_missing_headers_and_insecure_cookies() {
+        let url = Url::parse("https://example.com").unwrap();
+        let headers = header_map(&[
+            ("set-cookie", "session=abc; Path=/; HttpOnly"),
+            ("set-cookie", "theme=dark; secure"),
+        ]);
+
+        assert_eq!(
+            security_header_findings(&url, &headers),
+            vec![
+                "missing Strict-Transport-Security header",
+                "missing Content-Security-Policy header",
+                "missing X-Content-Type-Options: nosniff",
+                "cookie 'session' is set without the Secure flag",
+            ]
+        );
+    }
+
+    #[test]
+    fn security_header_findings_skips_hsts_over_http() {
+        let url = Url::parse("http://example.com").unwrap();
+        let headers = header_map(&[
+            ("content-security-policy", "default-src 'self'"),
+            ("x-content-type-options", "nosniff"),
+        ]);
+
+        assert!(security_header_findings(&url, &headers).is_empty());
+    }
+}
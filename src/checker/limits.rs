@@ -0,0 +1,342 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use axum_prometheus::metrics::gauge;
+use reqwest::Url;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::split_host_port;
+use crate::config::{Endpoint, ServerConfig};
+
+/// Checks waiting for a free slot
+const QUEUED_METRIC: &str = "uptime_forge_checks_queued";
+/// Checks holding a slot
+const IN_FLIGHT_METRIC: &str = "uptime_forge_checks_in_flight";
+
+/// Limits on checks running at the same time, in total and per target host
+#[derive(Default)]
+pub(super) struct Limiter(Mutex<Limits>);
+
+#[derive(Default)]
+struct Limits {
+    max_concurrent: usize,
+    max_per_host: usize,
+    global: Option<Pool>,
+    hosts: HashMap<String, Pool>,
+}
+
+/// Semaphore that can be resized while checks hold permits
+#[derive(Clone)]
+struct Pool {
+    semaphore: Arc<Semaphore>,
+    /// Permits still to be removed after shrinking, taken from permits as they
+    /// are released
+    excess: Arc<AtomicUsize>,
+}
+
+impl Pool {
+    fn new(size: usize) -> Self {
+        Pool {
+            semaphore: Arc::new(Semaphore::new(size)),
+            excess: Arc::default(),
+        }
+    }
+
+    /// Change the number of permits from `from` to `to`
+    fn resize(&self, from: usize, to: usize) {
+        if to >= from {
+            // Growing first cancels permits still to be removed
+            let grow = to - from;
+            let cancelled = self
+                .excess
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |excess| {
+                    Some(excess.saturating_sub(grow))
+                })
+                .map_or(0, |excess| excess.min(grow));
+            self.semaphore.add_permits(grow - cancelled);
+        } else {
+            let shrink = from - to;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            self.excess.fetch_add(shrink - forgotten, Ordering::SeqCst);
+        }
+    }
+
+    async fn acquire(&self) -> Option<Permit> {
+        let permit = Arc::clone(&self.semaphore).acquire_owned().await.ok()?;
+        Some(Permit {
+            permit: Some(permit),
+            excess: Arc::clone(&self.excess),
+        })
+    }
+}
+
+/// Permit of a pool, removed instead of returned while the pool has shrunk
+struct Permit {
+    permit: Option<OwnedSemaphorePermit>,
+    excess: Arc<AtomicUsize>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let shrinking = self
+            .excess
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |excess| {
+                excess.checked_sub(1)
+            })
+            .is_ok();
+        if shrinking && let Some(permit) = self.permit.take() {
+            permit.forget();
+        }
+    }
+}
+
+/// Limiter used by all checks, configured from the `[server]` section
+static LIMITER: LazyLock<Limiter> = LazyLock::new(Limiter::default);
+
+/// Apply the server's limits. Checks already running keep their slots.
+pub(super) fn configure(server: &ServerConfig) {
+    LIMITER.configure(server.max_concurrent_checks, server.max_checks_per_host);
+}
+
+/// Wait for a slot to check `endpoint`, which is held until the slot is dropped
+pub(super) async fn acquire(endpoint: &Endpoint) -> Slot {
+    LIMITER.acquire(target_host(endpoint).as_deref()).await
+}
+
+/// Permission to run one check attempt
+pub(super) struct Slot {
+    _host: Option<Permit>,
+    _global: Option<Permit>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        gauge!(IN_FLIGHT_METRIC).decrement(1.0);
+    }
+}
+
+/// Counts a check as queued while it waits for its permits
+struct Queued;
+
+impl Queued {
+    fn new() -> Self {
+        gauge!(QUEUED_METRIC).increment(1.0);
+        Queued
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        gauge!(QUEUED_METRIC).decrement(1.0);
+    }
+}
+
+impl Limiter {
+    fn limits(&self) -> MutexGuard<'_, Limits> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Set the limits, 0 meaning unlimited. Limits that change are resized, so
+    /// checks holding permits still count against them.
+    fn configure(&self, max_concurrent: usize, max_per_host: usize) {
+        let mut limits = self.limits();
+
+        let previous = std::mem::replace(&mut limits.max_concurrent, max_concurrent);
+        limits.global = match limits.global.take() {
+            Some(pool) if max_concurrent > 0 => {
+                pool.resize(previous, max_concurrent);
+                Some(pool)
+            }
+            _ => (max_concurrent > 0).then(|| Pool::new(max_concurrent)),
+        };
+
+        let previous = std::mem::replace(&mut limits.max_per_host, max_per_host);
+        if max_per_host == 0 {
+            limits.hosts.clear();
+        }
+        for pool in limits.hosts.values() {
+            pool.resize(previous, max_per_host);
+        }
+    }
+
+    async fn acquire(&self, host: Option<&str>) -> Slot {
+        let (host_pool, global_pool) = {
+            let mut limits = self.limits();
+            let max_per_host = limits.max_per_host;
+            let host_pool = host.filter(|_| max_per_host > 0).map(|host| {
+                limits
+                    .hosts
+                    .entry(host.to_string())
+                    .or_insert_with(|| Pool::new(max_per_host))
+                    .clone()
+            });
+            (host_pool, limits.global.clone())
+        };
+
+        let queued = Queued::new();
+        // The host permit comes first, so checks waiting on a busy host don't hold
+        // global slots that checks of other hosts could use
+        let host_permit = match host_pool {
+            Some(pool) => pool.acquire().await,
+            None => None,
+        };
+        let global_permit = match global_pool {
+            Some(pool) => pool.acquire().await,
+            None => None,
+        };
+        drop(queued);
+
+        gauge!(IN_FLIGHT_METRIC).increment(1.0);
+        Slot {
+            _host: host_permit,
+            _global: global_permit,
+        }
+    }
+}
+
+/// Host an endpoint's checks connect to, in lowercase
+fn target_host(endpoint: &Endpoint) -> Option<String> {
+    let addr = endpoint.resolved_addr();
+    if let Ok(url) = Url::parse(&addr)
+        && let Some(host) = url.host_str()
+    {
+        return Some(
+            host.trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_ascii_lowercase(),
+        );
+    }
+
+    let addr = addr
+        .split_once("://")
+        .map_or(addr.as_str(), |(_, rest)| rest);
+    let host = split_host_port(addr).map_or(addr, |(host, _)| host);
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn make_endpoint(addr: &str) -> Endpoint {
+        toml::from_str(&format!("addr = \"{addr}\"")).unwrap()
+    }
+
+    /// Whether `acquire` for `host` gets a slot without waiting
+    async fn slot_is_free(limiter: &Limiter, host: &str) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), limiter.acquire(Some(host)))
+            .await
+            .is_ok()
+    }
+
+    #[test]
+    fn target_host_handles_urls_and_host_ports() {
+        let host = |addr| target_host(&make_endpoint(addr));
+        assert_eq!(
+            host("https://API.example.com/health"),
+            Some("api.example.com".to_string())
+        );
+        assert_eq!(
+            host("db.example.com:5432"),
+            Some("db.example.com".to_string())
+        );
+        assert_eq!(host("[::1]:22"), Some("::1".to_string()));
+        assert_eq!(host("http://[::1]:8080/"), Some("::1".to_string()));
+        assert_eq!(host("example.com"), Some("example.com".to_string()));
+    }
+
+    #[tokio::test]
+    async fn unlimited_by_default() {
+        let limiter = Limiter::default();
+        let _slots: Vec<_> =
+            futures::future::join_all((0..100).map(|_| limiter.acquire(None))).await;
+        assert!(slot_is_free(&limiter, "example.com").await);
+    }
+
+    #[tokio::test]
+    async fn global_limit_queues_extra_checks() {
+        let limiter = Limiter::default();
+        limiter.configure(2, 0);
+
+        let first = limiter.acquire(Some("a.example.com")).await;
+        let _second = limiter.acquire(Some("b.example.com")).await;
+        assert!(!slot_is_free(&limiter, "c.example.com").await);
+
+        drop(first);
+        assert!(slot_is_free(&limiter, "c.example.com").await);
+    }
+
+    #[tokio::test]
+    async fn host_limit_leaves_other_hosts_free() {
+        let limiter = Limiter::default();
+        limiter.configure(0, 1);
+
+        let _busy = limiter.acquire(Some("a.example.com")).await;
+        assert!(!slot_is_free(&limiter, "a.example.com").await);
+        assert!(slot_is_free(&limiter, "b.example.com").await);
+    }
+
+    #[tokio::test]
+    async fn waiting_on_a_host_does_not_hold_a_global_slot() {
+        let limiter = Arc::new(Limiter::default());
+        limiter.configure(2, 1);
+
+        let _busy = limiter.acquire(Some("a.example.com")).await;
+        let waiting = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire(Some("a.example.com")).await })
+        };
+        tokio::task::yield_now().await;
+
+        assert!(slot_is_free(&limiter, "b.example.com").await);
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn reconfiguring_resizes_the_limits() {
+        let limiter = Limiter::default();
+        limiter.configure(1, 0);
+        let _slot = limiter.acquire(None).await;
+        assert!(!slot_is_free(&limiter, "example.com").await);
+
+        limiter.configure(2, 0);
+        assert!(slot_is_free(&limiter, "example.com").await);
+    }
+
+    #[tokio::test]
+    async fn shrinking_counts_slots_still_held() {
+        let limiter = Limiter::default();
+        limiter.configure(2, 2);
+        let first = limiter.acquire(Some("example.com")).await;
+        let second = limiter.acquire(Some("example.com")).await;
+
+        limiter.configure(1, 1);
+        drop(first);
+        // The released slot is removed, so the one still held fills the new limit
+        assert!(!slot_is_free(&limiter, "example.com").await);
+        assert!(!slot_is_free(&limiter, "other.example.com").await);
+
+        drop(second);
+        assert!(slot_is_free(&limiter, "example.com").await);
+    }
+
+    #[tokio::test]
+    async fn growing_cancels_a_pending_shrink() {
+        let limiter = Limiter::default();
+        limiter.configure(2, 0);
+        let first = limiter.acquire(None).await;
+        let _second = limiter.acquire(None).await;
+
+        limiter.configure(1, 0);
+        limiter.configure(2, 0);
+        drop(first);
+        assert!(slot_is_free(&limiter, "example.com").await);
+    }
+}
@@ -0,0 +1,825 @@
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt as _, AsyncWriteExt, BufReader};
+
+use super::registry::{self, Checker, Validation};
+use super::{
+    CertificateInfo, CheckResult, CheckStatus, ErrorType, base_result, credentials,
+    open_connection, split_host_port, tls,
+};
+use crate::config::{CheckType, Endpoint};
+
+/// Name sent with SMTP EHLO
+const EHLO_NAME: &str = "uptime-forge";
+/// Longest line read from a server
+const MAX_LINE_LEN: u64 = 8 * 1024;
+/// Most lines read for a single reply, such as an EHLO or CAPA listing
+const MAX_REPLY_LINES: usize = 100;
+
+type MailError = (ErrorType, String);
+
+/// Options of a mail check, from its `[smtp]`, `[imap]` or `[pop3]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MailConfig {
+    /// TLS: "starttls" (default), "implicit" or "none"
+    tls: MailTls,
+}
+
+/// How a mail check secures its connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum MailTls {
+    /// Connect in plain text, require the server to offer STARTTLS and upgrade
+    #[default]
+    Starttls,
+    /// TLS from the start (SMTPS, IMAPS, POP3S)
+    Implicit,
+    /// Plain text only
+    None,
+}
+
+/// smtp, imap and pop3
+pub(super) struct MailChecker;
+
+impl Checker for MailChecker {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let mut validation = Validation {
+            errors: registry::host_port_error(endpoint, "mail server")
+                .into_iter()
+                .collect(),
+            warnings: Vec::new(),
+        };
+
+        let config = match endpoint.section::<MailConfig>() {
+            Ok(config) => config.unwrap_or_default(),
+            Err(e) => {
+                validation.errors.push(e);
+                return validation;
+            }
+        };
+        if endpoint.auth.is_some() {
+            if endpoint.check_type == CheckType::Smtp {
+                validation
+                    .warnings
+                    .push("auth is not used by smtp checks, which don't log in".to_string());
+            } else if config.tls == MailTls::None {
+                validation.warnings.push(
+                    "auth credentials are sent in plain text with tls = \"none\"".to_string(),
+                );
+            }
+        }
+
+        validation
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(async move {
+            match endpoint.section() {
+                Ok(config) => check_mail(name, endpoint, &config.unwrap_or_default()).await,
+                Err(e) => registry::section_error(name, endpoint, e),
+            }
+        })
+    }
+}
+
+/// Step of a mail conversation, each reported with its own error type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Connect,
+    Greeting,
+    Capabilities,
+    StartTls,
+    Login,
+}
+
+impl Step {
+    fn error_type(self) -> ErrorType {
+        match self {
+            Step::Connect => ErrorType::Connection,
+            Step::Greeting => ErrorType::MailGreeting,
+            Step::Capabilities => ErrorType::MailCapabilities,
+            Step::StartTls => ErrorType::StartTls,
+            Step::Login => ErrorType::MailLogin,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Step::Connect => "connect",
+            Step::Greeting => "greeting",
+            Step::Capabilities => "capabilities",
+            Step::StartTls => "STARTTLS",
+            Step::Login => "login",
+        }
+    }
+}
+
+/// Default port of a mail protocol
+fn default_port(check_type: &CheckType, tls: MailTls) -> u16 {
+    let implicit = tls == MailTls::Implicit;
+    match check_type {
+        CheckType::Imap if implicit => 993,
+        CheckType::Imap => 143,
+        CheckType::Pop3 if implicit => 995,
+        CheckType::Pop3 => 110,
+        _ if implicit => 465,
+        _ => 25,
+    }
+}
+
+/// Perform an SMTP, IMAP or POP3 check: greeting, capabilities, STARTTLS and
+/// (IMAP and POP3 only) login
+async fn check_mail(name: &str, endpoint: &Endpoint, config: &MailConfig) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let addr = endpoint.resolved_addr();
+    let scheme = format!("{}://", endpoint.check_type.as_str());
+    let addr = addr.strip_prefix(&scheme).unwrap_or(&addr);
+    let (host, port) = split_host_port(addr).unwrap_or_else(|| {
+        (
+            addr.trim_start_matches('[').trim_end_matches(']'),
+            default_port(&endpoint.check_type, config.tls),
+        )
+    });
+
+    let start = Instant::now();
+    let mut step = Step::Connect;
+    let outcome = tokio::time::timeout(
+        Duration::from_secs(endpoint.timeout),
+        converse(endpoint, config.tls, host, port, &mut step),
+    )
+    .await;
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+
+    match outcome {
+        Ok(Ok(certificate)) => {
+            result.status = CheckStatus::Up;
+            result.certificate = certificate;
+        }
+        Ok(Err((error_type, error))) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
+        }
+        Err(_) => {
+            result.error = Some(format!("timed out during {}", step.describe()));
+            result.error_type = Some(ErrorType::Timeout);
+        }
+    }
+
+    result
+}
+
+/// Connect and run the protocol conversation, keeping `step` up to date so a
+/// timeout can tell where the server stopped answering
+async fn converse(
+    endpoint: &Endpoint,
+    tls: MailTls,
+    host: &str,
+    port: u16,
+    step: &mut Step,
+) -> Result<Option<CertificateInfo>, MailError> {
+    let stream = open_connection(endpoint, host, port).await?;
+    let mut session = Session::new(Box::new(stream), step);
+    if tls == MailTls::Implicit {
+        session = session.upgrade(endpoint, host).await?;
+    }
+
+    let session = match endpoint.check_type {
+        CheckType::Imap => imap(session, endpoint, tls, host).await?,
+        CheckType::Pop3 => pop3(session, endpoint, tls, host).await?,
+        _ => smtp(session, endpoint, tls, host).await?,
+    };
+    Ok(session.certificate)
+}
+
+/// Line-based conversation with a mail server
+struct Session<'a> {
+    stream: BufReader<Box<dyn tls::Stream>>,
+    step: &'a mut Step,
+    certificate: Option<CertificateInfo>,
+    /// Number of IMAP commands sent, used for tags
+    commands: u32,
+}
+
+impl<'a> Session<'a> {
+    fn new(stream: Box<dyn tls::Stream>, step: &'a mut Step) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            step,
+            certificate: None,
+            commands: 0,
+        }
+    }
+
+    fn begin(&mut self, step: Step) {
+        *self.step = step;
+    }
+
+    /// Error of the current step
+    fn fail(&self, message: impl std::fmt::Display) -> MailError {
+        (
+            self.step.error_type(),
+            format!("{}: {message}", self.step.describe()),
+        )
+    }
+
+    async fn send(&mut self, line: &str) -> Result<(), MailError> {
+        let stream = self.stream.get_mut();
+        let sent = match stream.write_all(format!("{line}\r\n").as_bytes()).await {
+            Ok(()) => stream.flush().await,
+            Err(e) => Err(e),
+        };
+        sent.map_err(|e| self.fail(e))
+    }
+
+    async fn line(&mut self) -> Result<String, MailError> {
+        let mut line = String::new();
+        let read = (&mut self.stream)
+            .take(MAX_LINE_LEN)
+            .read_line(&mut line)
+            .await
+            .map_err(|e| self.fail(e))?;
+        if read == 0 {
+            return Err(self.fail("connection closed by server"));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Replace the connection with a TLS connection over it. Data the server
+    /// sent ahead of the handshake is discarded.
+    async fn upgrade(self, endpoint: &Endpoint, host: &str) -> Result<Session<'a>, MailError> {
+        let (stream, certificate) = tls::connect(self.stream.into_inner(), endpoint, host).await?;
+        Ok(Session {
+            stream: BufReader::new(Box::new(stream)),
+            step: self.step,
+            certificate,
+            commands: self.commands,
+        })
+    }
+
+    /// Fail the STARTTLS step unless the server offers `command`
+    fn require_starttls(&self, capabilities: &[String], command: &str) -> Result<(), MailError> {
+        if capabilities.iter().any(|capability| capability == command) {
+            Ok(())
+        } else {
+            Err(self.fail(format!("server does not offer {command}")))
+        }
+    }
+}
+
+/// First word of each line, uppercased
+fn keywords<'l>(lines: impl IntoIterator<Item = &'l str>) -> Vec<String> {
+    lines
+        .into_iter()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_ascii_uppercase)
+        .collect()
+}
+
+async fn smtp<'a>(
+    mut session: Session<'a>,
+    endpoint: &Endpoint,
+    tls: MailTls,
+    host: &str,
+) -> Result<Session<'a>, MailError> {
+    session.begin(Step::Greeting);
+    smtp_expect(&mut session, 220).await?;
+
+    session.begin(Step::Capabilities);
+    let extensions = smtp_ehlo(&mut session).await?;
+
+    if tls == MailTls::Starttls {
+        session.begin(Step::StartTls);
+        session.require_starttls(&extensions, "STARTTLS")?;
+        session.send("STARTTLS").await?;
+        smtp_expect(&mut session, 220).await?;
+        session = session.upgrade(endpoint, host).await?;
+
+        session.begin(Step::Capabilities);
+        smtp_ehlo(&mut session).await?;
+    }
+
+    let _ = session.send("QUIT").await;
+    Ok(session)
+}
+
+/// Read an SMTP reply, possibly spanning several lines, and fail unless its code is `code`.
+/// Returns the text of each line.
+async fn smtp_expect(session: &mut Session<'_>, code: u16) -> Result<Vec<String>, MailError> {
+    let mut lines = Vec::new();
+    loop {
+        let line = session.line().await?;
+        let Some(reply_code) = line.get(..3).and_then(|c| c.parse::<u16>().ok()) else {
+            return Err(session.fail(format!("unexpected reply '{line}'")));
+        };
+        if reply_code != code {
+            return Err(session.fail(line));
+        }
+        lines.push(line.get(4..).unwrap_or_default().to_string());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(lines);
+        }
+        if lines.len() >= MAX_REPLY_LINES {
+            return Err(session.fail("reply too long"));
+        }
+    }
+}
+
+/// Send EHLO and return the extensions the server advertises
+async fn smtp_ehlo(session: &mut Session<'_>) -> Result<Vec<String>, MailError> {
+    session.send(&format!("EHLO {EHLO_NAME}")).await?;
+    let lines = smtp_expect(session, 250).await?;
+    // The first line is the server's greeting to us
+    Ok(keywords(lines.iter().skip(1).map(String::as_str)))
+}
+
+async fn imap<'a>(
+    mut session: Session<'a>,
+    endpoint: &Endpoint,
+    tls: MailTls,
+    host: &str,
+) -> Result<Session<'a>, MailError> {
+    session.begin(Step::Greeting);
+    let greeting = session.line().await?;
+    if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+        return Err(session.fail(greeting));
+    }
+
+    session.begin(Step::Capabilities);
+    let mut capabilities = imap_capabilities(&mut session).await?;
+
+    if tls == MailTls::Starttls {
+        session.begin(Step::StartTls);
+        session.require_starttls(&capabilities, "STARTTLS")?;
+        imap_command(&mut session, "STARTTLS").await?;
+        session = session.upgrade(endpoint, host).await?;
+
+        session.begin(Step::Capabilities);
+        capabilities = imap_capabilities(&mut session).await?;
+    }
+
+    if let Some((username, password)) = credentials(endpoint) {
+        session.begin(Step::Login);
+        if capabilities.iter().any(|c| c == "LOGINDISABLED") {
+            return Err(session.fail("server does not allow LOGIN (LOGINDISABLED)"));
+        }
+        imap_command(
+            &mut session,
+            &format!("LOGIN {} {}", imap_quote(&username), imap_quote(&password)),
+        )
+        .await?;
+    }
+
+    let _ = imap_command(&mut session, "LOGOUT").await;
+    Ok(session)
+}
+
+/// Send a tagged IMAP command and return its untagged responses, failing unless it completes with OK
+async fn imap_command(session: &mut Session<'_>, command: &str) -> Result<Vec<String>, MailError> {
+    session.commands += 1;
+    let tag = format!("a{}", session.commands);
+    session.send(&format!("{tag} {command}")).await?;
+
+    let mut untagged = Vec::new();
+    loop {
+        let line = session.line().await?;
+        if let Some(status) = line
+            .strip_prefix(tag.as_str())
+            .and_then(|rest| rest.strip_prefix(' '))
+        {
+            return if status.starts_with("OK") {
+                Ok(untagged)
+            } else {
+                Err(session.fail(status))
+            };
+        }
+        if untagged.len() >= MAX_REPLY_LINES {
+            return Err(session.fail("reply too long"));
+        }
+        untagged.push(line);
+    }
+}
+
+async fn imap_capabilities(session: &mut Session<'_>) -> Result<Vec<String>, MailError> {
+    let untagged = imap_command(session, "CAPABILITY").await?;
+    Ok(untagged
+        .iter()
+        .filter_map(|line| line.strip_prefix("* CAPABILITY "))
+        .flat_map(str::split_whitespace)
+        .map(str::to_ascii_uppercase)
+        .collect())
+}
+
+/// IMAP quoted string
+fn imap_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+async fn pop3<'a>(
+    mut session: Session<'a>,
+    endpoint: &Endpoint,
+    tls: MailTls,
+    host: &str,
+) -> Result<Session<'a>, MailError> {
+    session.begin(Step::Greeting);
+    let greeting = session.line().await?;
+    if !greeting.starts_with("+OK") {
+        return Err(session.fail(greeting));
+    }
+
+    session.begin(Step::Capabilities);
+    let capabilities = pop3_capabilities(&mut session).await?;
+
+    if tls == MailTls::Starttls {
+        session.begin(Step::StartTls);
+        session.require_starttls(&capabilities, "STLS")?;
+        pop3_command(&mut session, "STLS").await?;
+        session = session.upgrade(endpoint, host).await?;
+
+        session.begin(Step::Capabilities);
+        pop3_capabilities(&mut session).await?;
+    }
+
+    if let Some((username, password)) = credentials(endpoint) {
+        session.begin(Step::Login);
+        pop3_command(&mut session, &format!("USER {username}")).await?;
+        pop3_command(&mut session, &format!("PASS {password}")).await?;
+    }
+
+    let _ = pop3_command(&mut session, "QUIT").await;
+    Ok(session)
+}
+
+/// Send a POP3 command and fail unless the server answers +OK
+async fn pop3_command(session: &mut Session<'_>, command: &str) -> Result<String, MailError> {
+    session.send(command).await?;
+    let reply = session.line().await?;
+    if reply.starts_with("+OK") {
+        Ok(reply)
+    } else {
+        Err(session.fail(reply))
+    }
+}
+
+/// Capabilities listed by CAPA. Servers without CAPA (RFC 2449) have none.
+async fn pop3_capabilities(session: &mut Session<'_>) -> Result<Vec<String>, MailError> {
+    session.send("CAPA").await?;
+    let reply = session.line().await?;
+    if reply.starts_with("-ERR") {
+        return Ok(Vec::new());
+    }
+    if !reply.starts_with("+OK") {
+        return Err(session.fail(reply));
+    }
+
+    let mut lines = Vec::new();
+    loop {
+        let line = session.line().await?;
+        if line == "." {
+            return Ok(keywords(lines.iter().map(String::as_str)));
+        }
+        if lines.len() >= MAX_REPLY_LINES {
+            return Err(session.fail("reply too long"));
+        }
+        lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::TcpListener;
+    use tokio_rustls::{
+        TlsAcceptor,
+        rustls::{ServerConfig, crypto::ring, pki_types::PrivateKeyDer},
+    };
+
+    use super::*;
+    use crate::config::AuthConfig;
+
+    /// What a fake server does after answering a command
+    enum Then {
+        Continue,
+        StartTls,
+        Close,
+    }
+
+    /// Answers a command line; the flag tells whether the connection is encrypted
+    type Handler = fn(&str, bool) -> (String, Then);
+
+    struct FakeServer {
+        addr: std::net::SocketAddr,
+        ca_pem: Vec<u8>,
+    }
+
+    /// Mail server on a random local port that sends `greeting` (after the TLS
+    /// handshake if `implicit_tls`) and answers commands with `handler`.
+    /// Its certificate is valid for "mail.test".
+    async fn spawn_server(
+        greeting: &'static str,
+        implicit_tls: bool,
+        handler: Handler,
+    ) -> FakeServer {
+        let certified = rcgen::generate_simple_self_signed(vec!["mail.test".to_string()]).unwrap();
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![certified.cert.der().clone()],
+                PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            if implicit_tls {
+                let Ok(tls) = acceptor.accept(stream).await else {
+                    return;
+                };
+                serve(tls, greeting, handler, true).await;
+            } else if let Some(stream) = serve(stream, greeting, handler, false).await
+                && let Ok(tls) = acceptor.accept(stream).await
+            {
+                serve(tls, "", handler, true).await;
+            }
+        });
+
+        FakeServer {
+            addr,
+            ca_pem: certified.cert.pem().into_bytes(),
+        }
+    }
+
+    /// Answer commands until the handler closes the connection, or hand the
+    /// stream back when it starts TLS
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+        stream: S,
+        greeting: &str,
+        handler: Handler,
+        tls: bool,
+    ) -> Option<S> {
+        let mut stream = BufReader::new(stream);
+        stream.get_mut().write_all(greeting.as_bytes()).await.ok()?;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.ok()? == 0 {
+                return None;
+            }
+            let (reply, then) = handler(line.trim_end(), tls);
+            stream.get_mut().write_all(reply.as_bytes()).await.ok()?;
+            match then {
+                Then::Continue => {}
+                Then::StartTls => return Some(stream.into_inner()),
+                Then::Close => return None,
+            }
+        }
+    }
+
+    fn make_endpoint(check_type: CheckType, server: &FakeServer) -> Endpoint {
+        let mut endpoint: Endpoint =
+            toml::from_str(&format!("addr = \"{}\"", server.addr)).unwrap();
+        endpoint.check_type = check_type;
+        endpoint.timeout = 1;
+        endpoint.tls_server_name = Some("mail.test".to_string());
+        endpoint.tls_files.ca_bundle = Some(server.ca_pem.clone());
+        endpoint
+    }
+
+    fn basic_auth(username: &str, password: &str) -> AuthConfig {
+        AuthConfig::Basic {
+            username: username.to_string(),
+            password: Some(password.to_string()),
+        }
+    }
+
+    fn smtp_server(command: &str, tls: bool) -> (String, Then) {
+        match command {
+            "EHLO uptime-forge" if tls => {
+                ("250-mail.test\r\n250 8BITMIME\r\n".into(), Then::Continue)
+            }
+            "EHLO uptime-forge" => (
+                "250-mail.test\r\n250-STARTTLS\r\n250 8BITMIME\r\n".into(),
+                Then::Continue,
+            ),
+            "STARTTLS" => ("220 ready to start TLS\r\n".into(), Then::StartTls),
+            "QUIT" => ("221 bye\r\n".into(), Then::Close),
+            _ => ("500 unknown command\r\n".into(), Then::Continue),
+        }
+    }
+
+    /// IMAP server offering STARTTLS that accepts user "probe" with password "secret"
+    fn imap_server(command: &str, tls: bool) -> (String, Then) {
+        let (tag, command) = command.split_once(' ').unwrap_or((command, ""));
+        match command {
+            "CAPABILITY" if tls => (
+                format!("* CAPABILITY IMAP4rev1 AUTH=PLAIN\r\n{tag} OK done\r\n"),
+                Then::Continue,
+            ),
+            "CAPABILITY" => (
+                format!("* CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED\r\n{tag} OK done\r\n"),
+                Then::Continue,
+            ),
+            "STARTTLS" => (format!("{tag} OK begin TLS\r\n"), Then::StartTls),
+            r#"LOGIN "probe" "secret""# => (format!("{tag} OK logged in\r\n"), Then::Continue),
+            "LOGOUT" => (format!("* BYE\r\n{tag} OK bye\r\n"), Then::Close),
+            _ => (
+                format!("{tag} NO [AUTHENTICATIONFAILED] invalid credentials\r\n"),
+                Then::Continue,
+            ),
+        }
+    }
+
+    /// POP3 server offering STLS that accepts user "probe" with password "secret"
+    fn pop3_server(command: &str, _tls: bool) -> (String, Then) {
+        match command {
+            "CAPA" => ("+OK\r\nUSER\r\nSTLS\r\n.\r\n".into(), Then::Continue),
+            "STLS" => ("+OK begin TLS\r\n".into(), Then::StartTls),
+            "USER probe" => ("+OK\r\n".into(), Then::Continue),
+            "PASS secret" => ("+OK logged in\r\n".into(), Then::Continue),
+            "QUIT" => ("+OK bye\r\n".into(), Then::Close),
+            _ => ("-ERR invalid\r\n".into(), Then::Continue),
+        }
+    }
+
+    #[tokio::test]
+    async fn smtp_starttls_reports_certificate() {
+        let server = spawn_server("220 mail.test ESMTP\r\n", false, smtp_server).await;
+        let endpoint = make_endpoint(CheckType::Smtp, &server);
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        let certificate = result.certificate.unwrap();
+        assert_eq!(certificate.dns_names, vec!["mail.test"]);
+        assert!(certificate.not_after > certificate.not_before);
+    }
+
+    #[tokio::test]
+    async fn smtp_without_starttls_offer_fails_starttls_step() {
+        fn handler(command: &str, _tls: bool) -> (String, Then) {
+            match command {
+                "QUIT" => ("221 bye\r\n".into(), Then::Close),
+                _ => ("250 mail.test\r\n".into(), Then::Continue),
+            }
+        }
+        let server = spawn_server("220 mail.test ESMTP\r\n", false, handler).await;
+        let mut endpoint = make_endpoint(CheckType::Smtp, &server);
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::StartTls));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("STARTTLS: server does not offer STARTTLS")
+        );
+
+        // Without TLS the same server is fine
+        let server = spawn_server("220 mail.test ESMTP\r\n", false, handler).await;
+        endpoint.addr = server.addr.to_string();
+        let config = MailConfig { tls: MailTls::None };
+        let result = check_mail("test", &endpoint, &config).await;
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.certificate, None);
+    }
+
+    #[tokio::test]
+    async fn smtp_rejected_greeting_is_greeting_error() {
+        let server = spawn_server("554 no service for you\r\n", false, smtp_server).await;
+        let endpoint = make_endpoint(CheckType::Smtp, &server);
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::MailGreeting));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("greeting: 554 no service for you")
+        );
+    }
+
+    #[tokio::test]
+    async fn smtp_untrusted_certificate_is_tls_error() {
+        let server = spawn_server("220 mail.test ESMTP\r\n", false, smtp_server).await;
+        let mut endpoint = make_endpoint(CheckType::Smtp, &server);
+        endpoint.tls_files.ca_bundle = None;
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Tls));
+    }
+
+    #[tokio::test]
+    async fn imap_logs_in_after_starttls() {
+        let server = spawn_server("* OK IMAP ready\r\n", false, imap_server).await;
+        let mut endpoint = make_endpoint(CheckType::Imap, &server);
+        endpoint.auth = Some(basic_auth("probe", "secret"));
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.certificate.is_some());
+    }
+
+    #[tokio::test]
+    async fn imap_rejected_login_is_login_error() {
+        let server = spawn_server("* OK IMAP ready\r\n", false, imap_server).await;
+        let mut endpoint = make_endpoint(CheckType::Imap, &server);
+        endpoint.auth = Some(basic_auth("probe", "wrong"));
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::MailLogin));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("login: NO [AUTHENTICATIONFAILED] invalid credentials")
+        );
+    }
+
+    #[tokio::test]
+    async fn imap_login_without_tls_respects_logindisabled() {
+        let server = spawn_server("* OK IMAP ready\r\n", false, imap_server).await;
+        let mut endpoint = make_endpoint(CheckType::Imap, &server);
+        endpoint.auth = Some(basic_auth("probe", "secret"));
+        let config = MailConfig { tls: MailTls::None };
+
+        let result = check_mail("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::MailLogin));
+        assert!(result.error.unwrap().contains("LOGINDISABLED"));
+    }
+
+    #[tokio::test]
+    async fn imap_implicit_tls() {
+        let server = spawn_server("* OK IMAPS ready\r\n", true, imap_server).await;
+        let mut endpoint = make_endpoint(CheckType::Imap, &server);
+        endpoint.auth = Some(basic_auth("probe", "secret"));
+        let config = MailConfig {
+            tls: MailTls::Implicit,
+        };
+
+        let result = check_mail("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.certificate.is_some());
+    }
+
+    #[tokio::test]
+    async fn pop3_logs_in_after_stls() {
+        let server = spawn_server("+OK POP3 ready\r\n", false, pop3_server).await;
+        let mut endpoint = make_endpoint(CheckType::Pop3, &server);
+        endpoint.auth = Some(basic_auth("probe", "secret"));
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert!(result.certificate.is_some());
+    }
+
+    #[tokio::test]
+    async fn pop3_rejected_password_is_login_error() {
+        let server = spawn_server("+OK POP3 ready\r\n", false, pop3_server).await;
+        let mut endpoint = make_endpoint(CheckType::Pop3, &server);
+        endpoint.auth = Some(basic_auth("probe", "wrong"));
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::MailLogin));
+        assert_eq!(result.error.as_deref(), Some("login: -ERR invalid"));
+    }
+
+    #[tokio::test]
+    async fn silent_server_times_out_during_greeting() {
+        let server = spawn_server("", false, pop3_server).await;
+        let endpoint = make_endpoint(CheckType::Pop3, &server);
+
+        let result = check_mail("test", &endpoint, &MailConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Timeout));
+        assert_eq!(result.error.as_deref(), Some("timed out during greeting"));
+    }
+
+    #[test]
+    fn default_ports_follow_protocol_and_tls() {
+        assert_eq!(default_port(&CheckType::Smtp, MailTls::Starttls), 25);
+        assert_eq!(default_port(&CheckType::Smtp, MailTls::Implicit), 465);
+        assert_eq!(default_port(&CheckType::Imap, MailTls::None), 143);
+        assert_eq!(default_port(&CheckType::Imap, MailTls::Implicit), 993);
+        assert_eq!(default_port(&CheckType::Pop3, MailTls::Starttls), 110);
+        assert_eq!(default_port(&CheckType::Pop3, MailTls::Implicit), 995);
+    }
+
+    #[test]
+    fn imap_quote_escapes_quotes_and_backslashes() {
+        assert_eq!(imap_quote(r#"pa"ss\word"#), r#""pa\"ss\\word""#);
+    }
+}
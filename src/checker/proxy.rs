@@ -0,0 +1,691 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    net::{IpAddr, SocketAddr},
+};
+
+use base64::Engine;
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::ErrorType;
+use crate::config::ProxyConfig;
+
+/// Largest CONNECT response head accepted from an HTTP proxy
+const MAX_CONNECT_RESPONSE: usize = 8192;
+
+/// Build the reqwest proxy for a proxy config
+pub(super) fn reqwest_proxy(config: &ProxyConfig) -> reqwest::Result<reqwest::Proxy> {
+    let url = config.resolved_url().map_or_else(
+        |_| crate::config::substitute_env_vars(&config.url),
+        |url| url.to_string(),
+    );
+    // Fail when the client is built rather than send requests around a broken proxy
+    reqwest::Proxy::all(url.as_str())?;
+
+    // `no_proxy` goes through `bypasses` like TCP checks, not reqwest's own matcher
+    let no_proxy = config.no_proxy.clone();
+    let mut proxy = reqwest::Proxy::custom(move |target| {
+        let host = target.host_str().unwrap_or_default();
+        (!bypasses(&no_proxy, host)).then(|| url.clone())
+    });
+    if let Some((username, password)) = config.resolved_credentials() {
+        proxy = proxy.basic_auth(&username, &password);
+    }
+    Ok(proxy)
+}
+
+/// Whether requests to `host` go through the proxy
+pub(super) fn intercepts(config: Option<&ProxyConfig>, host: &str) -> bool {
+    config.is_some_and(|config| !bypasses(&config.no_proxy, host))
+}
+
+/// Whether `host` matches a `no_proxy` entry. Entries are `*`, IP addresses,
+/// CIDR ranges or domains, where a domain also matches its subdomains.
+fn bypasses(no_proxy: &[String], host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let ip = host.parse::<IpAddr>().ok();
+
+    no_proxy.iter().map(|entry| entry.trim()).any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+        if let Some(ip) = ip {
+            return match entry.split_once('/') {
+                Some((network, prefix)) => in_cidr(ip, network, prefix),
+                None => entry.parse::<IpAddr>() == Ok(ip),
+            };
+        }
+        let domain = entry.trim_start_matches('.');
+        !domain.is_empty()
+            && (host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase())))
+    })
+}
+
+/// Whether `ip` is inside the `network/prefix` range
+fn in_cidr(ip: IpAddr, network: &str, prefix: &str) -> bool {
+    let (Ok(network), Ok(prefix)) = (network.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Classify a connect error of a request sent through a proxy. Failures reported
+/// by the proxy about the target keep their target classification, anything else
+/// happened while talking to the proxy itself.
+pub(super) fn classify_connect_error(error: &reqwest::Error) -> ErrorType {
+    let mut messages = Vec::new();
+    let mut source: Option<&dyn Error> = Some(error);
+    while let Some(e) = source {
+        messages.push(e.to_string().to_lowercase());
+        source = e.source();
+    }
+
+    if let Some(error_type) = messages.iter().find_map(|m| socks_reply_error_type(m)) {
+        return error_type;
+    }
+
+    let talked_to_proxy = messages
+        .iter()
+        .any(|m| m.contains("tunnel error") || m.contains("socks"));
+    if !talked_to_proxy
+        && messages
+            .iter()
+            .any(|m| m.contains("tls") || m.contains("ssl") || m.contains("certificate"))
+    {
+        return ErrorType::Tls;
+    }
+
+    ErrorType::Proxy
+}
+
+/// Error type for a SOCKS5 reply describing a failure to reach the target
+fn socks_reply_error_type(message: &str) -> Option<ErrorType> {
+    match message.strip_prefix("socks error: ")? {
+        "network unreachable" | "host unreachable" => Some(ErrorType::Connection),
+        "connection refused" => Some(ErrorType::TcpRefused),
+        "ttl expired" => Some(ErrorType::Timeout),
+        _ => None,
+    }
+}
+
+/// Open a TCP connection to `host:port` through the proxy
+pub(super) async fn connect(
+    config: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, (ErrorType, String)> {
+    let url = config
+        .resolved_url()
+        .map_err(|e| (ErrorType::Proxy, format!("invalid proxy url: {e}")))?;
+    let proxy_host = url
+        .host_str()
+        .ok_or_else(|| (ErrorType::Proxy, "proxy url has no host".to_string()))?;
+    let proxy_port = url.port_or_known_default().unwrap_or(1080);
+
+    let mut stream = TcpStream::connect((proxy_host, proxy_port))
+        .await
+        .map_err(|e| {
+            (
+                ErrorType::Proxy,
+                format!("failed to connect to proxy {proxy_host}:{proxy_port}: {e}"),
+            )
+        })?;
+
+    match url.scheme() {
+        "http" => http_connect(&mut stream, config, host, port).await?,
+        "socks5" | "socks5h" => socks5_connect(&mut stream, config, &url, host, port).await?,
+        scheme => {
+            return Err((
+                ErrorType::Proxy,
+                format!("unsupported proxy scheme '{scheme}'"),
+            ));
+        }
+    }
+
+    Ok(stream)
+}
+
+fn proxy_io_error(e: &std::io::Error) -> (ErrorType, String) {
+    (ErrorType::Proxy, format!("proxy handshake failed: {e}"))
+}
+
+/// Establish an HTTP CONNECT tunnel
+async fn http_connect(
+    stream: &mut TcpStream,
+    config: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<(), (ErrorType, String)> {
+    let authority = if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    };
+
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some((username, password)) = config.resolved_credentials() {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        let _ = write!(request, "Proxy-Authorization: Basic {credentials}\r\n");
+    }
+    request.push_str("\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+
+    // Read the response head byte by byte so no tunnelled data is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_CONNECT_RESPONSE {
+            return Err((
+                ErrorType::Proxy,
+                "proxy CONNECT response too long".to_string(),
+            ));
+        }
+        let byte = stream.read_u8().await.map_err(|e| proxy_io_error(&e))?;
+        head.push(byte);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok());
+
+    match status {
+        Some(200..=299) => Ok(()),
+        Some(407) => Err((
+            ErrorType::Proxy,
+            "proxy authentication required".to_string(),
+        )),
+        // The proxy reached out but couldn't get to the target
+        Some(502..=504) => Err((
+            ErrorType::Connection,
+            format!("proxy could not reach target: {status_line}"),
+        )),
+        _ => Err((
+            ErrorType::Proxy,
+            format!("proxy refused CONNECT: {status_line}"),
+        )),
+    }
+}
+
+/// Establish a SOCKS5 connection (RFC 1928), with username/password auth (RFC 1929)
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    config: &ProxyConfig,
+    url: &Url,
+    host: &str,
+    port: u16,
+) -> Result<(), (ErrorType, String)> {
+    let credentials = config.resolved_credentials();
+
+    // Greeting: offer "no auth", plus username/password when configured
+    let greeting: &[u8] = if credentials.is_some() {
+        &[5, 2, 0, 2]
+    } else {
+        &[5, 1, 0]
+    };
+    stream
+        .write_all(greeting)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+    let mut choice = [0u8; 2];
+    stream
+        .read_exact(&mut choice)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+
+    match (choice, &credentials) {
+        ([5, 0], _) => {}
+        ([5, 2], Some((username, password))) => {
+            socks5_authenticate(stream, username, password).await?;
+        }
+        _ => {
+            return Err((
+                ErrorType::Proxy,
+                "SOCKS proxy rejected all authentication methods".to_string(),
+            ));
+        }
+    }
+
+    // Connect request, resolving locally unless the proxy should resolve names
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(ip) => push_socks_ip(&mut request, ip),
+        Err(_) if url.scheme() == "socks5h" => {
+            let name = u8::try_from(host.len())
+                .map_err(|_| (ErrorType::Dns, format!("hostname '{host}' is too long")))?;
+            request.push(3);
+            request.push(name);
+            request.extend_from_slice(host.as_bytes());
+        }
+        Err(_) => {
+            let addr: SocketAddr = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| (ErrorType::Dns, format!("failed to resolve address: {e}")))?
+                .next()
+                .ok_or_else(|| (ErrorType::Dns, format!("no addresses found for '{host}'")))?;
+            push_socks_ip(&mut request, addr.ip());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream
+        .write_all(&request)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+
+    // Reply: version, status, reserved, then the bound address which is skipped
+    let mut reply = [0u8; 4];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+    let bound_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => usize::from(stream.read_u8().await.map_err(|e| proxy_io_error(&e))?),
+        _ => 0,
+    };
+    let mut bound = vec![0u8; bound_len + 2];
+    stream
+        .read_exact(&mut bound)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+
+    match reply[1] {
+        0 => Ok(()),
+        3 => Err((
+            ErrorType::Connection,
+            "SOCKS proxy: network unreachable".to_string(),
+        )),
+        4 => Err((
+            ErrorType::Connection,
+            "SOCKS proxy: host unreachable".to_string(),
+        )),
+        5 => Err((
+            ErrorType::TcpRefused,
+            "SOCKS proxy: connection refused".to_string(),
+        )),
+        6 => Err((ErrorType::Timeout, "SOCKS proxy: TTL expired".to_string())),
+        code => Err((
+            ErrorType::Proxy,
+            format!("SOCKS proxy error (reply code {code})"),
+        )),
+    }
+}
+
+async fn socks5_authenticate(
+    stream: &mut TcpStream,
+    username: &str,
+    password: &str,
+) -> Result<(), (ErrorType, String)> {
+    let too_long = || {
+        (
+            ErrorType::Proxy,
+            "SOCKS credentials are longer than 255 bytes".to_string(),
+        )
+    };
+    let mut request = vec![1, u8::try_from(username.len()).map_err(|_| too_long())?];
+    request.extend_from_slice(username.as_bytes());
+    request.push(u8::try_from(password.len()).map_err(|_| too_long())?);
+    request.extend_from_slice(password.as_bytes());
+    stream
+        .write_all(&request)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+
+    let mut reply = [0u8; 2];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .map_err(|e| proxy_io_error(&e))?;
+    if reply[1] == 0 {
+        Ok(())
+    } else {
+        Err((
+            ErrorType::Proxy,
+            "SOCKS proxy rejected the credentials".to_string(),
+        ))
+    }
+}
+
+fn push_socks_ip(request: &mut Vec<u8>, ip: IpAddr) {
+    match ip {
+        IpAddr::V4(ip) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::checker::{check_http, check_tcp};
+    use crate::config::{CheckType, Endpoint};
+
+    fn make_proxy_endpoint(addr: &str, check_type: CheckType, proxy: ProxyConfig) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str(&format!(
+            r#"
+            addr = "{addr}"
+            timeout = 5
+            "#
+        ))
+        .unwrap();
+        endpoint.check_type = check_type;
+        endpoint.proxy = Some(proxy);
+        endpoint
+    }
+
+    fn proxy_config(url: &str) -> ProxyConfig {
+        ProxyConfig {
+            url: url.to_string(),
+            username: None,
+            password: None,
+            no_proxy: vec![],
+        }
+    }
+
+    /// A port with nothing listening on it
+    async fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    /// HTTP proxy answering every request with `response`, recording request heads
+    async fn spawn_http_proxy(response: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        let Ok(byte) = stream.read_u8().await else {
+                            return;
+                        };
+                        head.push(byte);
+                    }
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8_lossy(&head).into_owned());
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let mut rest = Vec::new();
+                    let _ = stream.read_to_end(&mut rest).await;
+                });
+            }
+        });
+        (format!("http://{addr}"), requests)
+    }
+
+    /// SOCKS5 proxy replying `reply` to every connect request, requiring
+    /// `credentials` when given
+    async fn spawn_socks5_proxy(
+        reply: u8,
+        credentials: Option<(&'static str, &'static str)>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut greeting = [0u8; 2];
+                    stream.read_exact(&mut greeting).await.unwrap();
+                    let mut methods = vec![0u8; usize::from(greeting[1])];
+                    stream.read_exact(&mut methods).await.unwrap();
+
+                    if let Some((username, password)) = credentials {
+                        if !methods.contains(&2) {
+                            stream.write_all(&[5, 0xFF]).await.unwrap();
+                            return;
+                        }
+                        stream.write_all(&[5, 2]).await.unwrap();
+                        let version = stream.read_u8().await.unwrap();
+                        assert_eq!(version, 1);
+                        let mut user = vec![0u8; usize::from(stream.read_u8().await.unwrap())];
+                        stream.read_exact(&mut user).await.unwrap();
+                        let mut pass = vec![0u8; usize::from(stream.read_u8().await.unwrap())];
+                        stream.read_exact(&mut pass).await.unwrap();
+                        let accepted = user == username.as_bytes() && pass == password.as_bytes();
+                        stream.write_all(&[1, u8::from(!accepted)]).await.unwrap();
+                        if !accepted {
+                            return;
+                        }
+                    } else {
+                        stream.write_all(&[5, 0]).await.unwrap();
+                    }
+
+                    let mut request = [0u8; 4];
+                    stream.read_exact(&mut request).await.unwrap();
+                    let addr_len = match request[3] {
+                        1 => 4,
+                        4 => 16,
+                        _ => usize::from(stream.read_u8().await.unwrap()),
+                    };
+                    let mut target = vec![0u8; addr_len + 2];
+                    stream.read_exact(&mut target).await.unwrap();
+
+                    stream
+                        .write_all(&[5, reply, 0, 1, 0, 0, 0, 0, 0, 0])
+                        .await
+                        .unwrap();
+                    let mut rest = Vec::new();
+                    let _ = stream.read_to_end(&mut rest).await;
+                });
+            }
+        });
+        format!("socks5h://{addr}")
+    }
+
+    // ============ no_proxy Tests ============
+
+    #[test]
+    fn bypasses_matches_domains_ips_and_cidrs() {
+        let no_proxy: Vec<String> = ["internal.example.com", ".corp", "10.0.0.0/8", "::1"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert!(bypasses(&no_proxy, "internal.example.com"));
+        assert!(bypasses(&no_proxy, "api.internal.example.com"));
+        assert!(bypasses(&no_proxy, "db.corp"));
+        assert!(bypasses(&no_proxy, "10.1.2.3"));
+        assert!(bypasses(&no_proxy, "[::1]"));
+        assert!(!bypasses(&no_proxy, "notinternal.example.com"));
+        assert!(!bypasses(&no_proxy, "11.0.0.1"));
+        assert!(!bypasses(&no_proxy, "example.com"));
+    }
+
+    #[test]
+    fn bypasses_everything_with_wildcard() {
+        assert!(bypasses(&["*".to_string()], "example.com"));
+        assert!(!bypasses(&[], "example.com"));
+    }
+
+    // ============ TCP Check Tests ============
+
+    #[tokio::test]
+    async fn tcp_check_tunnels_through_http_connect_with_credentials() {
+        let (url, requests) = spawn_http_proxy("HTTP/1.1 200 Connection established\r\n\r\n").await;
+        let mut proxy = proxy_config(&url);
+        proxy.username = Some("probe".to_string());
+        proxy.password = Some("secret".to_string());
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy);
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("CONNECT db.internal:5432 HTTP/1.1\r\n"));
+        // base64("probe:secret")
+        assert!(requests[0].contains("Proxy-Authorization: Basic cHJvYmU6c2VjcmV0\r\n"));
+    }
+
+    #[tokio::test]
+    async fn tcp_check_reports_proxy_auth_failure_as_proxy_error() {
+        let (url, _) = spawn_http_proxy("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").await;
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy_config(&url));
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Proxy));
+    }
+
+    #[tokio::test]
+    async fn tcp_check_reports_unreachable_target_as_connection_error() {
+        let (url, _) = spawn_http_proxy("HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy_config(&url));
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Connection));
+    }
+
+    #[tokio::test]
+    async fn tcp_check_connects_through_socks5_with_credentials() {
+        let url = spawn_socks5_proxy(0, Some(("probe", "secret"))).await;
+        let mut proxy = proxy_config(&url);
+        proxy.username = Some("probe".to_string());
+        proxy.password = Some("secret".to_string());
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy);
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn tcp_check_reports_socks5_refusal_as_target_failure() {
+        let url = spawn_socks5_proxy(5, None).await;
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy_config(&url));
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::TcpRefused));
+    }
+
+    #[tokio::test]
+    async fn tcp_check_reports_unreachable_proxy_as_proxy_error() {
+        let url = format!("http://127.0.0.1:{}", closed_port().await);
+        let endpoint = make_proxy_endpoint("db.internal:5432", CheckType::Tcp, proxy_config(&url));
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Proxy));
+    }
+
+    #[tokio::test]
+    async fn tcp_check_skips_proxy_for_no_proxy_hosts() {
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+        let mut proxy = proxy_config(&format!("http://127.0.0.1:{}", closed_port().await));
+        proxy.no_proxy = vec!["127.0.0.0/8".to_string()];
+        let endpoint = make_proxy_endpoint(&target_addr.to_string(), CheckType::Tcp, proxy);
+
+        let result = check_tcp("tcp", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+    }
+
+    // ============ HTTP Check Tests ============
+
+    #[tokio::test]
+    async fn http_check_sends_request_through_http_proxy() {
+        let (url, requests) =
+            spawn_http_proxy("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        let endpoint = make_proxy_endpoint(
+            "http://target.invalid/health",
+            CheckType::Http,
+            proxy_config(&url),
+        );
+
+        let result = check_http("http", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        assert!(
+            requests.lock().unwrap()[0].starts_with("GET http://target.invalid/health HTTP/1.1")
+        );
+    }
+
+    #[tokio::test]
+    async fn http_check_skips_proxy_for_no_proxy_hosts() {
+        let (target, requests) =
+            spawn_http_proxy("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        let mut proxy = proxy_config(&format!("http://127.0.0.1:{}", closed_port().await));
+        proxy.no_proxy = vec!["127.0.0.0/8".to_string()];
+        let endpoint = make_proxy_endpoint(&format!("{target}/health"), CheckType::Http, proxy);
+
+        let result = check_http("http", &endpoint).await;
+
+        assert!(result.is_up(), "unexpected error: {:?}", result.error);
+        // Sent directly, so the request line has no absolute URL
+        assert!(requests.lock().unwrap()[0].starts_with("GET /health HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn http_check_reports_unreachable_proxy_as_proxy_error() {
+        let url = format!("http://127.0.0.1:{}", closed_port().await);
+        let endpoint = make_proxy_endpoint(
+            "https://target.invalid/health",
+            CheckType::Http,
+            proxy_config(&url),
+        );
+
+        let result = check_http("http", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Proxy));
+    }
+
+    #[tokio::test]
+    async fn http_check_reports_socks5_unreachable_host_as_target_failure() {
+        let url = spawn_socks5_proxy(4, None).await;
+        let endpoint = make_proxy_endpoint(
+            "http://target.invalid/health",
+            CheckType::Http,
+            proxy_config(&url),
+        );
+
+        let result = check_http("http", &endpoint).await;
+
+        assert!(!result.is_up());
+        assert_eq!(result.error_type, Some(ErrorType::Connection));
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{CheckResult, CheckStatus, LocationStatus, schedule};
+use crate::config::Endpoint;
+
+/// Number of check periods after which an agent's result no longer counts
+const STALE_AFTER_PERIODS: i32 = 3;
+
+/// Decide an endpoint with a `quorum` from this instance's result and the latest
+/// results of its agents (`remote`, by location). The endpoint is down once at least
+/// `quorum` locations see it down, and a partial outage while fewer do.
+///
+/// Agent results older than three check periods (the interval, or the time between
+/// runs of a `schedule`) don't count. When fewer locations have
+/// reported than the quorum, all of them must agree, so an endpoint still goes down
+/// while its agents are offline.
+pub(super) fn decide(
+    result: &mut CheckResult,
+    endpoint: &Endpoint,
+    remote: Option<&BTreeMap<String, LocationStatus>>,
+    now: DateTime<Utc>,
+) {
+    let Some(quorum) = endpoint.quorum else {
+        return;
+    };
+    if matches!(
+        result.status,
+        CheckStatus::NotMonitored | CheckStatus::Unreachable
+    ) {
+        return;
+    }
+
+    let stale_after = TimeDelta::from_std(schedule::check_period(endpoint, now))
+        .ok()
+        .and_then(|period| period.checked_mul(STALE_AFTER_PERIODS))
+        .unwrap_or(TimeDelta::MAX);
+    let fresh: Vec<_> = remote
+        .into_iter()
+        .flat_map(BTreeMap::values)
+        .filter(|status| now - status.checked_at <= stale_after)
+        .collect();
+
+    let locations = fresh.len() + 1;
+    let local_down = result.status == CheckStatus::Down;
+    let down = fresh
+        .iter()
+        .filter(|status| matches!(status.status, CheckStatus::Down | CheckStatus::Unreachable))
+        .count()
+        + usize::from(local_down);
+    if down == 0 {
+        return;
+    }
+
+    let quorum = usize::try_from(quorum).unwrap_or(usize::MAX).min(locations);
+    if down >= quorum && local_down {
+        return;
+    }
+
+    result.local_status = Some(result.status);
+    let reason = if down >= quorum {
+        result.status = CheckStatus::Down;
+        format!("down from {down} of {locations} locations")
+    } else {
+        result.status = CheckStatus::PartialOutage;
+        format!("partial outage (down from {down} of {locations} locations, quorum {quorum})")
+    };
+    result.error = Some(match result.error.take() {
+        Some(error) => format!("{reason}: {error}"),
+        None => reason,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::base_result;
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        "2026-03-02T10:00:00Z".parse().unwrap()
+    }
+
+    fn endpoint(quorum: Option<u32>) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.quorum = quorum;
+        endpoint
+    }
+
+    fn local(endpoint: &Endpoint, status: CheckStatus) -> CheckResult {
+        let mut result = base_result("api", endpoint);
+        result.status = status;
+        if status == CheckStatus::Down {
+            result.error = Some("connection refused".to_string());
+        }
+        result
+    }
+
+    fn remote(statuses: &[(&str, CheckStatus, i64)]) -> BTreeMap<String, LocationStatus> {
+        statuses
+            .iter()
+            .map(|&(location, status, seconds_ago)| {
+                let status = LocationStatus {
+                    location: location.to_string(),
+                    status,
+                    response_time_ms: None,
+                    error: None,
+                    checked_at: now() - TimeDelta::seconds(seconds_ago),
+                };
+                (location.to_string(), status)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_location_down_is_a_partial_outage() {
+        let endpoint = endpoint(Some(2));
+        let mut result = local(&endpoint, CheckStatus::Down);
+        let remote = remote(&[
+            ("eu-west", CheckStatus::Up, 10),
+            ("us-east", CheckStatus::Up, 10),
+        ]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::PartialOutage);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("partial outage (down from 1 of 3 locations, quorum 2): connection refused")
+        );
+        assert!(result.is_up());
+        assert_eq!(result.local_status, Some(CheckStatus::Down));
+    }
+
+    #[test]
+    fn quorum_of_locations_down_is_down() {
+        let endpoint = endpoint(Some(2));
+        let mut result = local(&endpoint, CheckStatus::Up);
+        let remote = remote(&[
+            ("eu-west", CheckStatus::Down, 10),
+            ("us-east", CheckStatus::Down, 10),
+        ]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(result.error.as_deref(), Some("down from 2 of 3 locations"));
+        assert_eq!(result.local_status, Some(CheckStatus::Up));
+    }
+
+    #[test]
+    fn local_down_agreed_by_quorum_is_left_alone() {
+        let endpoint = endpoint(Some(2));
+        let mut result = local(&endpoint, CheckStatus::Down);
+        let remote = remote(&[("eu-west", CheckStatus::Down, 10)]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(result.error.as_deref(), Some("connection refused"));
+        assert_eq!(result.local_status, None);
+    }
+
+    #[test]
+    fn stale_agent_results_do_not_count() {
+        let endpoint = endpoint(Some(2));
+        let mut result = local(&endpoint, CheckStatus::Down);
+        // The default interval is 60s, so results older than 3 minutes are stale
+        let remote = remote(&[("eu-west", CheckStatus::Up, 600)]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        // Only this instance counts, so its result decides
+        assert_eq!(result.status, CheckStatus::Down);
+    }
+
+    #[test]
+    fn scheduled_agent_results_stay_fresh_between_runs() {
+        let mut endpoint = endpoint(Some(2));
+        endpoint.schedule = Some("0 * * * *".to_string());
+        let mut result = local(&endpoint, CheckStatus::Up);
+        // Checked at the previous hourly run, much longer ago than the default interval
+        let remote = remote(&[
+            ("eu-west", CheckStatus::Down, 3600),
+            ("us-east", CheckStatus::Down, 3600),
+        ]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::Down);
+    }
+
+    #[test]
+    fn without_quorum_the_local_result_decides() {
+        let endpoint = endpoint(None);
+        let mut result = local(&endpoint, CheckStatus::Up);
+        let remote = remote(&[
+            ("eu-west", CheckStatus::Down, 10),
+            ("us-east", CheckStatus::Down, 10),
+        ]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::Up);
+    }
+
+    #[test]
+    fn all_locations_up_is_unchanged() {
+        let endpoint = endpoint(Some(2));
+        let mut result = local(&endpoint, CheckStatus::Degraded);
+        let remote = remote(&[("eu-west", CheckStatus::Up, 10)]);
+
+        decide(&mut result, &endpoint, Some(&remote), now());
+
+        assert_eq!(result.status, CheckStatus::Degraded);
+        assert!(result.error.is_none());
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{Result, bail};
+use futures::future::BoxFuture;
+use url::Url;
+
+use super::{
+    CheckResult, ErrorType, base_result, broker, check_dns, check_http, check_tcp, domain, flow,
+    mail, script, ssh, udp,
+};
+use crate::config::{Endpoint, validate_flow_steps};
+
+/// Errors and warnings a checker found in an endpoint's config
+#[derive(Debug, Default, PartialEq)]
+pub struct Validation {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A check type: validates the endpoints using it and checks them.
+///
+/// Check types that need options of their own read them from a section named after
+/// the type with [`Endpoint::section`] instead of adding fields to `Endpoint`.
+pub trait Checker: Send + Sync {
+    /// Validate the options this check type uses, called when the config is loaded
+    fn validate(&self, _endpoint: &Endpoint) -> Validation {
+        Validation::default()
+    }
+
+    /// Check the endpoint once; retries and latency thresholds are applied by the caller
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult>;
+}
+
+/// Checkers by the check type name used in the config
+pub struct Registry {
+    checkers: HashMap<String, Box<dyn Checker>>,
+}
+
+impl Registry {
+    /// Registry with the check types built into uptime-forge
+    pub fn builtin() -> Self {
+        let mut registry = Registry {
+            checkers: HashMap::new(),
+        };
+        registry
+            .register("http", Http)
+            .register("tcp", Tcp)
+            .register("dns", Dns)
+            .register("flow", Flow)
+            .register("domain", domain::DomainChecker)
+            .register("udp", udp::UdpChecker)
+            .register("smtp", mail::MailChecker)
+            .register("imap", mail::MailChecker)
+            .register("pop3", mail::MailChecker)
+            .register("mqtt", broker::BrokerChecker)
+            .register("amqp", broker::BrokerChecker)
+            .register("kafka", broker::BrokerChecker)
+            .register("ssh", ssh::SshChecker)
+            .register("script", script::ScriptChecker);
+        registry
+    }
+
+    /// Serve `check_type` with `checker`, replacing any checker registered for it
+    pub fn register(&mut self, check_type: &str, checker: impl Checker + 'static) -> &mut Self {
+        self.checkers
+            .insert(check_type.to_string(), Box::new(checker));
+        self
+    }
+
+    /// Checker serving `check_type`, if one is registered
+    pub fn get(&self, check_type: &str) -> Option<&dyn Checker> {
+        self.checkers.get(check_type).map(AsRef::as_ref)
+    }
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Use `registry` for validating and running checks. Must be called before the
+/// config is loaded, otherwise the built-in registry is already in use.
+pub fn install_registry(registry: Registry) -> Result<()> {
+    if REGISTRY.set(registry).is_err() {
+        bail!("check registry is already in use");
+    }
+    Ok(())
+}
+
+/// Registry in use, the built-in one unless another was installed
+pub fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(Registry::builtin)
+}
+
+/// Result of checking an endpoint whose section doesn't parse. Validation rejects
+/// such configs, so this only happens for checks run without loading a config.
+pub(super) fn section_error(name: &str, endpoint: &Endpoint, error: String) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+    result.error = Some(error);
+    result.error_type = Some(ErrorType::Unknown);
+    result
+}
+
+/// Error for an address that isn't `host` or `host:port`, optionally with the
+/// check type as scheme
+pub(super) fn host_port_error(endpoint: &Endpoint, server: &str) -> Option<String> {
+    let scheme = format!("{}://", endpoint.check_type.as_str());
+    let addr = endpoint
+        .addr
+        .strip_prefix(&scheme)
+        .unwrap_or(&endpoint.addr);
+    (addr.is_empty() || addr.contains(['/', '@'])).then(|| {
+        format!(
+            "{server} address '{}' should be 'host' or 'host:port'",
+            endpoint.addr
+        )
+    })
+}
+
+struct Http;
+
+impl Checker for Http {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let resolved_addr = endpoint.resolved_addr();
+        Validation {
+            errors: Url::parse(&resolved_addr)
+                .err()
+                .map(|e| format!("invalid URL '{resolved_addr}': {e}"))
+                .into_iter()
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(check_http(name, endpoint))
+    }
+}
+
+struct Tcp;
+
+impl Checker for Tcp {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        // TCP addresses should be in format "host:port" or "tcp://host:port"
+        let addr = endpoint
+            .addr
+            .strip_prefix("tcp://")
+            .unwrap_or(&endpoint.addr);
+        Validation {
+            errors: (!addr.contains(':'))
+                .then(|| {
+                    format!(
+                        "TCP address '{}' must include port (e.g., 'host:port')",
+                        endpoint.addr
+                    )
+                })
+                .into_iter()
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(check_tcp(name, endpoint))
+    }
+}
+
+struct Dns;
+
+impl Checker for Dns {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        // DNS addresses should be valid hostnames
+        let addr = endpoint
+            .addr
+            .strip_prefix("dns://")
+            .unwrap_or(&endpoint.addr);
+        Validation {
+            errors: addr
+                .contains("://")
+                .then(|| {
+                    format!(
+                        "DNS address '{}' should be a hostname, not a URL",
+                        endpoint.addr
+                    )
+                })
+                .into_iter()
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(check_dns(name, endpoint))
+    }
+}
+
+struct Flow;
+
+impl Checker for Flow {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let resolved_addr = endpoint.resolved_addr();
+        Validation {
+            errors: match Url::parse(&resolved_addr) {
+                Ok(base) => validate_flow_steps(&base, &endpoint.steps),
+                Err(e) => vec![format!("invalid URL '{resolved_addr}': {e}")],
+            },
+            warnings: Vec::new(),
+        }
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(flow::check_flow(name, endpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::{CheckStatus, ErrorType, base_result, check_endpoint};
+    use crate::config::CheckType;
+
+    /// Check type only available in tests, configured by a `[endpoints.x.fixed]` section
+    struct Fixed;
+
+    #[derive(serde::Deserialize)]
+    struct FixedConfig {
+        up: bool,
+    }
+
+    impl Checker for Fixed {
+        fn validate(&self, endpoint: &Endpoint) -> Validation {
+            match endpoint.section::<FixedConfig>() {
+                Ok(Some(_)) => Validation::default(),
+                Ok(None) => Validation {
+                    errors: vec!["fixed checks require a [fixed] section".to_string()],
+                    warnings: Vec::new(),
+                },
+                Err(e) => Validation {
+                    errors: vec![e],
+                    warnings: Vec::new(),
+                },
+            }
+        }
+
+        fn check<'a>(
+            &'a self,
+            name: &'a str,
+            endpoint: &'a Endpoint,
+        ) -> BoxFuture<'a, CheckResult> {
+            Box::pin(async move {
+                let mut result = base_result(name, endpoint);
+                if let Ok(Some(FixedConfig { up: true })) = endpoint.section() {
+                    result.status = CheckStatus::Up;
+                } else {
+                    result.error = Some("fixed down".to_string());
+                    result.error_type = Some(ErrorType::Unknown);
+                }
+                result
+            })
+        }
+    }
+
+    fn fixed_endpoint(section: &str) -> Endpoint {
+        toml::from_str(&format!(
+            "addr = \"anything\"\ntype = \"fixed\"\n[fixed]\n{section}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn builtin_registry_serves_every_builtin_type() {
+        let registry = Registry::builtin();
+        for check_type in [
+            "http", "tcp", "dns", "flow", "domain", "udp", "smtp", "imap", "pop3", "mqtt", "amqp",
+            "kafka", "ssh", "script",
+        ] {
+            assert!(registry.get(check_type).is_some(), "{check_type}");
+        }
+        assert!(registry.get("fixed").is_none());
+    }
+
+    #[test]
+    fn custom_check_type_reads_its_section() {
+        let mut registry = Registry::builtin();
+        registry.register("fixed", Fixed);
+        let checker = registry.get("fixed").unwrap();
+
+        let valid = fixed_endpoint("up = true");
+        let invalid = fixed_endpoint("up = \"yes\"");
+
+        assert_eq!(valid.check_type, CheckType::Other("fixed".to_string()));
+        assert_eq!(checker.validate(&valid), Validation::default());
+        assert_eq!(checker.validate(&invalid).errors.len(), 1);
+        assert!(
+            checker.validate(&invalid).errors[0].starts_with("[fixed] section: "),
+            "{:?}",
+            checker.validate(&invalid)
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_checker_runs_its_check() {
+        let mut registry = Registry::builtin();
+        registry.register("fixed", Fixed);
+        let endpoint = fixed_endpoint("up = true");
+
+        let result = registry
+            .get("fixed")
+            .unwrap()
+            .check("test", &endpoint)
+            .await;
+
+        assert!(result.is_up());
+    }
+
+    #[tokio::test]
+    async fn unregistered_check_type_is_down() {
+        let endpoint = fixed_endpoint("up = true");
+
+        let result = check_endpoint("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Unknown));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("no checker is registered for check type 'fixed'")
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use ulid::Ulid;
+
+use super::CheckStatus;
+use crate::config::Endpoint;
+use crate::db::endpoint_id_from_name;
+
+/// Time until the endpoint's next check plus random jitter: the next match of its
+/// `schedule`, or else its slot on the interval grid. After `consecutive_down`
+/// failed checks, `down_interval` brings the next check forward.
+///
+/// Every endpoint runs at a fixed offset into its interval derived from its ID, so
+/// checks spread evenly over the interval instead of firing together, and a restarted
+/// checker (after a reload or a restart of the process) keeps the same slots.
+pub(super) fn next_check_delay(name: &str, endpoint: &Endpoint, consecutive_down: u32) -> Duration {
+    let jitter_ms = endpoint.jitter.saturating_mul(1000);
+    let jitter = if jitter_ms > 0 {
+        Duration::from_millis(rand::random_range(0..=jitter_ms))
+    } else {
+        Duration::ZERO
+    };
+    let now = Utc::now();
+    let scheduled = endpoint
+        .schedule
+        .as_deref()
+        .and_then(|schedule| delay_until_scheduled(schedule, endpoint.timezone, now))
+        .unwrap_or_else(|| delay_until_slot(name, endpoint.interval, now.into()));
+    let delay =
+        down_delay(endpoint, consecutive_down).map_or(scheduled, |down| down.min(scheduled));
+    delay + jitter
+}
+
+/// Window after startup over which the first checks are spread
+const STARTUP_WINDOW_MS: u64 = 30_000;
+
+/// Time after startup until the endpoint's first check. Endpoints with a `schedule`
+/// wait for its next match. Other first checks spread over the startup window (or
+/// the interval, if shorter) by the endpoint's slot offset, so the dashboard fills
+/// quickly without every endpoint being checked at once.
+pub(super) fn startup_delay(name: &str, endpoint: &Endpoint) -> Duration {
+    if endpoint.schedule.is_some() {
+        return next_check_delay(name, endpoint, 0);
+    }
+    let window_ms = endpoint
+        .interval
+        .saturating_mul(1000)
+        .clamp(1, STARTUP_WINDOW_MS);
+    Duration::from_millis(slot_offset_ms(name, window_ms))
+}
+
+/// Failed checks in a row after a check with `status`. Unreachable endpoints weren't
+/// checked while a dependency is down, so they don't count towards `down_interval`.
+pub(super) fn consecutive_down_after(status: CheckStatus, consecutive_down: u32) -> u32 {
+    match status {
+        CheckStatus::Down => consecutive_down.saturating_add(1),
+        CheckStatus::Up
+        | CheckStatus::Degraded
+        | CheckStatus::NotMonitored
+        | CheckStatus::Unreachable
+        | CheckStatus::PartialOutage => 0,
+    }
+}
+
+/// Delay after `consecutive_down` failed checks: `down_interval`, multiplied by
+/// `down_backoff` for every failure after the first
+fn down_delay(endpoint: &Endpoint, consecutive_down: u32) -> Option<Duration> {
+    let down_interval = Duration::from_secs(endpoint.down_interval?);
+    let failures_after_first = consecutive_down.checked_sub(1)?;
+    let factor = endpoint
+        .down_backoff
+        .unwrap_or(1.0)
+        .powi(i32::try_from(failures_after_first).unwrap_or(i32::MAX));
+    // Overflow only happens long after the regular schedule takes over again
+    Some(Duration::try_from_secs_f64(down_interval.as_secs_f64() * factor).unwrap_or(Duration::MAX))
+}
+
+/// Whether the endpoint is monitored at `now`, i.e. within its active hours if set
+pub(super) fn is_active(endpoint: &Endpoint, now: DateTime<Utc>) -> bool {
+    endpoint
+        .active_hours
+        .as_ref()
+        .is_none_or(|hours| hours.contains(now.with_timezone(&endpoint.timezone).naive_local()))
+}
+
+/// Time between the endpoint's checks: the gap between the two latest matches of
+/// its `schedule` before `now`, or else its interval
+pub(super) fn check_period(endpoint: &Endpoint, now: DateTime<Utc>) -> Duration {
+    endpoint
+        .schedule
+        .as_deref()
+        .and_then(|schedule| scheduled_period(schedule, endpoint.timezone, now))
+        .unwrap_or(Duration::from_secs(endpoint.interval))
+}
+
+/// Gap between the two latest matches of a cron expression in `timezone` before `now`
+fn scheduled_period(schedule: &str, timezone: Tz, now: DateTime<Utc>) -> Option<Duration> {
+    let mut matches = Cron::from_str(schedule)
+        .ok()?
+        .iter_before(now.with_timezone(&timezone));
+    let latest = matches.next()?;
+    let previous = matches.next()?;
+    (latest - previous).to_std().ok()
+}
+
+/// Time from `now` until the next match of a cron expression in `timezone`
+fn delay_until_scheduled(schedule: &str, timezone: Tz, now: DateTime<Utc>) -> Option<Duration> {
+    let next = Cron::from_str(schedule)
+        .ok()?
+        .find_next_occurrence(&now.with_timezone(&timezone), false)
+        .ok()?;
+    (next.with_timezone(&Utc) - now).to_std().ok()
+}
+
+/// Time from `now` until the next slot of `name` on a grid of `interval` seconds
+fn delay_until_slot(name: &str, interval: u64, now: SystemTime) -> Duration {
+    let interval_ms = interval.saturating_mul(1000).max(1);
+    let offset_ms = slot_offset_ms(name, interval_ms);
+    let now_ms = now.duration_since(UNIX_EPOCH).map_or(0, |since| {
+        u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+    });
+
+    let into_cycle = (now_ms % interval_ms + interval_ms - offset_ms) % interval_ms;
+    Duration::from_millis(interval_ms - into_cycle)
+}
+
+/// Offset of the endpoint's slot into its interval
+fn slot_offset_ms(name: &str, interval_ms: u64) -> u64 {
+    let id = Ulid::from_string(&endpoint_id_from_name(name)).map_or(0, u128::from);
+    u64::try_from(id % u128::from(interval_ms)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn delay_lands_on_the_endpoint_slot() {
+        let offset = slot_offset_ms("api", 60_000);
+        for now in [0, 1_234, 59_999, 600_000, 1_700_000_123_456] {
+            let delay = delay_until_slot("api", 60, at(now));
+            let slot = now + u64::try_from(delay.as_millis()).unwrap();
+            assert_eq!(slot % 60_000, offset, "now = {now}");
+            assert!(delay > Duration::ZERO && delay <= Duration::from_mins(1));
+        }
+    }
+
+    #[test]
+    fn check_at_its_slot_waits_a_full_interval() {
+        let offset = slot_offset_ms("api", 30_000);
+        let delay = delay_until_slot("api", 30, at(90_000 + offset));
+        assert_eq!(delay, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn slots_are_stable_and_spread_out() {
+        assert_eq!(slot_offset_ms("api", 60_000), slot_offset_ms("api", 60_000));
+
+        let offsets: Vec<_> = (0..100)
+            .map(|i| slot_offset_ms(&format!("endpoint-{i}"), 60_000))
+            .collect();
+        // All 100 endpoints in the same second would mean no staggering
+        let mut seconds: Vec<_> = offsets.iter().map(|ms| ms / 1000).collect();
+        seconds.dedup();
+        assert!(seconds.len() > 30, "{offsets:?}");
+    }
+
+    #[test]
+    fn startup_checks_spread_over_the_startup_window() {
+        let endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        let mut short = endpoint.clone();
+        short.interval = 10;
+
+        let delays: Vec<_> = (0..100)
+            .map(|i| startup_delay(&format!("endpoint-{i}"), &endpoint))
+            .collect();
+
+        assert!(delays.iter().all(|delay| *delay < Duration::from_secs(30)));
+        let mut seconds: Vec<_> = delays.iter().map(Duration::as_secs).collect();
+        seconds.sort_unstable();
+        seconds.dedup();
+        assert!(seconds.len() > 20, "{delays:?}");
+        assert!(startup_delay("api", &short) < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn scheduled_endpoints_start_at_their_next_match() {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        // Only on February 29th, so never within the startup window
+        endpoint.schedule = Some("0 0 29 2 *".to_string());
+
+        assert!(startup_delay("api", &endpoint) > Duration::from_hours(24));
+    }
+
+    #[test]
+    fn only_down_checks_count_as_consecutive_failures() {
+        assert_eq!(consecutive_down_after(CheckStatus::Down, 2), 3);
+        assert_eq!(consecutive_down_after(CheckStatus::Unreachable, 2), 0);
+        assert_eq!(consecutive_down_after(CheckStatus::PartialOutage, 2), 0);
+        assert_eq!(consecutive_down_after(CheckStatus::Up, 2), 0);
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn schedule_runs_at_the_next_match() {
+        let delay = delay_until_scheduled("5 * * * *", Tz::UTC, utc("2026-03-02T10:04:30Z"));
+        assert_eq!(delay, Some(Duration::from_secs(30)));
+
+        let delay = delay_until_scheduled("5 * * * *", Tz::UTC, utc("2026-03-02T10:05:00Z"));
+        assert_eq!(delay, Some(Duration::from_hours(1)));
+    }
+
+    #[test]
+    fn check_period_follows_the_schedule() {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        assert_eq!(
+            check_period(&endpoint, utc("2026-03-02T10:04:30Z")),
+            Duration::from_mins(1)
+        );
+
+        endpoint.schedule = Some("0 3 * * *".to_string());
+        assert_eq!(
+            check_period(&endpoint, utc("2026-03-02T10:04:30Z")),
+            Duration::from_hours(24)
+        );
+    }
+
+    #[test]
+    fn schedule_uses_the_timezone() {
+        // 09:00 in Berlin is 08:00 UTC in winter
+        let delay = delay_until_scheduled(
+            "0 9 * * *",
+            chrono_tz::Europe::Berlin,
+            utc("2026-01-05T07:00:00Z"),
+        );
+        assert_eq!(delay, Some(Duration::from_hours(1)));
+    }
+
+    #[test]
+    fn invalid_schedule_falls_back_to_the_interval() {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.schedule = Some("not cron".to_string());
+        assert!(next_check_delay("api", &endpoint, 0) <= Duration::from_mins(1));
+    }
+
+    #[test]
+    fn active_hours_use_the_timezone() {
+        let endpoint: Endpoint = toml::from_str(
+            r#"
+            addr = "https://example.com"
+            timezone = "America/New_York"
+            active_hours = { start = "09:00", end = "17:00", days = ["mon", "tue", "wed", "thu", "fri"] }
+            "#,
+        )
+        .unwrap();
+
+        // Monday 08:30 and 09:30 in New York
+        assert!(!is_active(&endpoint, utc("2026-03-02T13:30:00Z")));
+        assert!(is_active(&endpoint, utc("2026-03-02T14:30:00Z")));
+        // Saturday 10:00
+        assert!(!is_active(&endpoint, utc("2026-03-07T15:00:00Z")));
+    }
+
+    fn down_endpoint(down_backoff: Option<f64>) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.interval = 300;
+        endpoint.down_interval = Some(10);
+        endpoint.down_backoff = down_backoff;
+        endpoint
+    }
+
+    #[test]
+    fn down_interval_applies_only_while_down() {
+        let endpoint = down_endpoint(None);
+
+        assert_eq!(down_delay(&endpoint, 0), None);
+        assert_eq!(down_delay(&endpoint, 1), Some(Duration::from_secs(10)));
+        assert_eq!(down_delay(&endpoint, 5), Some(Duration::from_secs(10)));
+        assert!(next_check_delay("api", &endpoint, 3) <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn down_backoff_grows_up_to_the_regular_schedule() {
+        let endpoint = down_endpoint(Some(2.0));
+
+        assert_eq!(down_delay(&endpoint, 1), Some(Duration::from_secs(10)));
+        assert_eq!(down_delay(&endpoint, 2), Some(Duration::from_secs(20)));
+        assert_eq!(down_delay(&endpoint, 4), Some(Duration::from_secs(80)));
+        assert_eq!(down_delay(&endpoint, 2000), Some(Duration::MAX));
+        assert!(next_check_delay("api", &endpoint, 2000) <= Duration::from_mins(5));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let mut endpoint: Endpoint = toml::from_str("addr = \"https://example.com\"").unwrap();
+        endpoint.interval = 60;
+        endpoint.jitter = 5;
+        let slot = delay_until_slot("api", 60, SystemTime::now());
+        for _ in 0..50 {
+            let delay = next_check_delay("api", &endpoint, 0);
+            // The slot may move on by a few milliseconds between the two calls
+            assert!(delay + Duration::from_secs(1) >= slot);
+            assert!(delay <= slot + Duration::from_secs(5));
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use reqwest::{Client, Method};
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope};
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Handle;
+
+use super::registry::{Checker, Validation};
+use super::{
+    CheckResult, CheckStatus, ErrorType, base_result, clients, open_connection, split_host_port,
+};
+use crate::config::Endpoint;
+
+type ScriptError = (ErrorType, String);
+type HelperResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Largest string, array and map a script may build
+const MAX_STRING_SIZE: usize = 4 << 20;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 10_000;
+/// Deepest function call nesting, which also bounds recursion
+const MAX_CALL_LEVELS: usize = 64;
+/// Largest reply read by `tcp_send`
+const MAX_TCP_REPLY: usize = 64 * 1024;
+/// Largest response body read by the HTTP helpers, which scripts couldn't hold anyway
+const MAX_HTTP_BODY: usize = MAX_STRING_SIZE;
+
+/// Options of a script check, from its `[script]` section
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptConfig {
+    /// Rhai source, returning `true`, `false` or a map with `up`, `message` and `metrics`
+    source: String,
+}
+
+pub(super) struct ScriptChecker;
+
+impl Checker for ScriptChecker {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let errors = match endpoint.section::<ScriptConfig>() {
+            Ok(Some(config)) => Engine::new()
+                .compile(&config.source)
+                .err()
+                .map(|e| format!("script: {e}"))
+                .into_iter()
+                .collect(),
+            Ok(None) => {
+                vec!["script checks require a [script] section with the source".to_string()]
+            }
+            Err(e) => vec![e],
+        };
+        Validation {
+            errors,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(check_script(name, endpoint))
+    }
+}
+
+/// What the script returned
+#[derive(Debug, PartialEq)]
+struct Output {
+    up: bool,
+    message: Option<String>,
+    metrics: Vec<(String, f64)>,
+}
+
+/// Run the endpoint's script, which returns `true`, `false` or a map with `up`,
+/// `message` and `metrics`. The script runs on a blocking thread and is stopped
+/// once the endpoint's timeout has passed.
+async fn check_script(name: &str, endpoint: &Endpoint) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let source = match endpoint.section::<ScriptConfig>() {
+        Ok(Some(config)) => config.source,
+        Ok(None) => {
+            result.error = Some("no script configured".to_string());
+            result.error_type = Some(ErrorType::ScriptError);
+            return result;
+        }
+        Err(e) => {
+            result.error = Some(e);
+            result.error_type = Some(ErrorType::ScriptError);
+            return result;
+        }
+    };
+    let client = match clients::shared_client(endpoint) {
+        Ok(client) => client,
+        Err(e) => {
+            result.error = Some(format!("failed to build HTTP client: {e}"));
+            result.error_type = Some(ErrorType::ClientBuild);
+            return result;
+        }
+    };
+
+    let start = Instant::now();
+    let helpers = Helpers {
+        runtime: Handle::current(),
+        client,
+        endpoint: endpoint.clone(),
+        deadline: start + Duration::from_secs(endpoint.timeout),
+    };
+    let script_name = name.to_string();
+    let outcome = tokio::task::spawn_blocking(move || run(&script_name, &source, helpers)).await;
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+
+    let outcome = outcome.unwrap_or_else(|e| {
+        Err((
+            ErrorType::ScriptError,
+            format!("script stopped unexpectedly: {e}"),
+        ))
+    });
+    match outcome {
+        Ok(output) => {
+            result.metrics = output.metrics;
+            if output.up {
+                result.status = CheckStatus::Up;
+                result.message = output.message;
+            } else {
+                result.error = Some(
+                    output
+                        .message
+                        .unwrap_or_else(|| "script reported down".to_string()),
+                );
+                result.error_type = Some(ErrorType::ScriptDown);
+            }
+        }
+        Err((error_type, error)) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
+        }
+    }
+
+    result
+}
+
+/// Evaluate the script with the endpoint's address in scope as `addr`
+fn run(name: &str, source: &str, helpers: Helpers) -> Result<Output, ScriptError> {
+    let timeout = helpers.endpoint.timeout;
+    let mut scope = Scope::new();
+    scope.push_constant("addr", helpers.endpoint.resolved_addr());
+
+    let value = engine(name, helpers)
+        .eval_with_scope::<Dynamic>(&mut scope, source)
+        .map_err(|e| match *e {
+            EvalAltResult::ErrorTerminated(..) => (
+                ErrorType::Timeout,
+                format!("script did not finish within {timeout}s"),
+            ),
+            e => (ErrorType::ScriptError, format!("script error: {e}")),
+        })?;
+
+    output(value).map_err(|e| (ErrorType::ScriptError, e))
+}
+
+/// Sandboxed engine with size limits, the deadline and the helper functions.
+/// Scripts can't import modules or reach the file system.
+fn engine(name: &str, helpers: Helpers) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new())
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .set_max_call_levels(MAX_CALL_LEVELS);
+
+    let deadline = helpers.deadline;
+    engine.on_progress(move |operations| {
+        // Reading the clock on every operation would slow scripts down noticeably
+        (operations % 1024 == 0 && Instant::now() >= deadline).then_some(Dynamic::UNIT)
+    });
+    let endpoint = name.to_string();
+    engine.on_print(move |text| tracing::info!(endpoint = %endpoint, "script: {text}"));
+    let endpoint = name.to_string();
+    engine.on_debug(move |text, _, _| tracing::debug!(endpoint = %endpoint, "script: {text}"));
+
+    let helpers = Rc::new(helpers);
+    let h = Rc::clone(&helpers);
+    engine.register_fn("http_get", move |url: &str| h.http("GET", url, &Map::new()));
+    let h = Rc::clone(&helpers);
+    engine.register_fn("http_post", move |url: &str, body: &str| {
+        let mut options = Map::new();
+        options.insert("body".into(), body.into());
+        h.http("POST", url, &options)
+    });
+    let h = Rc::clone(&helpers);
+    engine.register_fn(
+        "http_request",
+        move |method: &str, url: &str, options: Map| h.http(method, url, &options),
+    );
+    let h = Rc::clone(&helpers);
+    engine.register_fn("tcp_connect", move |addr: &str| h.tcp_connect(addr));
+    let h = Rc::clone(&helpers);
+    engine.register_fn("tcp_send", move |addr: &str, data: &str| {
+        h.tcp_send(addr, data)
+    });
+
+    engine
+}
+
+/// Network access for scripts, using the endpoint's TLS, proxy and `local_address`
+/// settings. Each call blocks the script until it completes or the deadline passes.
+struct Helpers {
+    runtime: Handle,
+    client: Client,
+    endpoint: Endpoint,
+    deadline: Instant,
+}
+
+impl Helpers {
+    /// Time left before the deadline, or an error once it has passed
+    fn remaining(&self) -> HelperResult<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("timed out".into());
+        }
+        Ok(remaining)
+    }
+
+    /// Send a request with the `headers` and `body` in `options` and return a map
+    /// with `status`, `headers`, `body` and `time_ms`
+    fn http(&self, method: &str, url: &str, options: &Map) -> HelperResult<Map> {
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| format!("invalid HTTP method '{method}'"))?;
+        let mut request = self.client.request(method, url).timeout(self.remaining()?);
+        if let Some(headers) = options.get("headers") {
+            let headers = headers
+                .clone()
+                .try_cast::<Map>()
+                .ok_or("http_request: `headers` should be a map")?;
+            for (name, value) in headers {
+                request = request.header(name.as_str(), value.to_string());
+            }
+        }
+        if let Some(body) = options.get("body") {
+            request = request.body(body.to_string());
+        }
+
+        let start = Instant::now();
+        let (status, headers, body) = self
+            .runtime
+            .block_on(async {
+                let mut response = request.send().await.map_err(|e| e.to_string())?;
+                let status = response.status();
+                let headers: Map = response
+                    .headers()
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                        (name.as_str().into(), value.into())
+                    })
+                    .collect();
+                let mut body = Vec::new();
+                while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+                    if body.len() + chunk.len() > MAX_HTTP_BODY {
+                        return Err(format!("response body exceeds {MAX_HTTP_BODY} bytes"));
+                    }
+                    body.extend_from_slice(&chunk);
+                }
+                Ok((status, headers, String::from_utf8_lossy(&body).into_owned()))
+            })
+            .map_err(|e| format!("{url}: {e}"))?;
+
+        let mut response = Map::new();
+        response.insert("status".into(), i64::from(status.as_u16()).into());
+        response.insert("headers".into(), headers.into());
+        response.insert("body".into(), body.into());
+        response.insert("time_ms".into(), elapsed_ms(start).into());
+        Ok(response)
+    }
+
+    /// Connect to `addr` ("host:port") and return the time taken in milliseconds
+    fn tcp_connect(&self, addr: &str) -> HelperResult<i64> {
+        let start = Instant::now();
+        let (host, port) = target(addr)?;
+        self.runtime
+            .block_on(tokio::time::timeout(
+                self.remaining()?,
+                open_connection(&self.endpoint, host, port),
+            ))
+            .map_err(|_| format!("{addr}: connection timed out"))?
+            .map_err(|(_, e)| format!("{addr}: {e}"))?;
+        Ok(elapsed_ms(start))
+    }
+
+    /// Send `data` to `addr` ("host:port") and return the first reply received
+    fn tcp_send(&self, addr: &str, data: &str) -> HelperResult<String> {
+        let (host, port) = target(addr)?;
+        self.runtime
+            .block_on(tokio::time::timeout(self.remaining()?, async {
+                let mut stream = open_connection(&self.endpoint, host, port)
+                    .await
+                    .map_err(|(_, e)| e)?;
+                stream
+                    .write_all(data.as_bytes())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let mut reply = vec![0; MAX_TCP_REPLY];
+                let len = stream.read(&mut reply).await.map_err(|e| e.to_string())?;
+                Ok::<_, String>(String::from_utf8_lossy(&reply[..len]).into_owned())
+            }))
+            .map_err(|_| format!("{addr}: no reply before the timeout"))?
+            .map_err(|e| format!("{addr}: {e}").into())
+    }
+}
+
+/// Split a "host:port" or "tcp://host:port" helper argument
+fn target(addr: &str) -> HelperResult<(&str, u16)> {
+    split_host_port(addr.strip_prefix("tcp://").unwrap_or(addr))
+        .ok_or_else(|| format!("address '{addr}' must include port").into())
+}
+
+fn elapsed_ms(start: Instant) -> i64 {
+    i64::try_from(start.elapsed().as_millis()).unwrap_or(i64::MAX)
+}
+
+/// Interpret the value a script returned
+fn output(value: Dynamic) -> Result<Output, String> {
+    if let Ok(up) = value.as_bool() {
+        return Ok(Output {
+            up,
+            message: None,
+            metrics: Vec::new(),
+        });
+    }
+    let type_name = value.type_name();
+    let Some(map) = value.try_cast::<Map>() else {
+        return Err(format!(
+            "script returned {type_name}, expected true, false or a map with an `up` field"
+        ));
+    };
+
+    let up = map
+        .get("up")
+        .and_then(|up| up.as_bool().ok())
+        .ok_or("script result needs a boolean `up` field")?;
+    let message = map
+        .get("message")
+        .filter(|message| !message.is_unit())
+        .map(ToString::to_string);
+    let metrics = match map.get("metrics") {
+        Some(metrics) => metrics
+            .clone()
+            .try_cast::<Map>()
+            .ok_or("script result `metrics` should be a map")?
+            .into_iter()
+            .map(|(name, value)| {
+                number(&value)
+                    .map(|value| (name.to_string(), value))
+                    .ok_or_else(|| format!("metric '{name}' is not a number"))
+            })
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(Output {
+        up,
+        message,
+        metrics,
+    })
+}
+
+fn number(value: &Dynamic) -> Option<f64> {
+    value.as_float().ok().or_else(|| {
+        // Integers beyond 2^53 lose precision, as they would in JSON
+        value
+            .as_int()
+            .ok()
+            .and_then(|int| serde_json::Number::from(int).as_f64())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Server on a random local port that answers every connection with `reply`
+    async fn spawn_server(reply: &'static [u8]) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(reply).await;
+            }
+        });
+        addr
+    }
+
+    fn make_endpoint(addr: &str, script: &str) -> Endpoint {
+        let mut endpoint: Endpoint =
+            toml::from_str(&format!("addr = \"{addr}\"\ntype = \"script\"")).unwrap();
+        let mut section = toml::Table::new();
+        section.insert("source".to_string(), script.into());
+        endpoint
+            .sections
+            .insert("script".to_string(), section.into());
+        endpoint.timeout = 2;
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn map_result_reports_message_and_metrics() {
+        let addr = spawn_server(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 28\r\nConnection: close\r\n\r\n{\"queue\":12,\"lag\":0.5,\"x\":1}",
+        )
+        .await;
+        let endpoint = make_endpoint(
+            &format!("http://{addr}/stats"),
+            r"
+            let response = http_get(addr);
+            let stats = parse_json(response.body);
+            #{
+                up: response.status == 200 && stats.queue < 100,
+                message: `queue at ${stats.queue}`,
+                metrics: #{ queue: stats.queue, lag: stats.lag },
+            }
+            ",
+        );
+
+        let result = check_script("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+        assert_eq!(result.message.as_deref(), Some("queue at 12"));
+        assert_eq!(
+            result.metrics,
+            vec![("lag".to_string(), 0.5), ("queue".to_string(), 12.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn http_helpers_refuse_oversized_bodies() {
+        let mut reply = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+        reply.resize(reply.len() + MAX_HTTP_BODY + 1, b'x');
+        let addr = spawn_server(reply.leak()).await;
+        let endpoint = make_endpoint(
+            &format!("http://{addr}/dump"),
+            r#"
+            let refused = false;
+            try {
+                http_get(addr);
+            } catch (e) {
+                refused = e.contains("response body exceeds");
+            }
+            refused
+            "#,
+        );
+
+        let result = check_script("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn false_result_is_down() {
+        let endpoint = make_endpoint(
+            "example",
+            r#"#{ up: false, message: "replica lagging", metrics: #{ lag: 30 } }"#,
+        );
+
+        let result = check_script("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ScriptDown));
+        assert_eq!(result.error.as_deref(), Some("replica lagging"));
+        assert_eq!(result.metrics, vec![("lag".to_string(), 30.0)]);
+    }
+
+    #[tokio::test]
+    async fn tcp_helpers_talk_to_server() {
+        let addr = spawn_server(b"+PONG\r\n").await;
+        let endpoint = make_endpoint(
+            &addr.to_string(),
+            r#"
+            tcp_connect(addr);
+            tcp_send(addr, "PING\r\n") == "+PONG\r\n"
+            "#,
+        );
+
+        let result = check_script("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn helper_errors_can_be_caught() {
+        let endpoint = make_endpoint(
+            "example",
+            r#"
+            let refused = false;
+            try {
+                tcp_connect("127.0.0.1:1");
+            } catch (e) {
+                refused = e.contains("127.0.0.1:1");
+            }
+            refused
+            "#,
+        );
+
+        let result = check_script("test", &endpoint).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn thrown_error_is_script_error() {
+        let endpoint = make_endpoint("example", r#"throw "no leader elected""#);
+
+        let result = check_script("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ScriptError));
+        assert!(
+            result
+                .error
+                .as_deref()
+                .is_some_and(|e| e.contains("no leader elected")),
+            "{:?}",
+            result.error
+        );
+    }
+
+    #[tokio::test]
+    async fn endless_loop_times_out() {
+        let mut endpoint = make_endpoint("example", "loop {}");
+        endpoint.timeout = 1;
+
+        let result = check_script("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Timeout));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("script did not finish within 1s")
+        );
+    }
+
+    #[tokio::test]
+    async fn imports_are_not_allowed() {
+        let endpoint = make_endpoint("example", r#"import "/etc/passwd" as secrets; true"#);
+
+        let result = check_script("test", &endpoint).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ScriptError));
+    }
+
+    #[test]
+    fn output_rejects_invalid_results() {
+        assert_eq!(
+            output(Dynamic::from(42_i64)),
+            Err(
+                "script returned i64, expected true, false or a map with an `up` field".to_string()
+            )
+        );
+
+        let mut map = Map::new();
+        map.insert("up".into(), true.into());
+        map.insert(
+            "metrics".into(),
+            Dynamic::from_map({
+                let mut metrics = Map::new();
+                metrics.insert("state".into(), "green".into());
+                metrics
+            }),
+        );
+        assert_eq!(
+            output(map.into()),
+            Err("metric 'state' is not a number".to_string())
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    ClientBuilder,
+    dns::{Addrs, Name, Resolve, Resolving},
+};
+use rustls::{
+    ClientConfig, NamedGroup,
+    client::{
+        ClientSessionMemoryCache, ClientSessionStore, Resumption, Tls12ClientSessionValue,
+        Tls13ClientSessionValue,
+    },
+    pki_types::ServerName,
+};
+use tower::{Layer, Service};
+
+use crate::config::Endpoint;
+
+use super::{HttpTimings, tls};
+
+/// Phase durations collected while a request is in flight
+#[derive(Debug, Default)]
+struct PhaseLog {
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    tls: Option<Duration>,
+    pending: Option<PendingConnection>,
+}
+
+/// A connection that is still being established
+#[derive(Debug)]
+struct PendingConnection {
+    started: Instant,
+    dns: Duration,
+    tls_started: Option<Instant>,
+}
+
+fn add(total: &mut Option<Duration>, elapsed: Duration) {
+    *total = Some(total.unwrap_or_default() + elapsed);
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Collects DNS, connect and TLS durations for the connections one check opens.
+/// Phases of several connections (e.g. across redirects) are summed.
+#[derive(Debug, Clone, Default)]
+pub(super) struct TimingRecorder(Arc<Mutex<PhaseLog>>);
+
+tokio::task_local! {
+    /// Recorder of the check sending a request in the current task. Clients are
+    /// shared between checks, so their instrumentation records into this one.
+    static CURRENT: TimingRecorder;
+}
+
+impl TimingRecorder {
+    fn log(&self) -> MutexGuard<'_, PhaseLog> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `request`, recording the phases of the connections it opens
+    pub(super) async fn record<F: Future>(&self, request: F) -> F::Output {
+        CURRENT.scope(self.clone(), request).await
+    }
+
+    /// Recorder of the request being sent, if it is recorded
+    fn current() -> Option<TimingRecorder> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    fn dns_resolved(&self, elapsed: Duration) {
+        let mut log = self.log();
+        add(&mut log.dns, elapsed);
+        if let Some(ref mut pending) = log.pending {
+            pending.dns += elapsed;
+        }
+    }
+
+    fn connection_started(&self) {
+        self.log().pending = Some(PendingConnection {
+            started: Instant::now(),
+            dns: Duration::ZERO,
+            tls_started: None,
+        });
+    }
+
+    fn tls_started(&self) {
+        if let Some(ref mut pending) = self.log().pending {
+            pending.tls_started.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn connection_finished(&self, established: bool) {
+        let mut log = self.log();
+        let Some(pending) = log.pending.take() else {
+            return;
+        };
+        if !established {
+            return;
+        }
+
+        let now = Instant::now();
+        let tcp_done = pending.tls_started.unwrap_or(now);
+        add(
+            &mut log.connect,
+            tcp_done
+                .duration_since(pending.started)
+                .saturating_sub(pending.dns),
+        );
+        if let Some(tls_started) = pending.tls_started {
+            add(&mut log.tls, now.duration_since(tls_started));
+        }
+    }
+
+    /// Build the breakdown for a request whose headers arrived after `headers_after`
+    /// (measured from before DNS resolution) and whose body took `download` to read.
+    pub(super) fn finish(
+        &self,
+        headers_after: Option<Duration>,
+        download: Option<Duration>,
+    ) -> HttpTimings {
+        let log = self.log();
+        let setup = [log.dns, log.connect, log.tls]
+            .into_iter()
+            .flatten()
+            .sum::<Duration>();
+
+        HttpTimings {
+            dns: log.dns.map(millis),
+            connect: log.connect.map(millis),
+            tls: log.tls.map(millis),
+            ttfb: headers_after.map(|elapsed| millis(elapsed.saturating_sub(setup))),
+            download: download.map(millis),
+        }
+    }
+}
+
+/// System resolver that records how long each lookup takes
+struct TimedResolver;
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let recorder = TimingRecorder::current();
+        Box::pin(async move {
+            let started = Instant::now();
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(recorder) = recorder {
+                recorder.dns_resolved(started.elapsed());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Connector layer that marks when a new connection starts and is ready
+#[derive(Clone)]
+struct TimedConnectLayer;
+
+impl<S> Layer<S> for TimedConnectLayer {
+    type Service = TimedConnect<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimedConnect { inner }
+    }
+}
+
+#[derive(Clone)]
+struct TimedConnect<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for TimedConnect<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let recorder = TimingRecorder::current();
+        if let Some(ref recorder) = recorder {
+            recorder.connection_started();
+        }
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let connection = connecting.await;
+            if let Some(recorder) = recorder {
+                recorder.connection_finished(connection.is_ok());
+            }
+            connection
+        })
+    }
+}
+
+/// Session cache that marks the start of each TLS handshake. rustls asks the store
+/// for a key exchange hint while building the `ClientHello`, right after TCP connects.
+#[derive(Debug)]
+struct TimedSessionStore {
+    inner: ClientSessionMemoryCache,
+}
+
+impl ClientSessionStore for TimedSessionStore {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.inner.set_kx_hint(server_name, group);
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        if let Some(recorder) = TimingRecorder::current() {
+            recorder.tls_started();
+        }
+        self.inner.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.inner.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.inner.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.inner.remove_tls12_session(server_name);
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.inner.insert_tls13_ticket(server_name, value);
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.inner.take_tls13_ticket(server_name)
+    }
+}
+
+/// rustls configuration matching what reqwest builds from the endpoint's TLS options,
+/// with a session store that reports handshake start times
+fn tls_config(endpoint: &Endpoint) -> Result<ClientConfig, String> {
+    let mut config = tls::client_config(endpoint)?;
+
+    // reqwest is built without HTTP/2
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    config.resumption = Resumption::store(Arc::new(TimedSessionStore {
+        inner: ClientSessionMemoryCache::new(32),
+    }));
+
+    Ok(config)
+}
+
+/// Time DNS, connection setup and TLS handshakes of the requests `builder`'s client
+/// sends within `TimingRecorder::record`
+pub(super) fn instrument(
+    builder: ClientBuilder,
+    endpoint: &Endpoint,
+) -> Result<ClientBuilder, String> {
+    Ok(builder
+        .use_preconfigured_tls(tls_config(endpoint)?)
+        .dns_resolver(Arc::new(TimedResolver))
+        .connector_layer(TimedConnectLayer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_is_empty_without_connections() {
+        let recorder = TimingRecorder::default();
+
+        assert_eq!(recorder.finish(None, None), HttpTimings::default());
+    }
+
+    #[test]
+    fn finish_splits_connect_and_tls_and_derives_ttfb() {
+        let recorder = TimingRecorder::default();
+        recorder.connection_started();
+        recorder.dns_resolved(Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(20));
+        recorder.tls_started();
+        std::thread::sleep(Duration::from_millis(20));
+        recorder.connection_finished(true);
+
+        let timings = recorder.finish(
+            Some(Duration::from_millis(200)),
+            Some(Duration::from_millis(7)),
+        );
+
+        assert_eq!(timings.dns, Some(5));
+        assert!(timings.connect.unwrap() >= 15);
+        assert!(timings.tls.unwrap() >= 20);
+        // Phases are truncated to whole milliseconds individually
+        let setup = 5 + timings.connect.unwrap() + timings.tls.unwrap();
+        assert!((198..=200).contains(&(setup + timings.ttfb.unwrap())));
+        assert_eq!(timings.download, Some(7));
+    }
+
+    #[test]
+    fn connection_without_tls_has_no_tls_phase() {
+        let recorder = TimingRecorder::default();
+        recorder.connection_started();
+        recorder.connection_finished(true);
+
+        let timings = recorder.finish(Some(Duration::from_millis(10)), None);
+
+        assert_eq!(timings.connect, Some(0));
+        assert_eq!(timings.tls, None);
+    }
+
+    #[test]
+    fn failed_connection_discards_phases() {
+        let recorder = TimingRecorder::default();
+        recorder.connection_started();
+        recorder.tls_started();
+        recorder.connection_finished(false);
+        assert_eq!(recorder.finish(None, None).connect, None);
+    }
+
+    #[tokio::test]
+    async fn records_only_within_the_request() {
+        let recorder = TimingRecorder::default();
+        let other = TimingRecorder::default();
+
+        recorder
+            .record(async {
+                TimingRecorder::current()
+                    .unwrap()
+                    .dns_resolved(Duration::from_millis(3));
+            })
+            .await;
+        assert!(TimingRecorder::current().is_none());
+
+        assert_eq!(recorder.finish(None, None).dns, Some(3));
+        assert_eq!(other.finish(None, None).dns, None);
+    }
+}
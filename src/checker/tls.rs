@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject},
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{TlsConnector, client::TlsStream};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::config::Endpoint;
+
+use super::{CertificateInfo, ErrorType};
+
+/// Connection to a server, plain or TLS
+pub(super) trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Verifier for `skip_tls_verification`, equivalent to reqwest's `danger_accept_invalid_certs`
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// rustls configuration from the endpoint's TLS options, equivalent to what reqwest builds
+pub(super) fn client_config(endpoint: &Endpoint) -> Result<ClientConfig, String> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let tls = &endpoint.tls_files;
+    let builder = if endpoint.skip_tls_verification {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+    } else {
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        if let Some(ref bundle) = tls.ca_bundle {
+            for cert in CertificateDer::pem_slice_iter(bundle) {
+                let cert = cert.map_err(|e| format!("invalid ca_bundle: {e}"))?;
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("invalid ca_bundle: {e}"))?;
+            }
+        }
+        builder.with_root_certificates(roots)
+    };
+
+    Ok(match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            let certs = CertificateDer::pem_slice_iter(cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("invalid client_cert: {e}"))?;
+            let key = PrivateKeyDer::from_pem_slice(key)
+                .map_err(|e| format!("invalid client_key: {e}"))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| format!("invalid client identity: {e}"))?
+        }
+        _ => builder.with_no_client_auth(),
+    })
+}
+
+/// Run a TLS handshake over `stream` and return the encrypted stream with the
+/// details of the server's certificate
+pub(super) async fn connect<S>(
+    stream: S,
+    endpoint: &Endpoint,
+    host: &str,
+) -> Result<(TlsStream<S>, Option<CertificateInfo>), (ErrorType, String)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = client_config(endpoint).map_err(|e| (ErrorType::ClientBuild, e))?;
+    let name = endpoint.tls_server_name.as_deref().unwrap_or(host);
+    let server_name = ServerName::try_from(name.to_string()).map_err(|e| {
+        (
+            ErrorType::ClientBuild,
+            format!("invalid server name '{name}': {e}"),
+        )
+    })?;
+
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| (ErrorType::Tls, format!("TLS handshake failed: {e}")))?;
+    let certificate = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(<[_]>::first)
+        .and_then(|der| certificate_info(der).ok());
+
+    Ok((stream, certificate))
+}
+
+/// Subject, issuer, validity and DNS names of a DER encoded certificate
+pub(super) fn certificate_info(der: &[u8]) -> Result<CertificateInfo, String> {
+    let (_, cert) =
+        X509Certificate::from_der(der).map_err(|e| format!("invalid certificate: {e}"))?;
+    let time = |time: i64| DateTime::<Utc>::from_timestamp(time, 0).unwrap_or_default();
+    let dns_names = match cert.subject_alternative_name() {
+        Ok(Some(names)) => names
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some((*name).to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(CertificateInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        not_before: time(cert.validity().not_before.timestamp()),
+        not_after: time(cert.validity().not_after.timestamp()),
+        dns_names,
+    })
+}
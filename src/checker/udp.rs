@@ -0,0 +1,635 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::BoxFuture;
+use regex::Regex;
+use serde::Deserialize;
+use tokio::net::UdpSocket;
+
+use super::registry::{self, Checker, Validation};
+use super::{CheckResult, CheckStatus, ErrorType, addresses, base_result, split_host_port};
+use crate::config::{Endpoint, substitute_env_vars};
+
+/// How long to wait for an ICMP port unreachable error when no reply is expected
+const UNREACHABLE_GRACE: Duration = Duration::from_millis(250);
+/// Largest datagram read as a reply
+const MAX_REPLY_SIZE: usize = 64 * 1024;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// Size of an NTP packet without extensions
+const NTP_PACKET_SIZE: usize = 48;
+/// Leap indicator 0, version 4, mode 3 (client)
+const NTP_CLIENT_HEADER: u8 = 0b00_100_011;
+const NTP_MODE_SERVER: u8 = 4;
+
+type UdpError = (ErrorType, String);
+
+/// Options of a UDP check, from its `[udp]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct UdpConfig {
+    /// Datagram sent: text, or `{ hex = "..." }` for binary data
+    payload: Option<UdpPayload>,
+    /// Regex the reply must match; without it no reply is awaited
+    expected_reply: Option<String>,
+    /// Protocol spoken (default: raw payload)
+    mode: UdpMode,
+    /// Degrade an NTP check when the clock offset exceeds this (ms)
+    max_clock_offset_ms: Option<u64>,
+}
+
+/// Datagram sent by a UDP check: text, or `{ hex = "..." }` for binary data
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum UdpPayload {
+    Text(String),
+    Hex { hex: String },
+}
+
+impl UdpPayload {
+    /// Bytes to send, with environment variables substituted in text payloads
+    fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            UdpPayload::Text(text) => Ok(substitute_env_vars(text).into_bytes()),
+            UdpPayload::Hex { hex } => {
+                let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+                if !digits.len().is_multiple_of(2) {
+                    return Err(format!("hex payload '{hex}' has an odd number of digits"));
+                }
+                digits
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(|| format!("invalid hex payload '{hex}'"))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Protocol spoken by a UDP check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum UdpMode {
+    /// Send `payload` and optionally match the reply against `expected_reply`
+    #[default]
+    Raw,
+    /// Query an NTP server and report the clock offset
+    Ntp,
+}
+
+pub(super) struct UdpChecker;
+
+impl Checker for UdpChecker {
+    fn validate(&self, endpoint: &Endpoint) -> Validation {
+        let mut validation = Validation::default();
+
+        let addr = endpoint
+            .addr
+            .strip_prefix("udp://")
+            .unwrap_or(&endpoint.addr);
+        if !addr.contains(':') {
+            validation.errors.push(format!(
+                "UDP address '{}' must include port (e.g., 'host:port')",
+                endpoint.addr
+            ));
+        }
+
+        let config = match endpoint.section::<UdpConfig>() {
+            Ok(config) => config.unwrap_or_default(),
+            Err(e) => {
+                validation.errors.push(e);
+                return validation;
+            }
+        };
+        if let Some(Err(e)) = config.payload.as_ref().map(UdpPayload::bytes) {
+            validation.errors.push(format!("payload: {e}"));
+        }
+        if let Some(ref pattern) = config.expected_reply
+            && let Err(e) = Regex::new(pattern)
+        {
+            validation
+                .errors
+                .push(format!("expected_reply: invalid regex '{pattern}': {e}"));
+        }
+
+        // Options of the mode not in use
+        match config.mode {
+            UdpMode::Raw => {
+                if config.max_clock_offset_ms.is_some() {
+                    validation
+                        .warnings
+                        .push("max_clock_offset_ms is only used when mode = \"ntp\"".to_string());
+                }
+            }
+            UdpMode::Ntp => {
+                let raw_options = [
+                    ("payload", config.payload.is_some()),
+                    ("expected_reply", config.expected_reply.is_some()),
+                ];
+                validation.warnings.extend(
+                    raw_options
+                        .iter()
+                        .filter(|(_, set)| *set)
+                        .map(|(option, _)| format!("{option} is ignored when mode = \"ntp\"")),
+                );
+            }
+        }
+
+        validation
+    }
+
+    fn check<'a>(&'a self, name: &'a str, endpoint: &'a Endpoint) -> BoxFuture<'a, CheckResult> {
+        Box::pin(async move {
+            match endpoint.section() {
+                Ok(config) => check_udp(name, endpoint, &config.unwrap_or_default()).await,
+                Err(e) => registry::section_error(name, endpoint, e),
+            }
+        })
+    }
+}
+
+/// Perform a UDP check: send the payload or an NTP query and evaluate the reply
+async fn check_udp(name: &str, endpoint: &Endpoint, config: &UdpConfig) -> CheckResult {
+    let mut result = base_result(name, endpoint);
+
+    let addr = endpoint.resolved_addr();
+    let addr = addr.strip_prefix("udp://").unwrap_or(&addr);
+    let Some((host, port)) = split_host_port(addr) else {
+        result.error = Some(format!("UDP address '{addr}' must include port"));
+        result.error_type = Some(ErrorType::Connection);
+        return result;
+    };
+
+    // Without ip_version or all_addresses this picks the first usable address
+    let targets = match addresses::resolve(host)
+        .await
+        .and_then(|resolved| addresses::select(endpoint, host, &resolved))
+    {
+        Ok(targets) => targets,
+        Err(e) => return addresses::unresolved(result, e),
+    };
+
+    if !addresses::pins_addresses(endpoint) {
+        return exchange(result, endpoint, config, SocketAddr::new(targets[0], port)).await;
+    }
+    let results = futures::future::join_all(targets.into_iter().map(|ip| {
+        let result = result.clone();
+        async move {
+            (
+                ip,
+                exchange(result, endpoint, config, SocketAddr::new(ip, port)).await,
+            )
+        }
+    }))
+    .await;
+    addresses::combine(results)
+}
+
+/// Run the check against one address
+async fn exchange(
+    mut result: CheckResult,
+    endpoint: &Endpoint,
+    config: &UdpConfig,
+    target: SocketAddr,
+) -> CheckResult {
+    let timeout = Duration::from_secs(endpoint.timeout);
+    let start = Instant::now();
+
+    let outcome = tokio::time::timeout(timeout, async {
+        let socket = bind(target, endpoint.local_address).await?;
+        match config.mode {
+            UdpMode::Raw => raw_exchange(&socket, config).await.map(|()| None),
+            UdpMode::Ntp => ntp_exchange(&socket).await.map(Some),
+        }
+    })
+    .await;
+    result.response_time_ms = Some(u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX));
+
+    match outcome {
+        Ok(Ok(offset)) => {
+            result.status = CheckStatus::Up;
+            result.clock_offset_ms = offset;
+            if let (Some(offset), Some(limit)) = (offset, config.max_clock_offset_ms)
+                && offset.unsigned_abs() > limit
+            {
+                result.degrade(format!(
+                    "clock offset {offset}ms exceeds max_clock_offset_ms ({limit}ms)"
+                ));
+            }
+        }
+        Ok(Err((error_type, error))) => {
+            result.error = Some(error);
+            result.error_type = Some(error_type);
+        }
+        Err(_) => {
+            result.error = Some(format!("no reply within {}s", endpoint.timeout));
+            result.error_type = Some(ErrorType::Timeout);
+        }
+    }
+
+    result
+}
+
+/// Open a UDP socket connected to `target`, optionally bound to `local_address`
+async fn bind(target: SocketAddr, local_address: Option<IpAddr>) -> Result<UdpSocket, UdpError> {
+    let local = local_address.unwrap_or(if target.is_ipv4() {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    });
+
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0))
+        .await
+        .map_err(|e| {
+            (
+                ErrorType::Connection,
+                format!("failed to bind to {local}: {e}"),
+            )
+        })?;
+    socket.connect(target).await.map_err(|e| {
+        (
+            ErrorType::Connection,
+            format!("failed to connect to {target}: {e}"),
+        )
+    })?;
+    Ok(socket)
+}
+
+/// Classify an error of a connected UDP socket; ICMP port unreachable shows up as refused
+fn socket_error(e: &std::io::Error) -> UdpError {
+    if e.kind() == std::io::ErrorKind::ConnectionRefused {
+        (ErrorType::PortUnreachable, "port unreachable".to_string())
+    } else {
+        (ErrorType::Connection, e.to_string())
+    }
+}
+
+/// Send the payload, then wait for a reply matching `expected_reply`. Without an
+/// expected reply, only wait briefly for the port to be reported unreachable.
+async fn raw_exchange(socket: &UdpSocket, config: &UdpConfig) -> Result<(), UdpError> {
+    let payload = config
+        .payload
+        .as_ref()
+        .map(UdpPayload::bytes)
+        .transpose()
+        .map_err(|e| (ErrorType::Unknown, e))?
+        .unwrap_or_default();
+    socket.send(&payload).await.map_err(|e| socket_error(&e))?;
+
+    let mut reply = vec![0; MAX_REPLY_SIZE];
+    let Some(ref pattern) = config.expected_reply else {
+        return match tokio::time::timeout(UNREACHABLE_GRACE, socket.recv(&mut reply)).await {
+            Ok(Err(e)) => Err(socket_error(&e)),
+            Ok(Ok(_)) | Err(_) => Ok(()),
+        };
+    };
+
+    let len = socket
+        .recv(&mut reply)
+        .await
+        .map_err(|e| socket_error(&e))?;
+    let reply = String::from_utf8_lossy(&reply[..len]);
+    // Invalid patterns are reported by config validation
+    if Regex::new(pattern).is_ok_and(|regex| regex.is_match(&reply)) {
+        Ok(())
+    } else {
+        Err((
+            ErrorType::ReplyMismatch,
+            format!(
+                "reply did not match '{pattern}': {}",
+                reply.chars().take(100).collect::<String>()
+            ),
+        ))
+    }
+}
+
+/// Query an NTP server and return the offset of the local clock to the server's, in ms
+async fn ntp_exchange(socket: &UdpSocket) -> Result<i64, UdpError> {
+    let mut request = [0; NTP_PACKET_SIZE];
+    request[0] = NTP_CLIENT_HEADER;
+    let originate = ntp_timestamp(SystemTime::now());
+    request[40..48].copy_from_slice(&originate.to_be_bytes());
+    socket.send(&request).await.map_err(|e| socket_error(&e))?;
+
+    let mut reply = vec![0; MAX_REPLY_SIZE];
+    let len = socket
+        .recv(&mut reply)
+        .await
+        .map_err(|e| socket_error(&e))?;
+    let destination = ntp_timestamp(SystemTime::now());
+
+    clock_offset(&reply[..len], originate, destination)
+}
+
+/// Clock offset in ms from an NTP reply to a request sent at `originate` and
+/// received at `destination`: ((T2 - T1) + (T3 - T4)) / 2
+fn clock_offset(reply: &[u8], originate: u64, destination: u64) -> Result<i64, UdpError> {
+    let invalid = |message: String| (ErrorType::NtpInvalid, message);
+
+    if reply.len() < NTP_PACKET_SIZE {
+        return Err(invalid(format!(
+            "NTP reply too short ({} bytes)",
+            reply.len()
+        )));
+    }
+    let field = |offset: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&reply[offset..offset + 8]);
+        u64::from_be_bytes(bytes)
+    };
+
+    if reply[0] & 0b111 != NTP_MODE_SERVER {
+        return Err(invalid(format!("unexpected NTP mode {}", reply[0] & 0b111)));
+    }
+    if reply[1] == 0 {
+        let code = String::from_utf8_lossy(&reply[12..16]);
+        return Err(invalid(format!("NTP server sent kiss code {code}")));
+    }
+    if field(24) != originate {
+        return Err(invalid(
+            "NTP reply does not answer this request".to_string(),
+        ));
+    }
+
+    let [t1, t2, t3, t4] = [originate, field(32), field(40), destination].map(ntp_to_nanos);
+    let offset = i128::midpoint(t2 - t1, t3 - t4);
+    Ok(i64::try_from(offset / 1_000_000).unwrap_or(i64::MAX))
+}
+
+/// NTP timestamp (seconds since 1900 in the upper 32 bits, fraction in the lower)
+fn ntp_timestamp(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_unix.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (u64::from(since_unix.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Nanoseconds since the Unix epoch of an NTP timestamp
+fn ntp_to_nanos(timestamp: u64) -> i128 {
+    let seconds = i128::from(timestamp >> 32) - i128::from(NTP_UNIX_OFFSET);
+    let fraction = (i128::from(timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    seconds * 1_000_000_000 + fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CheckType;
+
+    fn make_endpoint(addr: SocketAddr) -> Endpoint {
+        let mut endpoint: Endpoint = toml::from_str(&format!("addr = \"udp://{addr}\"")).unwrap();
+        endpoint.check_type = CheckType::Udp;
+        endpoint.timeout = 1;
+        endpoint
+    }
+
+    #[test]
+    fn section_parses_payload_and_mode() {
+        let raw: Endpoint = toml::from_str(
+            r#"
+            addr = "game.example.com:27015"
+            type = "udp"
+
+            [udp]
+            payload = { hex = "ff ff ff ff 54" }
+            expected_reply = "^\\xff"
+            "#,
+        )
+        .unwrap();
+        let ntp: Endpoint = toml::from_str(
+            r#"
+            addr = "pool.ntp.org:123"
+            type = "udp"
+
+            [udp]
+            mode = "ntp"
+            max_clock_offset_ms = 500
+            "#,
+        )
+        .unwrap();
+
+        let raw = raw.section::<UdpConfig>().unwrap().unwrap();
+        let ntp = ntp.section::<UdpConfig>().unwrap().unwrap();
+
+        assert_eq!(
+            raw.payload,
+            Some(UdpPayload::Hex {
+                hex: "ff ff ff ff 54".to_string()
+            })
+        );
+        assert_eq!(raw.mode, UdpMode::Raw);
+        assert_eq!(raw.max_clock_offset_ms, None);
+        assert_eq!(ntp.mode, UdpMode::Ntp);
+        assert_eq!(ntp.max_clock_offset_ms, Some(500));
+    }
+
+    #[test]
+    fn udp_payload_bytes() {
+        let text = UdpPayload::Text("ping".to_string());
+        let hex = |hex: &str| UdpPayload::Hex {
+            hex: hex.to_string(),
+        };
+
+        assert_eq!(text.bytes().unwrap(), b"ping");
+        assert_eq!(hex("ff 00 1A").bytes().unwrap(), vec![0xff, 0x00, 0x1a]);
+        assert!(
+            hex("fff")
+                .bytes()
+                .unwrap_err()
+                .contains("odd number of digits")
+        );
+        assert!(
+            hex("zz")
+                .bytes()
+                .unwrap_err()
+                .contains("invalid hex payload")
+        );
+    }
+
+    /// UDP server answering every datagram with `reply(request)`
+    async fn spawn_udp_server(reply: fn(&[u8]) -> Vec<u8>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let _ = socket.send_to(&reply(&buf[..len]), peer).await;
+            }
+        });
+        addr
+    }
+
+    /// Port with nothing listening on it
+    async fn closed_port() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.local_addr().unwrap()
+    }
+
+    /// NTP server reply whose clock is 1.5s ahead of the local clock
+    fn ntp_reply(request: &[u8]) -> Vec<u8> {
+        let ahead = ntp_timestamp(SystemTime::now() + Duration::from_millis(1500));
+        let mut reply = vec![0; NTP_PACKET_SIZE];
+        reply[0] = 0b00_100_100;
+        reply[1] = 2;
+        reply[24..32].copy_from_slice(&request[40..48]);
+        reply[32..40].copy_from_slice(&ahead.to_be_bytes());
+        reply[40..48].copy_from_slice(&ahead.to_be_bytes());
+        reply
+    }
+
+    #[tokio::test]
+    async fn check_udp_matches_reply() {
+        let addr = spawn_udp_server(|request| [b"pong: ", request].concat()).await;
+        let endpoint = make_endpoint(addr);
+        let config = UdpConfig {
+            payload: Some(UdpPayload::Text("ping".to_string())),
+            expected_reply: Some("^pong: ping$".to_string()),
+            ..UdpConfig::default()
+        };
+
+        let result = check_udp("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_udp_sends_hex_payload() {
+        let addr = spawn_udp_server(|request| {
+            if request == [0xff, 0xff, 0xff, 0xff] {
+                b"ok".to_vec()
+            } else {
+                b"bad".to_vec()
+            }
+        })
+        .await;
+        let endpoint = make_endpoint(addr);
+        let config = UdpConfig {
+            payload: Some(UdpPayload::Hex {
+                hex: "ff ff ff ff".to_string(),
+            }),
+            expected_reply: Some("ok".to_string()),
+            ..UdpConfig::default()
+        };
+
+        let result = check_udp("test", &endpoint, &config).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_udp_fails_on_reply_mismatch() {
+        let addr = spawn_udp_server(|_| b"ERR".to_vec()).await;
+        let endpoint = make_endpoint(addr);
+        let config = UdpConfig {
+            expected_reply: Some("^OK".to_string()),
+            ..UdpConfig::default()
+        };
+
+        let result = check_udp("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::ReplyMismatch));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("reply did not match '^OK': ERR")
+        );
+    }
+
+    #[tokio::test]
+    async fn check_udp_times_out_without_reply() {
+        // Bound but never read, so there is neither a reply nor a port unreachable error
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = make_endpoint(silent.local_addr().unwrap());
+        let config = UdpConfig {
+            expected_reply: Some(String::new()),
+            ..UdpConfig::default()
+        };
+
+        let result = check_udp("test", &endpoint, &config).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::Timeout));
+        assert_eq!(result.error.as_deref(), Some("no reply within 1s"));
+    }
+
+    #[tokio::test]
+    async fn check_udp_is_up_without_expected_reply() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = make_endpoint(silent.local_addr().unwrap());
+
+        let result = check_udp("test", &endpoint, &UdpConfig::default()).await;
+
+        assert!(result.is_up(), "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn check_udp_reports_unreachable_port() {
+        let endpoint = make_endpoint(closed_port().await);
+
+        let result = check_udp("test", &endpoint, &UdpConfig::default()).await;
+
+        assert_eq!(result.error_type, Some(ErrorType::PortUnreachable));
+    }
+
+    #[tokio::test]
+    async fn check_udp_reports_ntp_clock_offset() {
+        let addr = spawn_udp_server(ntp_reply).await;
+        let endpoint = make_endpoint(addr);
+        let config = UdpConfig {
+            mode: UdpMode::Ntp,
+            max_clock_offset_ms: Some(1000),
+            ..UdpConfig::default()
+        };
+
+        let result = check_udp("test", &endpoint, &config).await;
+
+        assert_eq!(result.status, CheckStatus::Degraded, "{:?}", result.error);
+        let offset = result.clock_offset_ms.unwrap();
+        assert!((1400..=1500).contains(&offset), "{offset}");
+        assert!(result.warnings[0].contains("exceeds max_clock_offset_ms (1000ms)"));
+    }
+
+    #[test]
+    fn clock_offset_rejects_invalid_replies() {
+        let originate = ntp_timestamp(SystemTime::now());
+        let mut reply = [0; NTP_PACKET_SIZE];
+        reply[0] = 0b00_100_100;
+        reply[1] = 0;
+        reply[12..16].copy_from_slice(b"RATE");
+
+        assert_eq!(
+            clock_offset(&reply, originate, originate),
+            Err((
+                ErrorType::NtpInvalid,
+                "NTP server sent kiss code RATE".to_string()
+            ))
+        );
+
+        reply[1] = 1;
+        assert_eq!(
+            clock_offset(&reply, originate, originate).unwrap_err().1,
+            "NTP reply does not answer this request"
+        );
+        assert_eq!(
+            clock_offset(&reply[..10], originate, originate)
+                .unwrap_err()
+                .1,
+            "NTP reply too short (10 bytes)"
+        );
+    }
+
+    #[test]
+    fn ntp_timestamps_round_trip() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_760_000_000_250);
+
+        assert_eq!(
+            ntp_to_nanos(ntp_timestamp(time)) / 1_000_000,
+            1_760_000_000_250
+        );
+    }
+}
@@ -6,7 +6,7 @@ use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use ulid::Ulid;
 
-use crate::checker::CheckResult;
+use crate::checker::{CheckResult, CheckStatus, ContentChange, ContentSample, HttpTimings};
 
 pub async fn connect_from_env() -> Result<Option<PgPool>> {
     let database_url = match std::env::var("DATABASE_URL") {
@@ -60,33 +60,54 @@ pub fn endpoint_id_from_name(name: &str) -> String {
     ulid.to_string()
 }
 
-/// Insert a check result as an uptime event
+/// Convert a duration in milliseconds to an `int` column value
+fn to_db_ms(ms: u64) -> i32 {
+    i32::try_from(ms).unwrap_or(i32::MAX)
+}
+
+/// Insert a check result as an uptime event. Results outside an endpoint's active
+/// hours are not recorded, so they don't count toward its uptime. Results an agent
+/// reports again after a lost response are recorded once.
 pub async fn insert_uptime_event(pool: &PgPool, result: &CheckResult) -> Result<()> {
+    if result.status == CheckStatus::NotMonitored {
+        return Ok(());
+    }
+
     let endpoint_id = endpoint_id_from_name(&result.name);
-    let ts = Utc::now();
+    let ts = result.checked_at;
     let status_code = result.status_code.map(i32::from);
-    let latency_ms = result
-        .response_time_ms
-        .map(|l| i32::try_from(l).unwrap_or(i32::MAX));
+    let latency_ms = result.response_time_ms.map(to_db_ms);
     let error_type = result
         .error_type
         .as_ref()
         .map(crate::checker::ErrorType::as_str);
     let error_message = result.error.as_deref();
+    let timings = result.timings.unwrap_or_default();
 
     sqlx::query(
         r"
-        INSERT INTO uptime_events (endpoint_id, ts, status_code, success, latency_ms, error_type, error_message)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO uptime_events (
+            endpoint_id, ts, status_code, success, status, latency_ms, error_type, error_message,
+            dns_ms, connect_ms, tls_ms, ttfb_ms, download_ms, location
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (endpoint_id, ts, location) DO NOTHING
         ",
     )
     .bind(&endpoint_id)
     .bind(ts)
     .bind(status_code)
-    .bind(result.is_up)
+    .bind(result.is_up())
+    .bind(result.status.as_str())
     .bind(latency_ms)
     .bind(error_type)
     .bind(error_message)
+    .bind(timings.dns.map(to_db_ms))
+    .bind(timings.connect.map(to_db_ms))
+    .bind(timings.tls.map(to_db_ms))
+    .bind(timings.ttfb.map(to_db_ms))
+    .bind(timings.download.map(to_db_ms))
+    .bind(result.location.as_deref())
     .execute(pool)
     .await
     .wrap_err("failed to insert uptime event")?;
@@ -176,6 +197,8 @@ impl TimeRange {
 pub enum BucketStatus {
     /// All checks succeeded
     Green,
+    /// All checks succeeded, some of them degraded
+    Degraded,
     /// Mix of success and failure
     Yellow,
     /// All checks failed
@@ -188,6 +211,7 @@ impl BucketStatus {
     pub fn css_class(self) -> &'static str {
         match self {
             BucketStatus::Green => "bg-green-500",
+            BucketStatus::Degraded => "bg-yellow-300",
             BucketStatus::Yellow => "bg-yellow-500",
             BucketStatus::Red => "bg-red-500",
             BucketStatus::Gray => "bg-gray-300",
@@ -199,7 +223,7 @@ impl BucketStatus {
 #[derive(Debug, Clone)]
 pub struct UptimeEvent {
     pub ts: DateTime<Utc>,
-    pub success: bool,
+    pub status: CheckStatus,
 }
 
 /// Row type for uptime events query
@@ -207,9 +231,13 @@ pub struct UptimeEvent {
 struct UptimeEventRow {
     ts: DateTime<Utc>,
     success: bool,
+    /// Missing for events recorded before the status column existed
+    status: Option<String>,
 }
 
-/// Get uptime events for an endpoint within a time range
+/// Get uptime events for an endpoint within a time range. Only this instance's
+/// results count: with a quorum they hold the decision across all locations, while
+/// agent results are kept per location.
 pub async fn get_uptime_events(
     pool: &PgPool,
     endpoint_name: &str,
@@ -220,9 +248,9 @@ pub async fn get_uptime_events(
 
     let rows: Vec<UptimeEventRow> = sqlx::query_as(
         r"
-        SELECT ts, success
+        SELECT ts, success, status
         FROM uptime_events
-        WHERE endpoint_id = $1 AND ts >= $2
+        WHERE endpoint_id = $1 AND ts >= $2 AND location IS NULL
         ORDER BY ts ASC
         ",
     )
@@ -236,7 +264,15 @@ pub async fn get_uptime_events(
         .into_iter()
         .map(|r| UptimeEvent {
             ts: r.ts,
-            success: r.success,
+            status: r
+                .status
+                .as_deref()
+                .and_then(CheckStatus::from_str)
+                .unwrap_or(if r.success {
+                    CheckStatus::Up
+                } else {
+                    CheckStatus::Down
+                }),
         })
         .collect())
 }
@@ -257,18 +293,28 @@ pub fn compute_bucket_statuses(events: &[UptimeEvent], range: TimeRange) -> Vec<
         let bucket_start = now - total_duration + bucket_duration * i32::try_from(i).unwrap_or(0);
         let bucket_end = bucket_start + bucket_duration;
 
+        // Unreachable checks say nothing about the endpoint itself
         let bucket_events: Vec<_> = events
             .iter()
             .filter(|e| e.ts >= bucket_start && e.ts < bucket_end)
+            .filter(|e| e.status != CheckStatus::Unreachable)
             .collect();
 
         if bucket_events.is_empty() {
             *bucket = BucketStatus::Gray;
         } else {
-            let successes = bucket_events.iter().filter(|e| e.success).count();
+            let successes = bucket_events
+                .iter()
+                .filter(|e| e.status != CheckStatus::Down)
+                .count();
+            let degraded = bucket_events
+                .iter()
+                .any(|e| matches!(e.status, CheckStatus::Degraded | CheckStatus::PartialOutage));
             let total = bucket_events.len();
 
-            *bucket = if successes == total {
+            *bucket = if successes == total && degraded {
+                BucketStatus::Degraded
+            } else if successes == total {
                 BucketStatus::Green
             } else if successes == 0 {
                 BucketStatus::Red
@@ -298,6 +344,164 @@ pub async fn get_all_endpoint_buckets(
     Ok(result)
 }
 
+/// Timing breakdown of a past HTTP check
+#[derive(Debug, Clone)]
+pub struct TimingSample {
+    pub ts: DateTime<Utc>,
+    pub timings: HttpTimings,
+}
+
+/// Row type for timing breakdown query
+#[derive(sqlx::FromRow)]
+struct TimingSampleRow {
+    ts: DateTime<Utc>,
+    dns_ms: Option<i32>,
+    connect_ms: Option<i32>,
+    tls_ms: Option<i32>,
+    ttfb_ms: Option<i32>,
+    download_ms: Option<i32>,
+}
+
+/// Get the most recent HTTP timing breakdowns of this instance's checks of an
+/// endpoint, newest first
+pub async fn get_recent_timings(
+    pool: &PgPool,
+    endpoint_name: &str,
+    limit: i64,
+) -> Result<Vec<TimingSample>> {
+    let endpoint_id = endpoint_id_from_name(endpoint_name);
+
+    let rows: Vec<TimingSampleRow> = sqlx::query_as(
+        r"
+        SELECT ts, dns_ms, connect_ms, tls_ms, ttfb_ms, download_ms
+        FROM uptime_events
+        WHERE endpoint_id = $1 AND ttfb_ms IS NOT NULL AND location IS NULL
+        ORDER BY ts DESC
+        LIMIT $2
+        ",
+    )
+    .bind(&endpoint_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to fetch timing breakdowns")?;
+
+    let from_db_ms = |ms: Option<i32>| ms.and_then(|ms| u64::try_from(ms).ok());
+    Ok(rows
+        .into_iter()
+        .map(|r| TimingSample {
+            ts: r.ts,
+            timings: HttpTimings {
+                dns: from_db_ms(r.dns_ms),
+                connect: from_db_ms(r.connect_ms),
+                tls: from_db_ms(r.tls_ms),
+                ttfb: from_db_ms(r.ttfb_ms),
+                download: from_db_ms(r.download_ms),
+            },
+        })
+        .collect())
+}
+
+/// Get the stored content baseline text of an endpoint
+pub async fn get_content_baseline(pool: &PgPool, endpoint_name: &str) -> Result<Option<String>> {
+    let endpoint_id = endpoint_id_from_name(endpoint_name);
+
+    let body: Option<(String,)> =
+        sqlx::query_as("SELECT body FROM content_baselines WHERE endpoint_id = $1")
+            .bind(&endpoint_id)
+            .fetch_optional(pool)
+            .await
+            .wrap_err("failed to fetch content baseline")?;
+
+    Ok(body.map(|(body,)| body))
+}
+
+/// Store the content baseline of an endpoint, replacing the previous one
+pub async fn save_content_baseline(
+    pool: &PgPool,
+    endpoint_name: &str,
+    sample: &ContentSample,
+) -> Result<()> {
+    let endpoint_id = endpoint_id_from_name(endpoint_name);
+
+    sqlx::query(
+        r"
+        INSERT INTO content_baselines (endpoint_id, hash, body, updated_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (endpoint_id)
+        DO UPDATE SET hash = EXCLUDED.hash, body = EXCLUDED.body, updated_at = EXCLUDED.updated_at
+        ",
+    )
+    .bind(&endpoint_id)
+    .bind(&sample.hash)
+    .bind(&sample.text)
+    .bind(Utc::now())
+    .execute(pool)
+    .await
+    .wrap_err("failed to save content baseline")?;
+
+    Ok(())
+}
+
+/// Record a detected content change
+pub async fn insert_content_change(
+    pool: &PgPool,
+    endpoint_name: &str,
+    change: &ContentChange,
+) -> Result<()> {
+    let endpoint_id = endpoint_id_from_name(endpoint_name);
+
+    sqlx::query(
+        r"
+        INSERT INTO content_changes (endpoint_id, ts, old_hash, new_hash, diff)
+        VALUES ($1, $2, $3, $4, $5)
+        ",
+    )
+    .bind(&endpoint_id)
+    .bind(Utc::now())
+    .bind(&change.old_hash)
+    .bind(&change.new_hash)
+    .bind(&change.diff)
+    .execute(pool)
+    .await
+    .wrap_err("failed to insert content change")?;
+
+    Ok(())
+}
+
+/// A past content change of an endpoint
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContentChangeEvent {
+    pub ts: DateTime<Utc>,
+    pub old_hash: String,
+    pub new_hash: String,
+    pub diff: String,
+}
+
+/// Get the most recent content changes of an endpoint, newest first
+pub async fn get_recent_content_changes(
+    pool: &PgPool,
+    endpoint_name: &str,
+    limit: i64,
+) -> Result<Vec<ContentChangeEvent>> {
+    let endpoint_id = endpoint_id_from_name(endpoint_name);
+
+    sqlx::query_as(
+        r"
+        SELECT ts, old_hash, new_hash, diff
+        FROM content_changes
+        WHERE endpoint_id = $1
+        ORDER BY ts DESC
+        LIMIT $2
+        ",
+    )
+    .bind(&endpoint_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to fetch content changes")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -438,6 +642,7 @@ mod tests {
     #[test]
     fn bucket_status_css_class_returns_correct_classes() {
         assert_eq!(BucketStatus::Green.css_class(), "bg-green-500");
+        assert_eq!(BucketStatus::Degraded.css_class(), "bg-yellow-300");
         assert_eq!(BucketStatus::Yellow.css_class(), "bg-yellow-500");
         assert_eq!(BucketStatus::Red.css_class(), "bg-red-500");
         assert_eq!(BucketStatus::Gray.css_class(), "bg-gray-300");
@@ -475,11 +680,11 @@ mod tests {
         let events = vec![
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(1),
-                success: true,
+                status: CheckStatus::Up,
             },
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(2),
-                success: true,
+                status: CheckStatus::Up,
             },
         ];
 
@@ -496,11 +701,11 @@ mod tests {
         let events = vec![
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(1),
-                success: false,
+                status: CheckStatus::Down,
             },
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(2),
-                success: false,
+                status: CheckStatus::Down,
             },
         ];
 
@@ -510,6 +715,37 @@ mod tests {
         assert_eq!(buckets[NUM_BUCKETS - 1], BucketStatus::Red);
     }
 
+    #[test]
+    fn compute_bucket_statuses_degraded_when_no_failures_but_degraded() {
+        let now = Utc::now();
+        let events = vec![
+            UptimeEvent {
+                ts: now - chrono::Duration::seconds(10),
+                status: CheckStatus::Up,
+            },
+            UptimeEvent {
+                ts: now - chrono::Duration::seconds(20),
+                status: CheckStatus::Degraded,
+            },
+        ];
+
+        let buckets = compute_bucket_statuses(&events, TimeRange::Hour1);
+
+        assert_eq!(buckets[NUM_BUCKETS - 1], BucketStatus::Degraded);
+    }
+
+    #[test]
+    fn compute_bucket_statuses_degraded_for_partial_outage() {
+        let events = vec![UptimeEvent {
+            ts: Utc::now() - chrono::Duration::seconds(10),
+            status: CheckStatus::PartialOutage,
+        }];
+
+        let buckets = compute_bucket_statuses(&events, TimeRange::Hour1);
+
+        assert_eq!(buckets[NUM_BUCKETS - 1], BucketStatus::Degraded);
+    }
+
     #[test]
     fn compute_bucket_statuses_yellow_for_mixed_results() {
         let now = Utc::now();
@@ -518,11 +754,11 @@ mod tests {
         let events = vec![
             UptimeEvent {
                 ts: now - chrono::Duration::seconds(10),
-                success: true,
+                status: CheckStatus::Up,
             },
             UptimeEvent {
                 ts: now - chrono::Duration::seconds(20),
-                success: false,
+                status: CheckStatus::Down,
             },
         ];
 
@@ -538,7 +774,7 @@ mod tests {
         // Create events from ~59 minutes ago (should be in first bucket for 1h range)
         let events = vec![UptimeEvent {
             ts: now - chrono::Duration::minutes(59),
-            success: true,
+            status: CheckStatus::Up,
         }];
 
         let buckets = compute_bucket_statuses(&events, TimeRange::Hour1);
@@ -555,7 +791,7 @@ mod tests {
         // Create events from 2 hours ago (outside 1h range)
         let events = vec![UptimeEvent {
             ts: now - chrono::Duration::hours(2),
-            success: true,
+            status: CheckStatus::Up,
         }];
 
         let buckets = compute_bucket_statuses(&events, TimeRange::Hour1);
@@ -571,7 +807,7 @@ mod tests {
         // Single success
         let success_event = vec![UptimeEvent {
             ts: now - chrono::Duration::seconds(30),
-            success: true,
+            status: CheckStatus::Up,
         }];
         let buckets = compute_bucket_statuses(&success_event, TimeRange::Hour1);
         assert_eq!(buckets[NUM_BUCKETS - 1], BucketStatus::Green);
@@ -579,7 +815,7 @@ mod tests {
         // Single failure
         let failure_event = vec![UptimeEvent {
             ts: now - chrono::Duration::seconds(30),
-            success: false,
+            status: CheckStatus::Down,
         }];
         let buckets = compute_bucket_statuses(&failure_event, TimeRange::Hour1);
         assert_eq!(buckets[NUM_BUCKETS - 1], BucketStatus::Red);
@@ -595,21 +831,21 @@ mod tests {
             // Recent bucket (success)
             UptimeEvent {
                 ts: now - chrono::Duration::seconds(30),
-                success: true,
+                status: CheckStatus::Up,
             },
             // ~15 minutes ago (failure)
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(15),
-                success: false,
+                status: CheckStatus::Down,
             },
             // ~28 minutes ago (mixed)
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(28),
-                success: true,
+                status: CheckStatus::Up,
             },
             UptimeEvent {
                 ts: now - chrono::Duration::minutes(28) + chrono::Duration::seconds(10),
-                success: false,
+                status: CheckStatus::Down,
             },
         ];
 
@@ -630,7 +866,12 @@ mod tests {
         let events: Vec<UptimeEvent> = (0..100)
             .map(|i| UptimeEvent {
                 ts: now - chrono::Duration::seconds(i * 36), // Spread over 3600 seconds
-                success: i % 2 == 0,                         // Alternate success/failure
+                // Alternate success/failure
+                status: if i % 2 == 0 {
+                    CheckStatus::Up
+                } else {
+                    CheckStatus::Down
+                },
             })
             .collect();
 
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
 use maud::{DOCTYPE, Markup, html};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 
-use crate::checker::CheckResult;
-use crate::config::CheckType;
-use crate::db::{BucketStatus, TimeRange};
+use crate::checker::{
+    AddressResult, CertificateInfo, CheckResult, CheckStatus, DomainInfo, HttpTimings,
+    LocationStatus, StepResult,
+};
+use crate::db::{BucketStatus, ContentChangeEvent, TimeRange, TimingSample};
 
 /// Git hash at build time (set by build.rs)
 pub const GIT_HASH: &str = env!("GIT_HASH");
@@ -120,7 +123,7 @@ pub fn dashboard(
                     hx-swap="innerHTML"
                     hx-include="#time-range-select"
                 {
-                    (status_grid_with_buckets(results, buckets, time_range))
+                    (status_grid_with_buckets(results, buckets, time_range, base_path))
                 }
             }
         }
@@ -179,12 +182,13 @@ pub fn status_grid_with_buckets(
     results: &[CheckResult],
     buckets: &HashMap<String, Vec<BucketStatus>>,
     time_range: TimeRange,
+    base_path: &str,
 ) -> Markup {
     html! {
         div class="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-3 gap-6" {
             @for result in results {
                 @let endpoint_buckets = buckets.get(&result.name);
-                (status_card_with_buckets(result, endpoint_buckets, time_range))
+                (status_card_with_buckets(result, endpoint_buckets, time_range, base_path))
             }
         }
 
@@ -201,21 +205,19 @@ fn status_card_with_buckets(
     result: &CheckResult,
     buckets: Option<&Vec<BucketStatus>>,
     time_range: TimeRange,
+    base_path: &str,
 ) -> Markup {
     let display_name = result.description.as_deref().unwrap_or(&result.name);
+    let detail_path = endpoint_path(base_path, &result.name);
 
-    let check_type_label = match result.check_type {
-        CheckType::Http => "HTTP",
-        CheckType::Tcp => "TCP",
-        CheckType::Dns => "DNS",
-    };
+    let check_type_label = result.check_type.as_str().to_ascii_uppercase();
 
     html! {
         div class="bg-white rounded-lg shadow-md p-6 hover:shadow-lg transition-shadow" {
             div class="flex items-center justify-between mb-4" {
                 div class="flex-1 min-w-0" {
                     h2 class="text-lg font-semibold text-gray-800 truncate" title=(display_name) {
-                        (display_name)
+                        a href=(detail_path) class="hover:text-blue-600" { (display_name) }
                     }
                     // Show group if present
                     @if let Some(ref group) = result.group {
@@ -227,7 +229,7 @@ fn status_card_with_buckets(
                     span class="px-2 py-0.5 text-xs font-medium bg-gray-100 text-gray-600 rounded" {
                         (check_type_label)
                     }
-                    (status_indicator(result.is_up))
+                    (status_indicator(result))
                 }
             }
 
@@ -264,11 +266,44 @@ fn status_card_with_buckets(
                     }
                 }
 
+                @if let Some(next_check) = result.next_check_at {
+                    div class="flex justify-between" {
+                        span class="text-gray-500" { "Next check" }
+                        span class="text-gray-700" title=(next_check.format("%Y-%m-%d %H:%M:%S UTC")) {
+                            (next_check_label(next_check))
+                        }
+                    }
+                }
+
+                (protocol_details(result))
+
+                @if !result.steps.is_empty() {
+                    (flow_steps(&result.steps))
+                }
+
+                @if !result.addresses.is_empty() {
+                    (address_results(&result.addresses))
+                }
+
+                (location_results(&result.locations))
+
+                @if !result.redirects.is_empty() {
+                    (redirect_chain(&result.addr, &result.redirects))
+                }
+
+                @if !result.warnings.is_empty() {
+                    ul class="mt-3 p-2 bg-yellow-50 rounded text-yellow-700 text-xs space-y-0.5" {
+                        @for warning in &result.warnings {
+                            li { (warning) }
+                        }
+                    }
+                }
+
                 @if let Some(ref error) = result.error {
                     div class="mt-3 p-2 bg-red-50 rounded text-red-600 text-xs" {
                         (error)
                         @if let Some(status) = result.status_code {
-                            @if !result.is_up {
+                            @if !result.is_up() {
                                 " "
                                 a
                                     href=(format!("https://http.cat/{}", status))
@@ -294,6 +329,374 @@ fn status_card_with_buckets(
     }
 }
 
+/// Path of the detail page for an endpoint
+fn endpoint_path(base_path: &str, name: &str) -> String {
+    /// Characters left unescaped in the endpoint name path segment
+    const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+        .remove(b'-')
+        .remove(b'_')
+        .remove(b'.')
+        .remove(b'~');
+
+    asset_path(
+        base_path,
+        &format!("/endpoints/{}", utf8_percent_encode(name, PATH_SEGMENT)),
+    )
+}
+
+/// Detail page for a single endpoint with its HTTP timing breakdown
+pub fn endpoint_detail(
+    result: &CheckResult,
+    history: &[TimingSample],
+    content_changes: &[ContentChangeEvent],
+    base_path: &str,
+) -> Markup {
+    let display_name = result.description.as_deref().unwrap_or(&result.name);
+    let history_scale = history
+        .iter()
+        .map(|sample| sample.timings.total_ms())
+        .max()
+        .unwrap_or_default();
+
+    let content = html! {
+        div class="container mx-auto px-4 py-8 max-w-4xl" {
+            a href=(asset_path(base_path, "/")) class="text-sm text-blue-500 hover:text-blue-700" {
+                "← Dashboard"
+            }
+
+            header class="mt-4 mb-8" {
+                div class="flex items-center gap-3" {
+                    (status_indicator(result))
+                    h1 class="text-3xl font-bold text-gray-800" { (display_name) }
+                }
+                p class="text-gray-600 mt-2 truncate" title=(result.addr) { (result.addr) }
+            }
+
+            section class="bg-white rounded-lg shadow-md p-6 mb-6" {
+                h2 class="text-lg font-semibold text-gray-800 mb-4" { "Latest check" }
+                @if let Some(ref timings) = result.timings {
+                    (timing_bar(timings, timings.total_ms()))
+                    (timing_legend(timings))
+                } @else {
+                    p class="text-sm text-gray-500" {
+                        "No timing breakdown available. Timings are recorded for HTTP checks."
+                    }
+                }
+                @if let Some(ref error) = result.error {
+                    div class="mt-4 p-2 bg-red-50 rounded text-red-600 text-xs" { (error) }
+                }
+                @if !result.locations.is_empty() {
+                    div class="mt-4" {
+                        h3 class="text-sm font-medium text-gray-700" { "Locations" }
+                        (location_results(&result.locations))
+                    }
+                }
+                @if let Some(next_check) = result.next_check_at {
+                    p class="mt-4 text-xs text-gray-500" title=(next_check.format("%Y-%m-%d %H:%M:%S UTC")) {
+                        "Next check " (next_check_label(next_check))
+                    }
+                }
+                @if let Some(ref content) = result.content {
+                    p class="mt-4 text-xs text-gray-500" {
+                        "Content hash: "
+                        span class="font-mono" title=(content.hash) { (short_hash(&content.hash)) }
+                    }
+                }
+            }
+
+            @if !content_changes.is_empty() {
+                section class="bg-white rounded-lg shadow-md p-6 mb-6" {
+                    h2 class="text-lg font-semibold text-gray-800 mb-4" { "Content changes" }
+                    div class="space-y-4" {
+                        @for change in content_changes {
+                            div {
+                                div class="flex items-center gap-3 text-xs text-gray-500 mb-1" {
+                                    span class="font-mono" { (change.ts.format("%m-%d %H:%M:%S")) }
+                                    span class="font-mono" title=(format!("{} -> {}", change.old_hash, change.new_hash)) {
+                                        (short_hash(&change.old_hash)) " → " (short_hash(&change.new_hash))
+                                    }
+                                }
+                                pre class="p-2 bg-gray-50 rounded text-xs text-gray-700 overflow-x-auto" {
+                                    (change.diff)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if !history.is_empty() {
+                section class="bg-white rounded-lg shadow-md p-6" {
+                    h2 class="text-lg font-semibold text-gray-800 mb-4" { "Recent checks" }
+                    div class="space-y-2" {
+                        @for sample in history {
+                            div class="flex items-center gap-3 text-xs" {
+                                span class="w-28 shrink-0 font-mono text-gray-500" {
+                                    (sample.ts.format("%m-%d %H:%M:%S"))
+                                }
+                                div class="flex-1" {
+                                    (timing_bar(&sample.timings, history_scale))
+                                }
+                                span class="w-16 shrink-0 text-right text-gray-600" {
+                                    (sample.timings.total_ms()) "ms"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    base(
+        &format!("Uptime Forge - {display_name}"),
+        &content,
+        base_path,
+    )
+}
+
+/// Abbreviated content hash for display
+fn short_hash(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
+
+/// Bar colors for the phases of `HttpTimings::phases`, in the same order
+const PHASE_COLORS: [&str; 5] = [
+    "bg-purple-400",
+    "bg-blue-400",
+    "bg-green-400",
+    "bg-yellow-400",
+    "bg-gray-400",
+];
+
+/// Stacked bar of HTTP phase timings, where `scale_ms` fills the full width
+fn timing_bar(timings: &HttpTimings, scale_ms: u64) -> Markup {
+    let scale_ms = scale_ms.max(1);
+
+    html! {
+        div class="flex h-3 w-full rounded-sm overflow-hidden bg-gray-100" {
+            @for ((label, ms), color) in timings.phases().into_iter().zip(PHASE_COLORS) {
+                @if let Some(ms) = ms {
+                    // Width in hundredths of a percent
+                    @let width = ms.saturating_mul(10_000) / scale_ms;
+                    div
+                        class=(color)
+                        style=(format!("width: {}.{:02}%", width / 100, width % 100))
+                        title=(format!("{label}: {ms}ms"))
+                    {}
+                }
+            }
+        }
+    }
+}
+
+/// Legend with the duration of each recorded phase
+fn timing_legend(timings: &HttpTimings) -> Markup {
+    html! {
+        ul class="mt-4 grid grid-cols-2 sm:grid-cols-5 gap-2 text-sm" {
+            @for ((label, ms), color) in timings.phases().into_iter().zip(PHASE_COLORS) {
+                li class="flex items-center gap-2" {
+                    span class={"inline-block w-3 h-3 rounded-sm " (color)} {}
+                    span class="text-gray-500" { (label) }
+                    span class="text-gray-700 ml-auto" {
+                        @if let Some(ms) = ms { (ms) "ms" } @else { "–" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Per-step timings and outcome of a flow check
+fn flow_steps(steps: &[StepResult]) -> Markup {
+    html! {
+        div class="mt-2 pt-2 border-t border-gray-100 space-y-1" {
+            @for step in steps {
+                div class="flex justify-between items-center text-xs" {
+                    span class="flex items-center gap-1 text-gray-600" {
+                        @if step.error.is_some() {
+                            span class="inline-block w-2 h-2 rounded-full bg-red-500" {}
+                        } @else {
+                            span class="inline-block w-2 h-2 rounded-full bg-green-500" {}
+                        }
+                        (step.name)
+                    }
+                    span class="text-gray-500" {
+                        @if let Some(status) = step.status_code {
+                            (status) " · "
+                        }
+                        @if let Some(ms) = step.response_time_ms {
+                            (ms) "ms"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Outcome of each checked address when specific or all resolved addresses are checked
+fn address_results(addresses: &[AddressResult]) -> Markup {
+    html! {
+        div class="mt-2 pt-2 border-t border-gray-100 space-y-1" {
+            @for address in addresses {
+                div class="flex justify-between items-center text-xs" title=[address.error.as_deref()] {
+                    span class="flex items-center gap-1 text-gray-600 font-mono truncate" {
+                        span class={"inline-block w-2 h-2 rounded-full " (status_dot_color(address.status))} {}
+                        (address.addr)
+                    }
+                    span class="text-gray-500" {
+                        @if let Some(ms) = address.response_time_ms {
+                            (ms) "ms"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Latest status of the endpoint from each location that checks it, nothing if
+/// no agents report on the endpoint
+fn location_results(locations: &[LocationStatus]) -> Markup {
+    if locations.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        div class="mt-2 pt-2 border-t border-gray-100 space-y-1" {
+            @for location in locations {
+                div class="flex justify-between items-center text-xs" title=[location.error.as_deref()] {
+                    span class="flex items-center gap-1 text-gray-600 truncate" {
+                        span class={"inline-block w-2 h-2 rounded-full " (status_dot_color(location.status))} {}
+                        (location.location)
+                    }
+                    span class="text-gray-500" title=(location.checked_at.format("%Y-%m-%d %H:%M:%S UTC")) {
+                        @if let Some(ms) = location.response_time_ms {
+                            (ms) "ms"
+                        } @else {
+                            (location.status.as_str())
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Color of the small status dot of an address or location
+fn status_dot_color(status: CheckStatus) -> &'static str {
+    match status {
+        CheckStatus::Up => "bg-green-500",
+        CheckStatus::Degraded => "bg-yellow-500",
+        CheckStatus::Down => "bg-red-500",
+        CheckStatus::NotMonitored => "bg-gray-400",
+        CheckStatus::Unreachable => "bg-orange-400",
+        CheckStatus::PartialOutage => "bg-purple-400",
+    }
+}
+
+/// Rows specific to a check type: clock offset, round trip, host key, script
+/// message and metrics, domain registration and certificate
+fn protocol_details(result: &CheckResult) -> Markup {
+    html! {
+        @if let Some(offset) = result.clock_offset_ms {
+            div class="flex justify-between" {
+                span class="text-gray-500" { "Clock offset" }
+                span class="text-gray-700" { (format!("{offset:+}")) "ms" }
+            }
+        }
+        @if let Some(round_trip) = result.round_trip_ms {
+            div class="flex justify-between" {
+                span class="text-gray-500" { "Round trip" }
+                span class="text-gray-700" { (round_trip) "ms" }
+            }
+        }
+        @if let Some(ref host_key) = result.host_key {
+            div class="flex justify-between" {
+                span class="text-gray-500" { "Host key" }
+                span class="text-gray-700 font-mono truncate ml-2 max-w-[200px]" title=(host_key) { (host_key) }
+            }
+        }
+        @if let Some(ref message) = result.message {
+            div class="flex justify-between" {
+                span class="text-gray-500" { "Message" }
+                span class="text-gray-700 truncate ml-2 max-w-[200px]" title=(message) { (message) }
+            }
+        }
+        @for (name, value) in &result.metrics {
+            div class="flex justify-between" {
+                span class="text-gray-500 truncate" title=(name) { (name) }
+                span class="text-gray-700" { (value) }
+            }
+        }
+        @if let Some(ref domain) = result.domain {
+            (domain_registration(domain))
+        }
+        @if let Some(ref certificate) = result.certificate {
+            (certificate_details(certificate))
+        }
+    }
+}
+
+/// Name and expiry of the certificate a server presented
+fn certificate_details(certificate: &CertificateInfo) -> Markup {
+    let days_left = (certificate.not_after - chrono::Utc::now()).num_days();
+    let name = certificate
+        .dns_names
+        .first()
+        .unwrap_or(&certificate.subject);
+    let title = format!("{}\nIssued by {}", certificate.subject, certificate.issuer);
+
+    html! {
+        div class="flex justify-between" {
+            span class="text-gray-500" { "Certificate" }
+            span class="text-gray-700 truncate ml-2 max-w-[200px]" title=(title) { (name) }
+        }
+        div class="flex justify-between" {
+            span class="text-gray-500" { "Cert expires" }
+            span class="text-gray-700" {
+                (certificate.not_after.format("%Y-%m-%d")) " (" (days_left) " days)"
+            }
+        }
+    }
+}
+
+/// Expiry date and registrar of a domain check
+fn domain_registration(domain: &DomainInfo) -> Markup {
+    let days_left = (domain.expires - chrono::Utc::now()).num_days();
+
+    html! {
+        div class="flex justify-between" {
+            span class="text-gray-500" { "Expires" }
+            span class="text-gray-700" title=(format!("via {}", domain.source.to_uppercase())) {
+                (domain.expires.format("%Y-%m-%d")) " (" (days_left) " days)"
+            }
+        }
+        @if let Some(ref registrar) = domain.registrar {
+            div class="flex justify-between" {
+                span class="text-gray-500" { "Registrar" }
+                span class="text-gray-700 truncate ml-2 max-w-[200px]" title=(registrar) { (registrar) }
+            }
+        }
+    }
+}
+
+/// Redirect chain of the last HTTP check, starting at the endpoint address
+fn redirect_chain(addr: &str, redirects: &[String]) -> Markup {
+    html! {
+        div class="mt-2 pt-2 border-t border-gray-100 space-y-1 text-xs" {
+            span class="text-gray-500" { "Redirects (" (redirects.len()) ")" }
+            ol class="space-y-0.5 text-gray-600" {
+                li class="truncate" title=(addr) { (addr) }
+                @for url in redirects {
+                    li class="truncate" title=(url) { "→ " (url) }
+                }
+            }
+        }
+    }
+}
+
 /// Status pills showing uptime history
 fn status_pills(buckets: Option<&Vec<BucketStatus>>, time_range: TimeRange) -> Markup {
     html! {
@@ -318,9 +721,46 @@ fn status_pills(buckets: Option<&Vec<BucketStatus>>, time_range: TimeRange) -> M
     }
 }
 
+/// Time until a scheduled check, e.g. "in 42s"
+fn next_check_label(next_check: chrono::DateTime<chrono::Utc>) -> String {
+    let seconds = (next_check - chrono::Utc::now()).num_seconds();
+    match seconds {
+        ..=0 => "due now".to_string(),
+        1..120 => format!("in {seconds}s"),
+        120..7200 => format!("in {}m", seconds / 60),
+        _ => format!("in {}h", seconds / 3600),
+    }
+}
+
 /// Pulsing status indicator dot
-fn status_indicator(is_up: bool) -> Markup {
-    let (bg_color, pulse_color) = if is_up {
+fn status_indicator(result: &CheckResult) -> Markup {
+    if result.status == CheckStatus::NotMonitored {
+        return html! {
+            span class="relative flex h-3 w-3" title="Not monitored" {
+                span class="relative inline-flex rounded-full h-3 w-3 bg-gray-400" {}
+            }
+        };
+    }
+
+    if result.status == CheckStatus::PartialOutage {
+        return html! {
+            span class="flex items-center gap-1" title="Down from fewer locations than the quorum" {
+                span class="px-2 py-0.5 text-xs font-medium bg-purple-100 text-purple-700 rounded" {
+                    "Partial outage"
+                }
+                span class="relative flex h-3 w-3" {
+                    span class="animate-ping absolute inline-flex h-full w-full rounded-full opacity-75 bg-purple-400" {}
+                    span class="relative inline-flex rounded-full h-3 w-3 bg-purple-500" {}
+                }
+            }
+        };
+    }
+
+    let (bg_color, pulse_color) = if result.status == CheckStatus::Unreachable {
+        ("bg-orange-500", "bg-orange-400")
+    } else if result.is_degraded() {
+        ("bg-yellow-500", "bg-yellow-400")
+    } else if result.is_up() {
         ("bg-green-500", "bg-green-400")
     } else {
         ("bg-red-500", "bg-red-400")
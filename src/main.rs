@@ -1,3 +1,4 @@
+mod agent;
 mod checker;
 mod config;
 mod db;
@@ -8,11 +9,11 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::{
-    Router,
-    extract::{Query, State},
-    http::StatusCode,
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::Html,
-    routing::get,
+    routing::{get, post},
 };
 use axum_prometheus::PrometheusMetricLayerBuilder;
 use color_eyre::eyre::{Context, Result};
@@ -30,8 +31,8 @@ use tower_http::{
 use tracing::Level;
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::checker::{CheckResultsState, ReloadTrigger};
-use crate::config::Config;
+use crate::checker::{CheckResultsState, EndpointsState, LocationResults, ReloadTrigger};
+use crate::config::{AgentAuth, Config};
 use crate::db::{BucketStatus, TimeRange};
 
 /// Combined application state
@@ -42,6 +43,14 @@ struct AppState {
     #[allow(dead_code)]
     db_pool: Option<PgPool>,
     base_path: String,
+    /// Agents allowed to report results, by location
+    agents: Arc<HashMap<String, AgentAuth>>,
+    /// Endpoints of the current config, which agents report on
+    endpoints: EndpointsState,
+    /// Latest results reported by agents
+    locations: LocationResults,
+    /// Location of this instance's own checks
+    location: String,
 }
 
 #[tokio::main]
@@ -54,10 +63,17 @@ async fn main() -> Result<()> {
 
     dotenvy::dotenv().ok();
 
+    // Check types are registered before the config is validated against them
+    checker::registry::install_registry(checker::registry::Registry::builtin())?;
+
     // Load configuration
     let config = Config::load("forge.toml")?;
     tracing::info!("loaded {} endpoints", config.endpoints.len());
 
+    if let Some(agent) = config.agent.clone() {
+        return run_agent(config, agent).await;
+    }
+
     let db_pool = db::connect_from_env().await?;
 
     // Set up Prometheus metrics layer (excludes /metrics and /health from tracking)
@@ -86,11 +102,10 @@ async fn main() -> Result<()> {
         // Prometheus metrics (innermost to capture actual request handling time)
         .layer(prometheus_layer);
 
-    // Create shared state for check results
+    // Create shared state for check results and the results reported by agents
     let check_results: CheckResultsState = Arc::default();
-
-    // Perform initial check before starting server
-    checker::initial_check(&config.endpoints, &check_results, db_pool.as_ref()).await;
+    let locations = LocationResults::default();
+    let endpoints = EndpointsState::default();
 
     // Spawn background tasks (endpoint checkers + config reloader)
     let config_path = PathBuf::from("forge.toml");
@@ -98,6 +113,8 @@ async fn main() -> Result<()> {
         config_path,
         config.clone(),
         check_results.clone(),
+        locations.clone(),
+        endpoints.clone(),
         db_pool.clone(),
     )
     .await;
@@ -109,6 +126,14 @@ async fn main() -> Result<()> {
         reload_trigger,
         db_pool,
         base_path: base_path.clone(),
+        agents: Arc::new(config.agents.clone()),
+        endpoints,
+        locations,
+        location: config
+            .server
+            .location
+            .clone()
+            .unwrap_or_else(|| agent::DEFAULT_LOCATION.to_string()),
     };
 
     // Build router with shared state
@@ -119,9 +144,11 @@ async fn main() -> Result<()> {
     let app_routes = Router::new()
         .route("/", get(index))
         .route("/status", get(status))
+        .route("/endpoints/{name}", get(endpoint_detail))
         .route("/reload", get(reload))
         .route("/health", get(health))
         .route("/metrics", get(|| async move { metric_handle.render() }))
+        .route(agent::RESULTS_PATH, post(agent_results))
         .fallback_service(static_files)
         .with_state(app_state);
 
@@ -144,6 +171,44 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Run as a probe agent: check the endpoints and report the results to the central
+/// instance, which stores them. Only `/health` and `/metrics` are served.
+async fn run_agent(config: Config, agent: config::AgentConfig) -> Result<()> {
+    tracing::info!(location = %agent.location, server = %agent.server, "running as agent");
+
+    let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
+        .with_ignore_patterns(&["/metrics", "/health"])
+        .with_default_metrics()
+        .build_pair();
+
+    let check_results: CheckResultsState = Arc::default();
+    checker::spawn_background_tasks(
+        PathBuf::from("forge.toml"),
+        config.clone(),
+        check_results.clone(),
+        LocationResults::default(),
+        EndpointsState::default(),
+        None,
+    )
+    .await;
+    agent::spawn_reporter(agent, check_results);
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(|| async move { metric_handle.render() }))
+        .layer(prometheus_layer);
+
+    let listener = tokio::net::TcpListener::bind(config.server.addr)
+        .await
+        .wrap_err("failed to bind to address")?;
+
+    tracing::info!("listening on {}", config.server.addr);
+
+    axum::serve(listener, app).await.wrap_err("server error")?;
+
+    Ok(())
+}
+
 fn init_tracing() {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info,tower_http=info"));
@@ -209,7 +274,12 @@ async fn index(
     State(state): State<AppState>,
     Query(params): Query<StatusQuery>,
 ) -> (StatusCode, Html<String>) {
-    let results = checker::get_sorted_results(&state.check_results).await;
+    let mut results = checker::get_sorted_results(&state.check_results).await;
+    agent::attach_locations(
+        &mut results,
+        &*state.locations.read().await,
+        &state.location,
+    );
     let time_range = params
         .range
         .as_deref()
@@ -245,7 +315,12 @@ async fn status(
     State(state): State<AppState>,
     Query(params): Query<StatusQuery>,
 ) -> (StatusCode, Html<String>) {
-    let results = checker::get_sorted_results(&state.check_results).await;
+    let mut results = checker::get_sorted_results(&state.check_results).await;
+    agent::attach_locations(
+        &mut results,
+        &*state.locations.read().await,
+        &state.location,
+    );
     let time_range = params
         .range
         .as_deref()
@@ -257,7 +332,10 @@ async fn status(
     match get_buckets(state.db_pool.as_ref(), &endpoint_names, time_range).await {
         BucketResult::Success(buckets) => (
             StatusCode::OK,
-            Html(layout::status_grid_with_buckets(&results, &buckets, time_range).into_string()),
+            Html(
+                layout::status_grid_with_buckets(&results, &buckets, time_range, &state.base_path)
+                    .into_string(),
+            ),
         ),
         BucketResult::DbError(_) => (
             StatusCode::SERVICE_UNAVAILABLE,
@@ -266,6 +344,66 @@ async fn status(
     }
 }
 
+/// Number of past checks shown in the timing history of the endpoint detail page
+const TIMING_HISTORY: i64 = 30;
+
+/// Number of content changes shown on the endpoint detail page
+const CONTENT_HISTORY: i64 = 10;
+
+/// Detail page for a single endpoint with its timing breakdown
+async fn endpoint_detail(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Html<String>) {
+    let Some(mut result) = state.check_results.read().await.get(&name).cloned() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Html(
+                layout::error_page(
+                    404,
+                    "Endpoint Not Found",
+                    &format!("No endpoint named '{name}' is configured."),
+                    &state.base_path,
+                )
+                .into_string(),
+            ),
+        );
+    };
+
+    agent::attach_locations(
+        std::slice::from_mut(&mut result),
+        &*state.locations.read().await,
+        &state.location,
+    );
+
+    let (history, content_changes) = match state.db_pool {
+        Some(ref pool) => {
+            let history = db::get_recent_timings(pool, &name, TIMING_HISTORY)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::warn!(endpoint = %name, error = %e, "failed to fetch timing history");
+                    Vec::new()
+                });
+            let content_changes = db::get_recent_content_changes(pool, &name, CONTENT_HISTORY)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::warn!(endpoint = %name, error = %e, "failed to fetch content changes");
+                    Vec::new()
+                });
+            (history, content_changes)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    (
+        StatusCode::OK,
+        Html(
+            layout::endpoint_detail(&result, &history, &content_changes, &state.base_path)
+                .into_string(),
+        ),
+    )
+}
+
 /// Trigger config reload and re-check all endpoints
 async fn reload(State(state): State<AppState>) -> StatusCode {
     if state.reload_trigger.send(()).await.is_ok() {
@@ -275,6 +413,37 @@ async fn reload(State(state): State<AppState>) -> StatusCode {
     }
 }
 
+/// Accept the results of a probe agent, record them for the dashboard and store them
+async fn agent_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(report): Json<agent::Report>,
+) -> StatusCode {
+    if agent::authorize(&state.agents, &headers, &report.location).is_err() {
+        tracing::warn!(location = %report.location, "rejected agent report with invalid token");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let results = agent::apply_report(report, &*state.endpoints.read().await);
+    agent::store_locations(&state.locations, &results).await;
+
+    if let Some(ref pool) = state.db_pool {
+        for result in &results {
+            if let Err(e) = db::insert_uptime_event(pool, result).await {
+                tracing::error!(
+                    endpoint = %result.name,
+                    location = ?result.location,
+                    error = %e,
+                    "failed to insert agent result"
+                );
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+        }
+    }
+
+    StatusCode::NO_CONTENT
+}
+
 async fn health() -> &'static str {
     "ok"
 }